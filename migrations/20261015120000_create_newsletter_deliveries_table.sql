@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE newsletter_deliveries
+(
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email    TEXT NOT NULL,
+    status              TEXT NOT NULL,
+    delivered_at        timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
@@ -0,0 +1,2 @@
+-- Tasks are only picked up by the delivery worker once `execute_after` is in the past.
+ALTER TABLE issue_delivery_queue ADD COLUMN execute_after timestamptz NOT NULL DEFAULT now();
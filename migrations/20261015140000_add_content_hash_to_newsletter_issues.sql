@@ -0,0 +1,3 @@
+-- Used to detect identical issues published in short succession. NULL for issues published before
+-- the column was introduced.
+ALTER TABLE newsletter_issues ADD COLUMN content_hash TEXT NULL;
@@ -0,0 +1,2 @@
+-- How many times the saved response has been returned to a retried request.
+ALTER TABLE idempotency ADD COLUMN replay_count INTEGER NOT NULL DEFAULT 0;
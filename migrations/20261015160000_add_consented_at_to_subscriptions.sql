@@ -0,0 +1,2 @@
+-- When the subscriber explicitly consented to receive our newsletter, if they did.
+ALTER TABLE subscriptions ADD COLUMN consented_at timestamptz NULL;
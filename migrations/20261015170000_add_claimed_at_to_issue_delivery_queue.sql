@@ -0,0 +1,3 @@
+-- Set when a worker picks up the task. Tasks whose worker crashed mid-send stay claimed until they
+-- are reconciled.
+ALTER TABLE issue_delivery_queue ADD COLUMN claimed_at timestamptz NULL;
@@ -0,0 +1,2 @@
+-- Rows enqueued before this migration get the time it ran.
+ALTER TABLE issue_delivery_queue ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
@@ -0,0 +1,10 @@
+-- Append-only: rows are never updated nor deleted.
+CREATE TABLE subscription_events(
+    event_id BIGSERIAL PRIMARY KEY,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id),
+    event_type TEXT NOT NULL,
+    source TEXT NOT NULL,
+    occurred_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX subscription_events_subscriber_id_idx ON subscription_events (subscriber_id);
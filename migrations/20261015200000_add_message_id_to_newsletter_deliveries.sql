@@ -0,0 +1,2 @@
+-- The `Message-ID` of the email we sent, to correlate bounces with deliveries.
+ALTER TABLE newsletter_deliveries ADD COLUMN message_id TEXT NULL;
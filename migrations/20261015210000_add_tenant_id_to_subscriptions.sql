@@ -0,0 +1,6 @@
+-- The newsletter each subscriber signed up to, when several are served from one deployment.
+-- Existing subscribers belong to the default tenant - see `tenant::DEFAULT_TENANT`.
+ALTER TABLE subscriptions ADD COLUMN tenant_id TEXT NOT NULL DEFAULT 'default';
+-- The same address can subscribe to more than one tenant.
+ALTER TABLE subscriptions DROP CONSTRAINT subscriptions_email_key;
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_tenant_id_email_key UNIQUE (tenant_id, email);
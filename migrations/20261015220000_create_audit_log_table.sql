@@ -0,0 +1,11 @@
+-- Append-only: rows are never updated nor deleted.
+CREATE TABLE audit_log(
+    event_id BIGSERIAL PRIMARY KEY,
+    user_id uuid NOT NULL
+        REFERENCES users (user_id),
+    action TEXT NOT NULL,
+    -- What the action was performed on, if anything - e.g. the id of a newsletter issue.
+    subject TEXT NULL,
+    occurred_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX audit_log_occurred_at_idx ON audit_log (occurred_at);
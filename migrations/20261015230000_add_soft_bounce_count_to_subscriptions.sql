@@ -0,0 +1,2 @@
+-- Soft bounces received for the subscriber: they are suppressed once it reaches the threshold.
+ALTER TABLE subscriptions ADD COLUMN soft_bounce_count INTEGER NOT NULL DEFAULT 0;
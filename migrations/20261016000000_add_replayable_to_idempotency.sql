@@ -0,0 +1,2 @@
+-- Responses too large to be stored are not replayable: retried requests are processed again.
+ALTER TABLE idempotency ADD COLUMN replayable BOOLEAN NOT NULL DEFAULT true;
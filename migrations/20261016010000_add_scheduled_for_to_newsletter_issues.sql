@@ -0,0 +1,3 @@
+-- Issues scheduled for later are only delivered from `scheduled_for` onwards. NULL if the issue went
+-- out as soon as it was published.
+ALTER TABLE newsletter_issues ADD COLUMN scheduled_for timestamptz NULL;
@@ -0,0 +1,2 @@
+-- The subscriber's BCP 47 language tag (e.g. `fr-ca`), lowercased. NULL if unknown.
+ALTER TABLE subscriptions ADD COLUMN locale TEXT NULL;
@@ -0,0 +1,12 @@
+-- Confirmation emails that failed to send, to be retried by the delivery worker. `email` is the
+-- rendered email, as JSON.
+CREATE TABLE confirmation_email_queue(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    recipient TEXT NOT NULL,
+    email TEXT NOT NULL,
+    n_retries INT NOT NULL DEFAULT 0,
+    execute_after timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (subscriber_id)
+);
+-- Set once the confirmation email could not be sent after every retry: support has to step in.
+ALTER TABLE subscriptions ADD COLUMN confirmation_failed BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,8 @@
+-- Overrides of the feature flag defaults set in the configuration, e.g. to turn a feature off in
+-- production without a deployment.
+CREATE TABLE feature_flags(
+    name TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (name)
+);
@@ -0,0 +1,3 @@
+-- Where subscribers came from, if they told us.
+ALTER TABLE subscriptions ADD COLUMN source TEXT NULL;
+ALTER TABLE subscriptions ADD COLUMN utm_campaign TEXT NULL;
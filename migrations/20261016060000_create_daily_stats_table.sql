@@ -0,0 +1,11 @@
+-- Newsletter activity rolled up per day (UTC), for the dashboard not to scan the raw tables.
+-- Rewritten by the stats aggregator as long as the day is not over.
+CREATE TABLE daily_stats(
+    day DATE NOT NULL,
+    sent BIGINT NOT NULL,
+    opened BIGINT NOT NULL DEFAULT 0,
+    clicked BIGINT NOT NULL DEFAULT 0,
+    bounced BIGINT NOT NULL,
+    aggregated_at timestamptz NOT NULL,
+    PRIMARY KEY (day)
+);
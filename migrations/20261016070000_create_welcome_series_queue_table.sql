@@ -0,0 +1,10 @@
+-- The steps of the welcome series each confirmed subscriber is due, one row per step: `step` is the
+-- position of the email in the configured series, starting at 1. `sent_at` is set once the step is
+-- done with - sent, or given up on - hence each step goes out at most once.
+CREATE TABLE welcome_series_queue(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    step INT NOT NULL,
+    execute_after timestamptz NOT NULL,
+    sent_at timestamptz NULL,
+    PRIMARY KEY (subscriber_id, step)
+);
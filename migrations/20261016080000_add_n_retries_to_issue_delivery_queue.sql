@@ -0,0 +1,2 @@
+-- How many times the delivery has been deferred after a transient failure.
+ALTER TABLE issue_delivery_queue ADD COLUMN n_retries INT NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- The tenant an issue has been published to: it is only delivered to that tenant's subscribers.
+-- Existing issues belong to the default tenant - see `tenant::DEFAULT_TENANT`.
+ALTER TABLE newsletter_issues ADD COLUMN tenant_id TEXT NOT NULL DEFAULT 'default';
@@ -0,0 +1,3 @@
+-- The stats aggregator scans a day's worth of deliveries and subscription events at a time.
+CREATE INDEX newsletter_deliveries_delivered_at_idx ON newsletter_deliveries (delivered_at);
+CREATE INDEX subscription_events_occurred_at_idx ON subscription_events (occurred_at);
@@ -0,0 +1,2 @@
+-- The subscriber's IANA time zone (e.g. `Europe/Paris`), for quiet hours. NULL if unknown.
+ALTER TABLE subscriptions ADD COLUMN time_zone TEXT NULL;
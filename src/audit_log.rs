@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+/// # Audit Log
+/// What admins did, and when, is appended to `audit_log` for security teams to review - see
+/// `GET /admin/audit/export.jsonl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    PasswordChanged,
+    NewsletterPublished,
+    WorkerPaused,
+    WorkerResumed,
+    SubscribersCleaned,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PasswordChanged => "password_changed",
+            Self::NewsletterPublished => "newsletter_published",
+            Self::WorkerPaused => "worker_paused",
+            Self::WorkerResumed => "worker_resumed",
+            Self::SubscribersCleaned => "subscribers_cleaned",
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct AuditEntry {
+    pub event_id: i64,
+    pub user_id: Uuid,
+    pub action: String,
+    pub subject: Option<String>,
+    // RFC 3339.
+    pub occurred_at: String,
+}
+
+/// Entries should be recorded in the same transaction as the change they describe, if any.
+#[tracing::instrument(skip(executor))]
+pub async fn record_audit_event(
+    executor: impl PgExecutor<'_>,
+    user_id: Uuid,
+    action: AuditAction,
+    subject: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (user_id, action, subject)
+        VALUES ($1, $2, $3)
+        "#,
+        user_id,
+        action.as_str(),
+        subject
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Up to `limit` entries after `after_event_id`, oldest first, that occurred within `[from, to)`.
+#[tracing::instrument(skip(pool))]
+pub async fn get_audit_entries(
+    pool: &PgPool,
+    after_event_id: i64,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: i64,
+) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT event_id, user_id, action, subject, occurred_at
+        FROM audit_log
+        WHERE
+            event_id > $1 AND
+            ($2::timestamptz IS NULL OR occurred_at >= $2) AND
+            ($3::timestamptz IS NULL OR occurred_at < $3)
+        ORDER BY event_id
+        LIMIT $4
+        "#,
+        after_event_id,
+        from,
+        to,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| AuditEntry {
+            event_id: r.event_id,
+            user_id: r.user_id,
+            action: r.action,
+            subject: r.subject,
+            occurred_at: r.occurred_at.to_rfc3339(),
+        })
+        .collect())
+}
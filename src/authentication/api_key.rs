@@ -0,0 +1,110 @@
+use super::middleware::reject_anonymous_users;
+use crate::utils::e500;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::{web, HttpResponse};
+use actix_web_lab::middleware::Next;
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+
+/// API keys, as SHA-256 digests.
+pub(super) struct ApiKeyDigests(Vec<[u8; 32]>);
+
+impl ApiKeyDigests {
+    pub(super) fn new(keys: &[Secret<String>]) -> Self {
+        Self(keys.iter().map(|k| digest(k.expose_secret())).collect())
+    }
+
+    /// We compare digests rather than the keys themselves: how long the comparison takes tells an
+    /// attacker nothing about how close their guess is to a valid key.
+    pub(super) fn is_valid(&self, key: &str) -> bool {
+        let key = digest(key);
+        self.0.contains(&key)
+    }
+}
+
+fn digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+/// The API keys of our data partners.
+pub struct PartnerApiKeys(ApiKeyDigests);
+
+impl PartnerApiKeys {
+    pub fn new(keys: &[Secret<String>]) -> Self {
+        Self(ApiKeyDigests::new(keys))
+    }
+}
+
+/// The API keys of the security teams exporting the audit log.
+pub struct AuditApiKeys(ApiKeyDigests);
+
+impl AuditApiKeys {
+    pub fn new(keys: &[Secret<String>]) -> Self {
+        Self(ApiKeyDigests::new(keys))
+    }
+}
+
+fn bearer_token(req: &ServiceRequest) -> Option<&str> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+fn unauthorized(req: ServiceRequest) -> ServiceResponse<BoxBody> {
+    let response = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, "Bearer"))
+        .finish();
+    req.into_response(response)
+}
+
+/// Partner requests must carry one of the configured API keys as a bearer token, i.e. an
+/// `Authorization: Bearer <key>` header. Everything else gets a `401 Unauthorized`.
+pub async fn reject_invalid_api_keys(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let api_keys = req
+        .app_data::<web::Data<PartnerApiKeys>>()
+        .ok_or_else(|| e500("Partner API keys are missing from the application state"))?;
+    let is_authorized = bearer_token(&req)
+        .map(|key| api_keys.0.is_valid(key))
+        .unwrap_or(false);
+
+    if !is_authorized {
+        return Ok(unauthorized(req).map_into_right_body());
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+/// Security teams export the audit log from their own tooling: requests carrying a bearer token
+/// must carry one of the audit API keys, the others must come from a logged-in admin - see
+/// `reject_anonymous_users`.
+pub async fn reject_unauthorized_auditors(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let api_keys = req
+        .app_data::<web::Data<AuditApiKeys>>()
+        .ok_or_else(|| e500("Audit API keys are missing from the application state"))?;
+    let is_authorized = match bearer_token(&req) {
+        Some(key) => api_keys.0.is_valid(key),
+        None => {
+            return reject_anonymous_users(req, next)
+                .await
+                .map(ServiceResponse::map_into_boxed_body)
+        }
+    };
+
+    if !is_authorized {
+        return Ok(unauthorized(req));
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)
+}
@@ -1,9 +1,14 @@
+use crate::configuration::SessionSettings;
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
+use actix_session::SessionGetError;
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
-use actix_web::{FromRequest, HttpMessage};
+use actix_web::http::header::ACCEPT;
+use actix_web::http::Method;
+use actix_web::{web, FromRequest, HttpMessage, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
 use actix_web_lab::middleware::Next;
 use std::fmt::Formatter;
 use std::ops::Deref;
@@ -31,22 +36,104 @@ impl Deref for UserId {
 /// middleware as output. THe asynchronous function must have the following signature and structure:
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
-    next: Next<impl MessageBody>,
+    next: Next<impl MessageBody + 'static>,
 ) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
     }?;
 
+    let session_settings = req
+        .app_data::<web::Data<SessionSettings>>()
+        .ok_or_else(|| e500("Session settings are missing from the application state"))?;
+
     match session.get_user_id().map_err(e500)? {
         Some(user_id) => {
+            if let Some(reason) = session_expiry(&session, session_settings).map_err(e500)? {
+                tracing::info!(%user_id, "{reason}");
+                session.log_out();
+                let response = if wants_json(&req) {
+                    unauthorized_json()
+                } else {
+                    FlashMessage::info("Your session has expired - please log in again.").send();
+                    see_other("/login")
+                };
+                // We must return a response rather than an error: the session and flash message
+                // middlewares only update the cookies of successful responses.
+                let (http_request, _) = req.into_parts();
+                let response = ServiceResponse::new(http_request, response);
+                return Ok(response.map_into_right_body());
+            }
+            session
+                .insert_last_seen(chrono::Utc::now().timestamp())
+                .map_err(e500)?;
             req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
+            next.call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        }
+        None if wants_json(&req) => {
+            let (http_request, _) = req.into_parts();
+            let response = ServiceResponse::new(http_request, unauthorized_json());
+            Ok(response.map_into_right_body())
         }
         None => {
+            // We remember where the user was going, so that we can send them there once they
+            // have logged in. Only `GET`s can be safely replayed via a redirect.
+            if req.method() == Method::GET {
+                let target = req
+                    .uri()
+                    .path_and_query()
+                    .map(|p| p.as_str())
+                    .unwrap_or_else(|| req.path());
+                session.insert_login_redirect(target).map_err(e500)?;
+                // As above, the session state is only persisted for successful responses.
+                let (http_request, _) = req.into_parts();
+                let response = ServiceResponse::new(http_request, see_other("/login"));
+                return Ok(response.map_into_right_body());
+            }
             let response = see_other("/login");
             let e = anyhow::anyhow!("The user has not logged in");
             Err(InternalError::from_response(e, response).into())
         }
     }
 }
+
+/// API clients asking for JSON cannot follow a redirect to the login page: they are told they are
+/// not logged in instead.
+fn wants_json(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .map_or(false, |h| h.contains("application/json"))
+}
+
+fn unauthorized_json() -> HttpResponse {
+    HttpResponse::Unauthorized().json(serde_json::json!({ "error": "You must log in first." }))
+}
+
+/// We only redirect to pages within the admin panel - anything else could turn our login page into
+/// an open redirect.
+pub fn is_safe_login_redirect(target: &str) -> bool {
+    target.starts_with("/admin/") && !target.contains("//") && !target.contains('\\')
+}
+
+/// The session cookie and its server-side state expire `ttl_seconds` after their last update - we
+/// must check the login timestamp ourselves to enforce an absolute TTL, as well as the idle timeout.
+fn session_expiry(
+    session: &TypedSession,
+    settings: &SessionSettings,
+) -> Result<Option<&'static str>, SessionGetError> {
+    let now = chrono::Utc::now().timestamp();
+    if let Some(logged_in_at) = session.get_logged_in_at()? {
+        if now - logged_in_at > settings.ttl_seconds {
+            return Ok(Some("The session has expired"));
+        }
+    }
+    if let Some(last_seen) = session.get_last_seen()? {
+        if now - last_seen > settings.idle_timeout_seconds {
+            return Ok(Some("The session has been idle for too long"));
+        }
+    }
+    Ok(None)
+}
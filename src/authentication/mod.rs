@@ -1,7 +1,13 @@
+mod api_key;
 mod middleware;
 mod password;
+mod webhook;
 
+pub use api_key::{
+    reject_invalid_api_keys, reject_unauthorized_auditors, AuditApiKeys, PartnerApiKeys,
+};
 pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use webhook::{reject_unauthenticated_webhooks, WebhookCredentials};
 
-pub use middleware::reject_anonymous_users;
 pub use middleware::UserId;
+pub use middleware::{is_safe_login_redirect, reject_anonymous_users};
@@ -19,18 +19,27 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
+/// A valid PHC string, using the same Argon2 parameters as the hashes we store, that no password we
+/// care about matches.
+const DUMMY_PASSWORD_HASH: &str = "$argon2id$v=19$m=15000,t=2,p=1$\
+    gZiV/M1gPc22ElAH/Jh1Hw$\
+    CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno";
+
+/// # Timing Attacks
+/// Verifying a password hash is, by design, slow - several milliseconds. If we returned as soon as
+/// we failed to find the username, an attacker could tell existing usernames apart from unknown ones
+/// just by timing our responses.
+///
+/// We always perform a hash verification instead: against the stored hash if the user exists,
+/// against `DUMMY_PASSWORD_HASH` otherwise. Both paths fail with the same
+/// `AuthError::InvalidCredentials` - only its source, which is never shown to the caller, differs.
 #[tracing::instrument(name = "Validate Credentials", skip(credentials, pool))]
 pub async fn validate_credentials(
     credentials: Credentials,
     pool: &PgPool,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-               gZiV/M1gPc22ElAH/Jh1Hw$\
-               CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+    let mut expected_password_hash = Secret::new(DUMMY_PASSWORD_HASH.to_string());
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(&credentials.username, pool)
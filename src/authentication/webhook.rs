@@ -0,0 +1,64 @@
+use super::api_key::ApiKeyDigests;
+use crate::configuration::WebhookBasicAuthSettings;
+use crate::utils::e500;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::{web, HttpResponse};
+use actix_web_lab::middleware::Next;
+use secrecy::{ExposeSecret, Secret};
+
+/// The HTTP Basic credentials webhooks must carry, if any.
+pub struct WebhookCredentials(Option<ApiKeyDigests>);
+
+impl WebhookCredentials {
+    pub fn new(settings: Option<&WebhookBasicAuthSettings>) -> Self {
+        // `username:password` is compared as a whole, the same way as an API key.
+        Self(settings.map(|s| {
+            let credentials = format!("{}:{}", s.username, s.password.expose_secret());
+            ApiKeyDigests::new(&[Secret::new(credentials)])
+        }))
+    }
+}
+
+/// `username:password`, decoded from an `Authorization: Basic <base64>` header.
+fn basic_credentials(req: &ServiceRequest) -> Option<String> {
+    let encoded = req
+        .headers()
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = base64::decode(encoded.trim()).ok()?;
+    String::from_utf8(decoded).ok()
+}
+
+/// # Webhook Authentication
+/// Postmark does not sign its webhooks: it authenticates them with HTTP Basic credentials taken
+/// from the webhook URL. If credentials are configured, webhooks without them - or with the wrong
+/// ones - get a `401 Unauthorized` and are not processed.
+pub async fn reject_unauthenticated_webhooks(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let credentials = req
+        .app_data::<web::Data<WebhookCredentials>>()
+        .ok_or_else(|| e500("Webhook credentials are missing from the application state"))?;
+    let is_authorized = match &credentials.0 {
+        Some(expected) => basic_credentials(&req)
+            .map(|credentials| expected.is_valid(&credentials))
+            .unwrap_or(false),
+        None => true,
+    };
+
+    if !is_authorized {
+        tracing::warn!("Rejected a webhook with missing or invalid credentials.");
+        let response = HttpResponse::Unauthorized()
+            .insert_header((WWW_AUTHENTICATE, r#"Basic realm="webhooks""#))
+            .finish();
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
@@ -0,0 +1,63 @@
+use crate::configuration::CacheControlSettings;
+use crate::session_state::TypedSession;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, CACHE_CONTROL};
+use actix_web::http::Method;
+use actix_web::{web, FromRequest};
+use actix_web_lab::middleware::Next;
+
+/// Assets that rarely change.
+const STATIC_ROUTES: &[&str] = &["/widget.js"];
+/// Pages that are the same for every anonymous visitor.
+const PUBLIC_ROUTES: &[&str] = &["/"];
+
+/// # Caching
+/// CDNs (and browsers) cache responses according to their `Cache-Control` header. We pick it based
+/// on the class of the route:
+/// * admin pages, and every response to a logged-in user, must never be stored: `no-store`;
+/// * static assets can be cached for a long time;
+/// * public pages can be cached for a short time;
+/// * everything else gets the configured default.
+///
+/// Handlers can still set their own `Cache-Control` header, which we leave untouched - unless the
+/// response is for a logged-in user.
+pub async fn set_cache_control(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let is_authenticated = {
+        let (http_request, payload) = req.parts_mut();
+        let session = TypedSession::from_request(http_request, payload).await?;
+        // We'd rather not cache a response we are unsure about.
+        !matches!(session.get_user_id(), Ok(None))
+    };
+    let path = req.path().to_owned();
+    let is_read = matches!(*req.method(), Method::GET | Method::HEAD);
+
+    let mut response = next.call(req).await?;
+    let settings = match response
+        .request()
+        .app_data::<web::Data<CacheControlSettings>>()
+    {
+        Some(settings) => settings.clone(),
+        None => return Ok(response),
+    };
+    let is_admin = path == "/admin" || path.starts_with("/admin/");
+    let is_cacheable = is_read && response.status().is_success();
+    let cache_control = if is_authenticated || is_admin {
+        "no-store".to_string()
+    } else if response.headers().contains_key(CACHE_CONTROL) {
+        return Ok(response);
+    } else if is_cacheable && STATIC_ROUTES.contains(&path.as_str()) {
+        format!("public, max-age={}", settings.static_max_age_seconds)
+    } else if is_cacheable && PUBLIC_ROUTES.contains(&path.as_str()) {
+        format!("public, max-age={}", settings.public_max_age_seconds)
+    } else {
+        settings.default.clone()
+    };
+    if let Ok(value) = HeaderValue::from_str(&cache_control) {
+        response.headers_mut().insert(CACHE_CONTROL, value);
+    }
+    Ok(response)
+}
@@ -0,0 +1,115 @@
+use crate::tenant::{TenantHosts, DEFAULT_TENANT};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HOST, LOCATION};
+use actix_web::http::{Method, Uri};
+use actix_web::{web, HttpResponse};
+use actix_web_lab::middleware::Next;
+
+/// The scheme and host of `ApplicationSettings::base_url`, e.g. `https` and `example.com`.
+#[derive(Debug)]
+pub struct CanonicalUrl {
+    scheme: String,
+    // With the port, if any.
+    authority: String,
+}
+
+impl CanonicalUrl {
+    pub fn new(base_url: &str) -> Result<Self, String> {
+        let uri = base_url
+            .parse::<Uri>()
+            .map_err(|_| format!("{base_url} is not a valid base URL."))?;
+        match (uri.scheme_str(), uri.authority()) {
+            (Some(scheme), Some(authority)) => Ok(Self {
+                scheme: scheme.to_lowercase(),
+                authority: authority.as_str().to_lowercase(),
+            }),
+            _ => Err(format!("{base_url} is not an absolute URL.")),
+        }
+    }
+
+    /// `None` if `scheme` and `authority` are canonical already.
+    fn redirect_for(&self, scheme: &str, authority: &str, path_and_query: &str) -> Option<String> {
+        if scheme.eq_ignore_ascii_case(&self.scheme)
+            && authority.eq_ignore_ascii_case(&self.authority)
+        {
+            return None;
+        }
+        Some(format!(
+            "{}://{}{path_and_query}",
+            self.scheme, self.authority
+        ))
+    }
+}
+
+/// # Canonical URL
+/// Pages served from several URLs - `www.` or not, over HTTP or HTTPS - split their search ranking,
+/// and pages served over HTTP load their assets without TLS. When enabled, `GET` and `HEAD` requests
+/// whose scheme or host differ from `base_url` are redirected there for good, with a `301`. The
+/// scheme reported by a reverse proxy (`X-Forwarded-Proto`) is taken into account.
+///
+/// Health checks are not redirected, and neither are the hosts of other tenants: they have their
+/// own base URL. Other methods are left alone - forms and webhooks would not survive the redirect.
+pub async fn redirect_to_canonical_url(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let is_exempt = req.path() == "/health_check"
+        || !matches!(*req.method(), Method::GET | Method::HEAD)
+        || req
+            .app_data::<web::Data<TenantHosts>>()
+            .map_or(false, |hosts| {
+                let host = req.headers().get(HOST).and_then(|h| h.to_str().ok());
+                hosts.tenant_for(host).id() != DEFAULT_TENANT
+            });
+    let location = match req.app_data::<web::Data<CanonicalUrl>>() {
+        Some(canonical_url) if !is_exempt => {
+            let connection_info = req.connection_info();
+            let path_and_query = req.uri().path_and_query().map_or("/", |p| p.as_str());
+            canonical_url.redirect_for(
+                connection_info.scheme(),
+                connection_info.host(),
+                path_and_query,
+            )
+        }
+        _ => None,
+    };
+    match location {
+        Some(location) => {
+            let response = HttpResponse::MovedPermanently()
+                .insert_header((LOCATION, location))
+                .finish();
+            Ok(req.into_response(response).map_into_right_body())
+        }
+        None => next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CanonicalUrl;
+
+    #[test]
+    fn only_other_schemes_and_hosts_are_redirected() {
+        let canonical_url = CanonicalUrl::new("https://example.com").unwrap();
+
+        assert_eq!(
+            canonical_url.redirect_for("https", "Example.com", "/"),
+            None
+        );
+        for (scheme, authority) in [("http", "example.com"), ("https", "www.example.com")] {
+            assert_eq!(
+                canonical_url.redirect_for(scheme, authority, "/login?next=%2F"),
+                Some("https://example.com/login?next=%2F".into())
+            );
+        }
+    }
+
+    #[test]
+    fn base_urls_must_be_absolute() {
+        assert!(CanonicalUrl::new("/newsletter").is_err());
+    }
+}
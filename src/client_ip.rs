@@ -0,0 +1,82 @@
+use actix_web::{web, HttpRequest};
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// The reverse proxies (e.g. load balancers) we are deployed behind.
+pub struct TrustedProxies(pub Vec<IpNet>);
+
+impl TrustedProxies {
+    fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|network| network.contains(ip))
+    }
+
+    /// # Client IP Behind Proxies
+    /// Behind a reverse proxy the TCP peer is the proxy itself: the client IP is forwarded to us in
+    /// the `X-Forwarded-For` header, with every proxy on the way appending the address it got the
+    /// request from.
+    ///
+    /// Anybody can set that header though: only the entries appended by our own proxies can be
+    /// trusted. We walk the list from the right, starting from the TCP peer, and stop at the first
+    /// address that is not one of our proxies - that is the client.
+    pub fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> IpAddr {
+        let mut client = peer;
+        let forwarded_for = forwarded_for.unwrap_or_default().rsplit(',');
+        for entry in forwarded_for {
+            if !self.contains(&client) {
+                break;
+            }
+            match entry.trim().parse() {
+                Ok(ip) => client = ip,
+                // A malformed entry was not appended by one of our proxies.
+                Err(_) => break,
+            }
+        }
+        client
+    }
+}
+
+/// The IP of the client behind `req`, taking `TrustedProxies` into account if they are registered
+/// in the application state. `None` if the peer address is unknown, e.g. in unit tests.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+    let forwarded_for = req
+        .headers()
+        .get("X-Forwarded-For")
+        .and_then(|h| h.to_str().ok());
+    Some(match req.app_data::<web::Data<TrustedProxies>>() {
+        Some(trusted_proxies) => trusted_proxies.client_ip(peer, forwarded_for),
+        None => peer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrustedProxies;
+    use std::net::IpAddr;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_for_entries_are_only_trusted_when_appended_by_our_proxies() {
+        let proxies = TrustedProxies(vec!["10.0.0.0/8".parse().unwrap()]);
+        let forwarded_for = Some("198.51.100.1, 203.0.113.7, 10.0.0.2");
+
+        // The peer is not a proxy: the header could have been set by anybody.
+        assert_eq!(
+            proxies.client_ip(ip("192.0.2.1"), forwarded_for),
+            ip("192.0.2.1")
+        );
+        // The first entry is client-provided: the client is the last non-proxy address.
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), forwarded_for),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), Some("not-an-ip")),
+            ip("10.0.0.1")
+        );
+        assert_eq!(proxies.client_ip(ip("10.0.0.1"), None), ip("10.0.0.1"));
+    }
+}
@@ -0,0 +1,55 @@
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, CONTENT_ENCODING};
+use actix_web::web;
+use actix_web_lab::middleware::Next;
+
+/// Responses with a body smaller than this many bytes are sent uncompressed.
+#[derive(Debug)]
+pub struct CompressionThreshold(pub u64);
+
+/// # Compression
+/// `Compress` encodes every response the client accepts an encoding for: it only leaves alone those
+/// that already have a `Content-Encoding`. Compressing a tiny body costs CPU and saves next to
+/// nothing, so we mark them as `identity` on their way out of the handler - registered inside
+/// `Compress` - and take the marker off again - registered outside `Compress`, with
+/// `strip_identity_encoding`.
+///
+/// Streamed bodies have no known size: they are always compressed.
+pub async fn skip_compressing_small_responses(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let mut response = next.call(req).await?;
+    let threshold = match response
+        .request()
+        .app_data::<web::Data<CompressionThreshold>>()
+    {
+        Some(threshold) => threshold.0,
+        None => return Ok(response),
+    };
+    let is_small =
+        matches!(response.response().body().size(), BodySize::Sized(size) if size < threshold);
+    if is_small && !response.headers().contains_key(CONTENT_ENCODING) {
+        response
+            .headers_mut()
+            .insert(CONTENT_ENCODING, HeaderValue::from_static("identity"));
+    }
+    Ok(response)
+}
+
+/// `identity` is the absence of an encoding: it is not meant to be sent to clients.
+pub async fn strip_identity_encoding(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let mut response = next.call(req).await?;
+    if response
+        .headers()
+        .get(CONTENT_ENCODING)
+        .map_or(false, |encoding| encoding == "identity")
+    {
+        response.headers_mut().remove(CONTENT_ENCODING);
+    }
+    Ok(response)
+}
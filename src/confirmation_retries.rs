@@ -0,0 +1,204 @@
+use crate::configuration::SubscriptionSettings;
+use crate::conversion_funnel::{record_funnel_step, FunnelStep};
+use crate::domain::SubscriberEmail;
+use crate::email_client::{EmailClient, SendEmailError};
+use crate::issue_delivery_worker::ExecutionOutcome;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{PgExecutor, PgPool};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A confirmation email, ready to be sent - either rendered by us, or by Postmark out of one of its
+/// templates. It is stored as JSON while it waits for a retry.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConfirmationEmail {
+    Rendered {
+        subject: String,
+        html_body: String,
+        text_body: String,
+    },
+    Template {
+        alias: String,
+        model: serde_json::Value,
+    },
+}
+
+impl ConfirmationEmail {
+    pub async fn send(
+        &self,
+        email_client: &EmailClient,
+        recipient: &SubscriberEmail,
+    ) -> Result<(), SendEmailError> {
+        match self {
+            Self::Rendered {
+                subject,
+                html_body,
+                text_body,
+            } => {
+                email_client
+                    .send_email(recipient, subject, html_body, text_body, &[])
+                    .await?
+            }
+            Self::Template { alias, model } => {
+                email_client.send_template(recipient, alias, model).await?
+            }
+        };
+        Ok(())
+    }
+}
+
+/// # Confirmation Retries
+/// A confirmation email that fails to send leaves the subscriber pending, with nothing to confirm
+/// their subscription with. Instead of giving up, we queue it: the delivery worker retries it up to
+/// `confirmation_retries` times, waiting `confirmation_retry_backoff_seconds` before the first retry
+/// and twice as long before each of the following ones.
+///
+/// Subscribers whose email could not be sent after every retry are flagged `confirmation_failed`,
+/// for support to step in.
+#[tracing::instrument(skip(executor, email))]
+pub async fn enqueue_confirmation_retry(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+    recipient: &str,
+    email: &ConfirmationEmail,
+    backoff_seconds: u64,
+) -> Result<(), anyhow::Error> {
+    let execute_after = Utc::now() + chrono::Duration::seconds(backoff_seconds as i64);
+    // A new subscription attempt replaces the email waiting for a retry, if any.
+    sqlx::query!(
+        r#"
+        INSERT INTO confirmation_email_queue (subscriber_id, recipient, email, execute_after)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (subscriber_id) DO UPDATE
+        SET recipient = $2, email = $3, n_retries = 0, execute_after = $4
+        "#,
+        subscriber_id,
+        recipient,
+        serde_json::to_string(email)?,
+        execute_after
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// The confirmation lane of the delivery worker: up to `max_concurrent_confirmations` emails are
+/// retried at once, regardless of how many newsletter deliveries are pending. Each email is locked
+/// while it is being retried, hence it is only sent once.
+///
+/// Without `confirmation_retries`, nothing is ever enqueued: there is no lane at all.
+pub(crate) async fn confirmation_retry_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    settings: SubscriptionSettings,
+) -> Result<(), anyhow::Error> {
+    if settings.confirmation_retries == 0 {
+        return Ok(());
+    }
+    let (pool, email_client, settings) = (&pool, &email_client, &settings);
+    let lanes = (0..settings.max_concurrent_confirmations.max(1)).map(|_| async move {
+        loop {
+            // Errors are already logged.
+            match try_execute_confirmation_retry(pool, email_client, settings).await {
+                Ok(ExecutionOutcome::TaskCompleted) => {}
+                Ok(ExecutionOutcome::EmptyQueue) | Err(_) => {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    });
+    futures_util::future::join_all(lanes).await;
+    Ok(())
+}
+
+/// Retries the next confirmation email that is due, if any.
+#[tracing::instrument(skip_all, fields(subscriber_id=tracing::field::Empty), err)]
+pub async fn try_execute_confirmation_retry(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    settings: &SubscriptionSettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query!(
+        r#"
+        SELECT subscriber_id, recipient, email, n_retries
+        FROM confirmation_email_queue
+        WHERE execute_after <= now()
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    let task = match task {
+        Some(task) => task,
+        None => return Ok(ExecutionOutcome::EmptyQueue),
+    };
+    tracing::Span::current().record("subscriber_id", tracing::field::display(task.subscriber_id));
+
+    let outcome = match SubscriberEmail::parse(task.recipient) {
+        Ok(recipient) => {
+            let email: ConfirmationEmail = serde_json::from_str(&task.email)
+                .context("Failed to deserialize a queued confirmation email.")?;
+            let outcome = email.send(email_client, &recipient).await;
+            if outcome.is_ok() {
+                record_funnel_step(FunnelStep::ConfirmationSent, task.subscriber_id);
+            }
+            outcome
+        }
+        Err(e) => {
+            tracing::error!(error.message = %e,
+                "Dropping a confirmation email. The stored recipient is invalid.");
+            Ok(())
+        }
+    };
+    match outcome {
+        Ok(()) => {
+            sqlx::query!(
+                "DELETE FROM confirmation_email_queue WHERE subscriber_id = $1",
+                task.subscriber_id
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+        Err(e) if task.n_retries + 1 < settings.confirmation_retries as i32 => {
+            tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                "Failed to send a confirmation email. Retrying later.");
+            let backoff_seconds =
+                settings.confirmation_retry_backoff_seconds << (task.n_retries + 1).min(16);
+            sqlx::query!(
+                r#"
+                UPDATE confirmation_email_queue
+                SET n_retries = n_retries + 1, execute_after = $2
+                WHERE subscriber_id = $1
+                "#,
+                task.subscriber_id,
+                Utc::now() + chrono::Duration::seconds(backoff_seconds as i64)
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, error.message = %e,
+                "Failed to send a confirmation email. The retries are exhausted: flagging it.");
+            sqlx::query!(
+                "DELETE FROM confirmation_email_queue WHERE subscriber_id = $1",
+                task.subscriber_id
+            )
+            .execute(&mut transaction)
+            .await?;
+            sqlx::query!(
+                "UPDATE subscriptions SET confirmation_failed = true WHERE id = $1",
+                task.subscriber_id
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+    }
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
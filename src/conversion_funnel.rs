@@ -0,0 +1,35 @@
+use uuid::Uuid;
+
+/// The target of the conversion funnel events - e.g. `RUST_LOG=info,conversion_funnel=off` turns
+/// them off.
+pub const TARGET: &str = "conversion_funnel";
+
+/// # Conversion Funnel
+/// A subscriber goes through these steps, in order. Each step is logged as an event of its own,
+/// carrying the `step` and the `subscriber_id`: a log pipeline can then compute how many subscribers
+/// make it from one step to the next. These events are never sampled out - see `telemetry`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FunnelStep {
+    Subscribed,
+    ConfirmationSent,
+    Confirmed,
+}
+
+impl FunnelStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FunnelStep::Subscribed => "subscribed",
+            FunnelStep::ConfirmationSent => "confirmation_sent",
+            FunnelStep::Confirmed => "confirmed",
+        }
+    }
+}
+
+pub fn record_funnel_step(step: FunnelStep, subscriber_id: Uuid) {
+    tracing::info!(
+        target: TARGET,
+        step = step.as_str(),
+        %subscriber_id,
+        "A subscriber reached a step of the conversion funnel."
+    );
+}
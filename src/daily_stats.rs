@@ -0,0 +1,121 @@
+use crate::configuration::Settings;
+use crate::startup::get_connection_pool;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// How often the stats of today and yesterday are rolled up again.
+const AGGREGATION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(serde::Serialize, Debug, PartialEq, Eq)]
+pub struct DailyStats {
+    // e.g. `2026-10-16`.
+    pub day: String,
+    pub sent: i64,
+    pub opened: i64,
+    pub clicked: i64,
+    pub bounced: i64,
+}
+
+/// # Daily Stats
+/// Rolls the newsletter activity of `day` (UTC) up into its `daily_stats` row, replacing the previous
+/// one: aggregating a day again is safe, and catches up on what happened since.
+/// * `sent` counts the emails delivered from `newsletter_deliveries` - skipped deliveries are not;
+/// * `bounced` counts the `bounced` subscription events.
+///
+/// We do not track opens nor clicks yet: `opened` and `clicked` stay at zero.
+///
+/// The day is matched as a `[midnight, next midnight)` range of timestamps rather than by casting
+/// each timestamp to a date, for the indexes on `delivered_at` and `occurred_at` to be used.
+#[tracing::instrument(name = "Aggregate daily stats", skip(pool), err)]
+pub async fn aggregate_daily_stats(pool: &PgPool, day: NaiveDate) -> Result<(), sqlx::Error> {
+    let start: DateTime<Utc> = DateTime::from_utc(day.and_hms_opt(0, 0, 0).unwrap(), Utc);
+    let end = start + chrono::Duration::days(1);
+    sqlx::query!(
+        r#"
+        INSERT INTO daily_stats (day, sent, bounced, aggregated_at)
+        SELECT
+            $1,
+            (
+                SELECT COUNT(*) FROM newsletter_deliveries
+                WHERE status = 'delivered' AND delivered_at >= $2 AND delivered_at < $3
+            ),
+            (
+                SELECT COUNT(*) FROM subscription_events
+                WHERE event_type = 'bounced' AND occurred_at >= $2 AND occurred_at < $3
+            ),
+            now()
+        ON CONFLICT (day) DO UPDATE
+        SET sent = EXCLUDED.sent, bounced = EXCLUDED.bounced, aggregated_at = EXCLUDED.aggregated_at
+        "#,
+        day,
+        start,
+        end
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The stats of the last `days` days that have been aggregated, most recent first.
+#[tracing::instrument(skip(pool))]
+pub async fn get_recent_daily_stats(
+    pool: &PgPool,
+    days: i64,
+) -> Result<Vec<DailyStats>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT day, sent, opened, clicked, bounced
+        FROM daily_stats
+        ORDER BY day DESC
+        LIMIT $1
+        "#,
+        days
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| DailyStats {
+            day: r.day.to_string(),
+            sent: r.sent,
+            opened: r.opened,
+            clicked: r.clicked,
+            bounced: r.bounced,
+        })
+        .collect())
+}
+
+/// Aggregates every day from the last one that has been aggregated up to `today`, both included:
+/// days missed while the aggregator was not running are backfilled. Yesterday is always aggregated
+/// again - to account for what happened between its last aggregation and midnight.
+#[tracing::instrument(name = "Catch up on daily stats", skip(pool), err)]
+pub async fn catch_up_daily_stats(pool: &PgPool, today: NaiveDate) -> Result<(), sqlx::Error> {
+    let yesterday = today - chrono::Duration::days(1);
+    let last_aggregated_day = sqlx::query!(r#"SELECT MAX(day) AS "day" FROM daily_stats"#)
+        .fetch_one(pool)
+        .await?
+        .day;
+    let mut day = last_aggregated_day.map_or(yesterday, |day| day.min(yesterday));
+    while day <= today {
+        aggregate_daily_stats(pool, day).await?;
+        day += chrono::Duration::days(1);
+    }
+    Ok(())
+}
+
+/// Catches up every `AGGREGATION_INTERVAL`.
+async fn aggregator_loop(pool: PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        // Errors are already logged - we will try again next time.
+        let _ = catch_up_daily_stats(&pool, Utc::now().date_naive()).await;
+        tokio::time::sleep(AGGREGATION_INTERVAL).await;
+    }
+}
+
+pub async fn run_stats_aggregator_until_stopped(
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    aggregator_loop(connection_pool).await
+}
@@ -0,0 +1,57 @@
+#[derive(Debug)]
+pub struct AttributionTag(String);
+
+impl AttributionTag {
+    /// The most characters a tag can have.
+    pub const MAX_LENGTH: usize = 100;
+
+    /// Returns an instance of `AttributionTag` - the `source` or `utm_campaign` a subscriber came
+    /// from - if the input is at most `MAX_LENGTH` characters long, once trimmed, and has no control
+    /// characters.
+    pub fn parse(s: String) -> Result<AttributionTag, String> {
+        let s = s.trim();
+        if s.chars().count() > Self::MAX_LENGTH {
+            return Err(format!(
+                "Sources and campaigns can be at most {} characters long.",
+                Self::MAX_LENGTH
+            ));
+        }
+        if s.is_empty() || s.chars().any(char::is_control) {
+            return Err(format!("{s} is not a valid source or campaign."));
+        }
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl AsRef<str> for AttributionTag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::AttributionTag;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn tags_are_trimmed() {
+        let tag = assert_ok!(AttributionTag::parse(" spring-sale ".into()));
+        assert_eq!(tag.as_ref(), "spring-sale");
+    }
+
+    #[test]
+    fn a_tag_of_max_length_is_valid() {
+        assert_ok!(AttributionTag::parse(
+            "ё".repeat(AttributionTag::MAX_LENGTH)
+        ));
+    }
+
+    #[test]
+    fn anything_else_is_rejected() {
+        let too_long = "a".repeat(AttributionTag::MAX_LENGTH + 1);
+        for tag in ["", " ", "new\nsletter", too_long.as_str()] {
+            assert_err!(AttributionTag::parse(tag.into()));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use reqwest::Url;
+
+/// The link, sent to new subscribers, to confirm their subscription: `base_url`, followed by the
+/// confirmation `path`, with the subscription token in the query string.
+#[derive(Debug)]
+pub struct ConfirmationLink(Url);
+
+impl ConfirmationLink {
+    /// `base_url` can carry a path prefix (e.g. `https://example.com/newsletter`) if we are not served
+    /// from the root of our domain. The token is percent-encoded as needed.
+    pub fn new(base_url: &str, path: &str, subscription_token: &str) -> Result<Self, String> {
+        let mut url =
+            Url::parse(base_url).map_err(|e| format!("{base_url} is not a valid base URL: {e}"))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("{base_url} is not an HTTP(S) URL."));
+        }
+        if !path.starts_with('/') {
+            return Err(format!(
+                "The confirmation path, {path}, must start with `/`."
+            ));
+        }
+        let full_path = format!("{}{path}", url.path().trim_end_matches('/'));
+        url.set_path(&full_path);
+        url.set_fragment(None);
+        url.query_pairs_mut()
+            .clear()
+            .append_pair("subscription_token", subscription_token);
+        Ok(Self(url))
+    }
+}
+
+impl AsRef<str> for ConfirmationLink {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl std::fmt::Display for ConfirmationLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfirmationLink;
+    use claims::assert_err;
+
+    #[test]
+    fn the_link_is_made_of_the_base_url_path_and_token() {
+        let link =
+            ConfirmationLink::new("http://127.0.0.1:8000", "/subscriptions/confirm", "abc123")
+                .unwrap();
+        assert_eq!(
+            link.as_ref(),
+            "http://127.0.0.1:8000/subscriptions/confirm?subscription_token=abc123"
+        );
+    }
+
+    #[test]
+    fn a_path_prefix_in_the_base_url_is_kept() {
+        let link = ConfirmationLink::new(
+            "https://example.com/newsletter/",
+            "/subscriptions/confirm",
+            "abc123",
+        )
+        .unwrap();
+        assert_eq!(
+            link.as_ref(),
+            "https://example.com/newsletter/subscriptions/confirm?subscription_token=abc123"
+        );
+    }
+
+    #[test]
+    fn url_sensitive_characters_in_the_token_are_encoded() {
+        let token = "a&b=c d/e?f#g+h%";
+        let link =
+            ConfirmationLink::new("https://example.com", "/subscriptions/confirm", token).unwrap();
+
+        assert_eq!(
+            link.as_ref(),
+            "https://example.com/subscriptions/confirm\
+            ?subscription_token=a%26b%3Dc+d%2Fe%3Ff%23g%2Bh%25"
+        );
+        // The token survives the round trip.
+        let url = reqwest::Url::parse(link.as_ref()).unwrap();
+        let (key, value) = url.query_pairs().next().unwrap();
+        assert_eq!(key, "subscription_token");
+        assert_eq!(value, token);
+    }
+
+    #[test]
+    fn invalid_base_urls_and_paths_are_rejected() {
+        assert_err!(ConfirmationLink::new("127.0.0.1", "/confirm", "abc123"));
+        assert_err!(ConfirmationLink::new(
+            "ftp://example.com",
+            "/confirm",
+            "abc123"
+        ));
+        assert_err!(ConfirmationLink::new(
+            "https://example.com",
+            "confirm",
+            "abc123"
+        ));
+    }
+}
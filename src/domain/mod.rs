@@ -1,7 +1,15 @@
+mod attribution_tag;
+mod confirmation_link;
 mod new_subscriber;
 mod subscriber_email;
+mod subscriber_locale;
 mod subscriber_name;
+mod subscriber_time_zone;
 
+pub use attribution_tag::AttributionTag;
+pub use confirmation_link::ConfirmationLink;
 pub use new_subscriber::NewSubscriber;
-pub use subscriber_email::SubscriberEmail;
+pub use subscriber_email::{SubscriberEmail, SubscriberEmailPolicy};
+pub use subscriber_locale::SubscriberLocale;
 pub use subscriber_name::SubscriberName;
+pub use subscriber_time_zone::SubscriberTimeZone;
@@ -1,4 +1,6 @@
-use crate::domain::{SubscriberEmail, SubscriberName};
+use crate::domain::{
+    AttributionTag, SubscriberEmail, SubscriberLocale, SubscriberName, SubscriberTimeZone,
+};
 
 /// # Type Driven Development
 /// Making an incorrect usage pattern unrepresentable, by construction is known as *type driven
@@ -11,4 +13,11 @@ use crate::domain::{SubscriberEmail, SubscriberName};
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    // Localizes what we send them, if set.
+    pub locale: Option<SubscriberLocale>,
+    // Quiet hours are in their local time, if set.
+    pub time_zone: Option<SubscriberTimeZone>,
+    // Where they came from, if they told us: reported in `/admin/stats`.
+    pub source: Option<AttributionTag>,
+    pub utm_campaign: Option<AttributionTag>,
 }
@@ -13,6 +13,77 @@ impl SubscriberEmail {
     }
 }
 
+/// Local parts reaching a team or a system, rather than a person.
+const ROLE_LOCAL_PARTS: &[&str] = &[
+    "abuse",
+    "admin",
+    "administrator",
+    "billing",
+    "contact",
+    "help",
+    "hostmaster",
+    "info",
+    "marketing",
+    "no-reply",
+    "noc",
+    "noreply",
+    "postmaster",
+    "root",
+    "sales",
+    "security",
+    "support",
+    "webmaster",
+];
+
+impl SubscriberEmail {
+    fn local_part(&self) -> &str {
+        self.0
+            .rsplit_once('@')
+            .map_or(&self.0, |(local_part, _)| local_part)
+    }
+
+    /// The part after the `@`, e.g. `example.com`.
+    pub fn domain(&self) -> &str {
+        self.0.rsplit_once('@').map_or("", |(_, domain)| domain)
+    }
+
+    /// `user+tag@example.com` is delivered to `user@example.com` by most providers.
+    pub fn is_plus_address(&self) -> bool {
+        self.local_part().contains('+')
+    }
+
+    /// A role account with a tag, e.g. `admin+newsletter@example.com`, is still a role account.
+    pub fn is_role_account(&self) -> bool {
+        let local_part = self.local_part();
+        let local_part = local_part.split('+').next().unwrap_or(local_part);
+        ROLE_LOCAL_PARTS.contains(&local_part.to_lowercase().as_str())
+    }
+}
+
+/// Some installs do not accept every valid address: `check` enforces their policy on top of
+/// `SubscriberEmail::parse`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SubscriberEmailPolicy {
+    pub block_plus_addressing: bool,
+    pub block_role_accounts: bool,
+}
+
+impl SubscriberEmailPolicy {
+    pub fn check(&self, email: &SubscriberEmail) -> Result<(), String> {
+        if self.block_plus_addressing && email.is_plus_address() {
+            return Err(format!(
+                "{email} is an alias: please subscribe with your address."
+            ));
+        }
+        if self.block_role_accounts && email.is_role_account() {
+            return Err(format!(
+                "{email} is a role account: please subscribe with a personal address."
+            ));
+        }
+        Ok(())
+    }
+}
+
 impl AsRef<str> for SubscriberEmail {
     fn as_ref(&self) -> &str {
         &self.0
@@ -32,8 +103,8 @@ impl std::fmt::Display for SubscriberEmail {
 /// is correct - it does not *exhaustively* explore the input space (except for tiny ones).
 #[cfg(test)]
 mod tests {
-    use super::SubscriberEmail;
-    use claims::assert_err;
+    use super::{SubscriberEmail, SubscriberEmailPolicy};
+    use claims::{assert_err, assert_ok};
     /// We are importing the `SafeEmail` faker! We also need the `Fake` trait to get access to the
     /// `.fake` method on `SafeEmail`
     use fake::faker::internet::en::SafeEmail;
@@ -57,6 +128,41 @@ mod tests {
         assert_err!(SubscriberEmail::parse(email));
     }
 
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.into()).unwrap()
+    }
+
+    #[test]
+    fn plus_addresses_are_rejected_when_blocking_plus_addressing() {
+        let policy = SubscriberEmailPolicy {
+            block_plus_addressing: true,
+            ..Default::default()
+        };
+        assert_err!(policy.check(&email("user+tag@x.com")));
+        assert_ok!(policy.check(&email("user@x.com")));
+        assert_ok!(policy.check(&email("admin@x.com")));
+    }
+
+    #[test]
+    fn role_accounts_are_rejected_when_blocking_role_accounts() {
+        let policy = SubscriberEmailPolicy {
+            block_role_accounts: true,
+            ..Default::default()
+        };
+        for role_account in ["admin@x.com", "Postmaster@x.com", "admin+tag@x.com"] {
+            assert_err!(policy.check(&email(role_account)));
+        }
+        assert_ok!(policy.check(&email("user+tag@x.com")));
+        assert_ok!(policy.check(&email("administrative.assistant@x.com")));
+    }
+
+    #[test]
+    fn plus_addresses_and_role_accounts_are_accepted_by_default() {
+        let policy = SubscriberEmailPolicy::default();
+        assert_ok!(policy.check(&email("user+tag@x.com")));
+        assert_ok!(policy.check(&email("admin@x.com")));
+    }
+
     #[derive(Debug, Clone)]
     struct ValidEmailFixture(pub String);
 
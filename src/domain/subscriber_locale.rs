@@ -0,0 +1,47 @@
+#[derive(Debug)]
+pub struct SubscriberLocale(String);
+
+impl SubscriberLocale {
+    /// Returns an instance of `SubscriberLocale` if the input looks like a BCP 47 language tag, e.g.
+    /// `fr` or `fr-CA`: dash-separated runs of 1 to 8 ASCII letters or digits, starting with a
+    /// letter. We store it lowercased - tags are case-insensitive.
+    pub fn parse(s: String) -> Result<SubscriberLocale, String> {
+        let s = s.trim().to_lowercase();
+        let is_valid = s.len() <= 35
+            && s.starts_with(|c: char| c.is_ascii_alphabetic())
+            && s.split('-').all(|subtag| {
+                (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+            });
+
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(format!("{s} is not a valid locale."))
+        }
+    }
+}
+
+impl AsRef<str> for SubscriberLocale {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberLocale;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn language_tags_are_valid_and_lowercased() {
+        let locale = assert_ok!(SubscriberLocale::parse(" fr-CA ".into()));
+        assert_eq!(locale.as_ref(), "fr-ca");
+    }
+
+    #[test]
+    fn anything_else_is_rejected() {
+        for locale in ["", "fr_CA", "fr--ca", "1fr", "fr-<script>", "abcdefghi"] {
+            assert_err!(SubscriberLocale::parse(locale.into()));
+        }
+    }
+}
@@ -6,6 +6,9 @@ pub struct SubscriberName(String);
 impl SubscriberName {
     /// Returns an instance of `SubscriberName` if the input satisfies all our validation constraints
     /// on subscriber names. It panics otherwise.
+    ///
+    /// The validation constraints are checked against the input as submitted, but we store its
+    /// canonical form: trimmed, with every run of whitespace collapsed to a single space.
     pub fn parse(s: String) -> Result<SubscriberName, String> {
         // `.trim()` returns a view over the input `s` without trailing whitespace-like characters.
         // `.is_empty` checks if the view contains any character.
@@ -26,7 +29,7 @@ impl SubscriberName {
         if is_empty_or_whitespace || is_too_long || contains_forbiden_characters {
             Err(format!("{s} is not a valid subscriber name."))
         } else {
-            Ok(Self(s))
+            Ok(Self(s.split_whitespace().collect::<Vec<_>>().join(" ")))
         }
     }
 }
@@ -76,6 +79,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn whitespace_is_collapsed_and_trimmed() {
+        let name = SubscriberName::parse("Le   Guin ".to_string()).unwrap();
+        assert_eq!(name.as_ref(), "Le Guin");
+    }
+
+    #[test]
+    fn names_are_validated_before_being_canonicalized() {
+        assert_err!(SubscriberName::parse("Le   (Guin) ".to_string()));
+        // Longer than 256 graphemes as submitted, even though its canonical form is not.
+        let name = format!("Le{}Guin", " ".repeat(256));
+        assert_err!(SubscriberName::parse(name));
+    }
+
     #[test]
     fn a_valid_name_is_parsed_successfully() {
         let name = "Ursula Le Guin".to_string();
@@ -0,0 +1,65 @@
+#[derive(Debug)]
+pub struct SubscriberTimeZone(String);
+
+impl SubscriberTimeZone {
+    /// Returns an instance of `SubscriberTimeZone` if the input looks like an IANA time zone name,
+    /// e.g. `Europe/Paris` or `America/Argentina/Buenos_Aires`: slash-separated runs of ASCII
+    /// letters, digits, `_`, `-` and `+`. Whether the time zone exists is up to Postgres' time zone
+    /// database.
+    pub fn parse(s: String) -> Result<SubscriberTimeZone, String> {
+        let s = s.trim().to_owned();
+        let is_valid = s.len() <= 64
+            && s.split('/').all(|part| {
+                !part.is_empty()
+                    && part != ".."
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+            });
+
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(format!("{s} is not a valid time zone."))
+        }
+    }
+}
+
+impl AsRef<str> for SubscriberTimeZone {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberTimeZone;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn iana_time_zone_names_are_valid() {
+        for time_zone in [
+            "UTC",
+            " Europe/Paris ",
+            "America/Argentina/Buenos_Aires",
+            "Etc/GMT-5",
+        ] {
+            let parsed = assert_ok!(SubscriberTimeZone::parse(time_zone.into()));
+            assert_eq!(parsed.as_ref(), time_zone.trim());
+        }
+    }
+
+    #[test]
+    fn anything_else_is_rejected() {
+        for time_zone in [
+            "",
+            "Europe/",
+            "/etc/passwd",
+            "Europe/../Paris",
+            "Europe/Paris<script>",
+        ] {
+            assert_err!(SubscriberTimeZone::parse(time_zone.into()));
+        }
+        assert_err!(SubscriberTimeZone::parse("A".repeat(65)));
+    }
+}
@@ -0,0 +1,74 @@
+use redis::aio::ConnectionManager;
+use redis::RedisError;
+use secrecy::{ExposeSecret, Secret};
+use std::net::IpAddr;
+use tokio::sync::OnceCell;
+
+/// # Collapsing Double Submits
+/// Impatient visitors (and flaky connections) submit the subscription form twice in a row. The form
+/// carries no idempotency key: instead, we remember every (tenant, client IP, email) triple we
+/// processed in Redis for `window_seconds`, and identical submissions within that window are not
+/// processed again. Subscribing to another tenant is not a duplicate.
+///
+/// The triple is remembered as soon as the submission is accepted: a submission that failed half-way
+/// through can only be retried once the window is over.
+pub struct DuplicateSubmissions {
+    client: redis::Client,
+    // Connected on first use: the application starts even if Redis is not reachable.
+    connection: OnceCell<ConnectionManager>,
+    key_prefix: String,
+    // 0 to process every submission.
+    window_seconds: u64,
+}
+
+impl DuplicateSubmissions {
+    pub fn new(
+        redis_uri: &Secret<String>,
+        key_prefix: String,
+        window_seconds: u64,
+    ) -> Result<Self, RedisError> {
+        Ok(Self {
+            client: redis::Client::open(redis_uri.expose_secret().as_str())?,
+            connection: OnceCell::new(),
+            key_prefix,
+            window_seconds,
+        })
+    }
+
+    /// `true` unless the same client submitted the same email to the same tenant within the window.
+    #[tracing::instrument(name = "Check for a duplicate submission", skip(self, email))]
+    pub async fn is_first_submission(
+        &self,
+        tenant_id: &str,
+        client_ip: Option<IpAddr>,
+        email: &str,
+    ) -> Result<bool, RedisError> {
+        if self.window_seconds == 0 {
+            return Ok(true);
+        }
+        let client_ip = client_ip.map_or_else(|| "unknown".into(), |ip| ip.to_string());
+        let key = format!(
+            "{}{tenant_id}:{client_ip}:{}",
+            self.key_prefix,
+            email.to_lowercase()
+        );
+        // `SET NX` replies `nil` if the key is already set.
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(self.window_seconds)
+            .query_async(&mut self.connection().await?)
+            .await?;
+        Ok(reply.is_some())
+    }
+
+    /// `ConnectionManager` reconnects on its own: it is cheap to clone and safe to keep around.
+    async fn connection(&self) -> Result<ConnectionManager, RedisError> {
+        self.connection
+            .get_or_try_init(|| self.client.get_tokio_connection_manager())
+            .await
+            .cloned()
+    }
+}
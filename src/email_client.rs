@@ -1,13 +1,110 @@
 use crate::domain::SubscriberEmail;
-use reqwest::{Client, Error, Url};
+use reqwest::{tls, Client, Error, Proxy, StatusCode, Url};
 use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Postmark rejects messages above 10 MB, attachments included.
+const MAX_ATTACHMENTS_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SendEmailError {
+    #[error("The attachments add up to {size} bytes, above the maximum of {max} bytes.")]
+    AttachmentsTooLarge { size: usize, max: usize },
+    #[error("The HTML and text bodies add up to {size} bytes, above the maximum of {max} bytes.")]
+    BodyTooLarge { size: usize, max: usize },
+    // We could not reach Postmark, it failed on its side, or it refused every email from us - e.g.
+    // the server token was revoked: the same email may go through later.
+    #[error(transparent)]
+    Transient(Error),
+    // Postmark rejected this email, for reasons specific to its recipient or its content.
+    #[error(transparent)]
+    Permanent(Error),
+}
+
+impl SendEmailError {
+    /// Sending the same email again is bound to fail the same way.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            Self::AttachmentsTooLarge { .. } | Self::BodyTooLarge { .. } | Self::Permanent(_) => {
+                true
+            }
+            Self::Transient(_) => false,
+        }
+    }
+
+    /// # Classifying Rejections
+    /// Only the rejections Postmark reports as specific to the email - see
+    /// `EMAIL_SPECIFIC_ERROR_CODES` - are permanent. Anything else is about our account or our
+    /// configuration - e.g. `401 Unauthorized` for a revoked server token, `422` for a suspended
+    /// account - and would fail every other email too: giving up on this one would not help, and
+    /// would drop a whole issue without anyone noticing.
+    fn from_rejection(e: Error, error_code: Option<i64>) -> Self {
+        let is_email_specific = e.status() == Some(StatusCode::UNPROCESSABLE_ENTITY)
+            && error_code.map_or(false, |code| EMAIL_SPECIFIC_ERROR_CODES.contains(&code));
+        if is_email_specific {
+            Self::Permanent(e)
+        } else {
+            Self::Transient(e)
+        }
+    }
+}
+
+impl From<Error> for SendEmailError {
+    /// Requests that failed before Postmark answered - it could not be reached, or the request
+    /// could not be built out of our configuration - are not specific to an email: they are
+    /// transient.
+    fn from(e: Error) -> Self {
+        Self::Transient(e)
+    }
+}
+
+/// Postmark's `ErrorCode`s for rejections of a single email: `300 Invalid email request` and
+/// `406 Inactive recipient` - e.g. they hard bounced, or marked a previous email as spam.
+const EMAIL_SPECIFIC_ERROR_CODES: &[i64] = &[300, 406];
+
+/// The body of Postmark's error responses.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PostmarkError {
+    error_code: i64,
+}
+
+/// A file attached to an email, in the format expected by Postmark's `Attachments` array.
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Attachment {
+    name: String,
+    // Base64-encoded
+    content: String,
+    content_type: String,
+}
+
+impl Attachment {
+    pub fn new(name: String, content_type: String, content: &[u8]) -> Self {
+        Self {
+            name,
+            content: base64::encode(content),
+            content_type,
+        }
+    }
+}
 
 pub struct EmailClient {
     http_client: Client,
+    request_timeout: std::time::Duration,
     base_url: Url,
     sender: SubscriberEmail,
     // We don't want to log this by accident
     authorization_token: Secret<String>,
+    unsubscribe_mailto: Option<String>,
+    confirmation_template: Option<String>,
+    max_body_size: Option<usize>,
+    reply_to: Option<String>,
+    // Display names for the `From` field of newsletter issues, by locale.
+    sender_names: HashMap<String, String>,
+    // Lowercase addresses and domains. Empty for no restriction.
+    recipient_allowlist: Vec<String>,
 }
 
 impl EmailClient {
@@ -15,60 +112,399 @@ impl EmailClient {
         base_url: &str,
         sender: SubscriberEmail,
         authorization_token: Secret<String>,
-        timeout: std::time::Duration,
+        connect_timeout: std::time::Duration,
+        request_timeout: std::time::Duration,
+        proxy: Option<&str>,
+        min_tls_version: Option<tls::Version>,
     ) -> Result<Self, String> {
-        match Url::parse(base_url) {
-            Ok(url) => Ok(Self {
-                http_client: Client::builder().timeout(timeout).build().unwrap(),
-                base_url: url,
-                sender,
-                authorization_token,
-            }),
-            Err(e) => Err(e.to_string()),
+        let base_url = Url::parse(base_url).map_err(|e| e.to_string())?;
+        // `connect_timeout` only bounds connecting: `request_timeout` is set on each request.
+        let mut builder = Client::builder().connect_timeout(connect_timeout);
+        // Without a proxy we connect to Postmark directly.
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(Proxy::all(proxy).map_err(|e| e.to_string())?);
+        }
+        if let Some(min_tls_version) = min_tls_version {
+            builder = builder.min_tls_version(min_tls_version);
         }
+        Ok(Self {
+            http_client: builder.build().map_err(|e| e.to_string())?,
+            request_timeout,
+            base_url,
+            sender,
+            authorization_token,
+            unsubscribe_mailto: None,
+            confirmation_template: None,
+            max_body_size: None,
+            reply_to: None,
+            sender_names: HashMap::new(),
+            recipient_allowlist: Vec::new(),
+        })
+    }
+
+    /// Newsletter issues advertise this address via the `List-Unsubscribe` header, alongside the
+    /// unsubscribe link of their recipient.
+    pub fn with_unsubscribe_mailto(mut self, mailto: Option<&str>) -> Self {
+        self.unsubscribe_mailto = mailto.map(String::from);
+        self
+    }
+
+    /// Confirmation emails are rendered by Postmark, using the template with this alias, if set.
+    pub fn with_confirmation_template(mut self, alias: Option<&str>) -> Self {
+        self.confirmation_template = alias.map(String::from);
+        self
+    }
+
+    /// Emails whose HTML and text bodies add up to more than `max_body_size` bytes are rejected
+    /// before sending. Postmark's own limit, attachments included, is 10 MB.
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+
+    /// Replies go to `reply_to`, if set, rather than to the sender.
+    pub fn with_reply_to(mut self, reply_to: Option<SubscriberEmail>) -> Self {
+        self.reply_to = reply_to.map(|r| r.as_ref().to_owned());
+        self
+    }
+
+    /// Newsletter issues are sent under the display name configured for the subscriber's locale.
+    pub fn with_sender_names(mut self, sender_names: HashMap<String, String>) -> Self {
+        self.sender_names = sender_names
+            .into_iter()
+            .map(|(locale, name)| (locale.to_lowercase(), name))
+            .collect();
+        self
+    }
+
+    /// # Recipient Allowlist
+    /// Outside of production, emails should only reach our own mailboxes. If `allowlist` is not
+    /// empty, emails are only sent to the addresses - e.g. `alice@example.com` - and the domains -
+    /// e.g. `example.com` - it lists: the others are dropped, as if they had been sent.
+    pub fn with_recipient_allowlist(mut self, allowlist: Vec<String>) -> Self {
+        self.recipient_allowlist = allowlist
+            .into_iter()
+            .map(|entry| entry.trim().trim_start_matches('@').to_lowercase())
+            .collect();
+        self
+    }
+
+    pub fn sender(&self) -> &SubscriberEmail {
+        &self.sender
+    }
+
+    pub fn confirmation_template(&self) -> Option<&str> {
+        self.confirmation_template.as_deref()
+    }
+
+    /// Check that our sender address is a confirmed sender signature on Postmark.
+    pub async fn is_sender_verified(&self, account_token: &Secret<String>) -> Result<bool, Error> {
+        let url = self.base_url.join("/senders").unwrap();
+
+        let response: SenderSignatures = self
+            .http_client
+            .get(url)
+            .timeout(self.request_timeout)
+            .query(&[("count", "500"), ("offset", "0")])
+            .header("X-Postmark-Account-Token", account_token.expose_secret())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(response
+            .sender_signatures
+            .iter()
+            .any(|s| s.confirmed && s.email_address.eq_ignore_ascii_case(self.sender.as_ref())))
     }
 
+    /// Returns the `Message-ID` of the email that has been sent.
     pub async fn send_email(
         &self,
         recipient: &SubscriberEmail,
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), Error> {
-        let url = self.base_url.join("/email").unwrap();
+        attachments: &[Attachment],
+    ) -> Result<String, SendEmailError> {
+        let envelope = Envelope {
+            from: self.sender.as_ref().to_owned(),
+            to: recipient,
+        };
+        self.send(
+            envelope,
+            subject,
+            html_content,
+            text_content,
+            attachments,
+            &[],
+        )
+        .await
+    }
 
-        let request_body = SendEmailRequest {
+    /// Same as `send_email`, with the `List-Unsubscribe` headers expected on mailing list emails:
+    /// `unsubscribe_link` is signed for the recipient, so that one-click unsubscribes know who to
+    /// unsubscribe. The sender's display name is localized according to `locale`, if configured.
+    pub async fn send_newsletter(
+        &self,
+        recipient: &SubscriberEmail,
+        locale: Option<&str>,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        unsubscribe_link: Option<&str>,
+    ) -> Result<String, SendEmailError> {
+        let mut headers = Vec::new();
+        let list_unsubscribe =
+            list_unsubscribe_header(self.unsubscribe_mailto.as_deref(), unsubscribe_link);
+        if let Some(list_unsubscribe) = &list_unsubscribe {
+            headers.push(EmailHeader {
+                name: "List-Unsubscribe",
+                value: list_unsubscribe,
+            });
+            // RFC 8058 one-click unsubscribe - only possible with an HTTPS URL.
+            if list_unsubscribe.contains("<https:") {
+                headers.push(EmailHeader {
+                    name: "List-Unsubscribe-Post",
+                    value: "List-Unsubscribe=One-Click",
+                });
+            }
+        }
+        let envelope = Envelope {
+            from: self.localized_sender(locale),
+            to: recipient,
+        };
+        self.send(envelope, subject, html_content, text_content, &[], &headers)
+            .await
+    }
+
+    /// `"<name>" <sender>`, with the name configured for `locale` - or for its language alone, e.g.
+    /// `fr` for `fr-CA`. The bare sender address if neither is configured.
+    fn localized_sender(&self, locale: Option<&str>) -> String {
+        let name = locale.map(str::to_lowercase).and_then(|locale| {
+            let language = locale.split(['-', '_']).next().unwrap_or_default();
+            self.sender_names
+                .get(&locale)
+                .or_else(|| self.sender_names.get(language))
+        });
+        match name {
+            Some(name) => {
+                let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("\"{name}\" <{}>", self.sender.as_ref())
+            }
+            None => self.sender.as_ref().to_owned(),
+        }
+    }
+
+    /// Send an email rendered by Postmark out of one of its server-side templates, identified by
+    /// its alias. The fields of `model` are available to the template as variables.
+    ///
+    /// Returns the `Message-ID` of the email that has been sent.
+    pub async fn send_template(
+        &self,
+        recipient: &SubscriberEmail,
+        template_alias: &str,
+        model: &impl serde::Serialize,
+    ) -> Result<String, SendEmailError> {
+        let message_id = self.message_id();
+        if !self.is_allowed_recipient(recipient) {
+            return Ok(message_id);
+        }
+        let headers = [EmailHeader {
+            name: "Message-ID",
+            value: &message_id,
+        }];
+        let request_body = SendEmailWithTemplateRequest {
             from: self.sender.as_ref(),
             to: recipient.as_ref(),
+            reply_to: self.reply_to.as_deref(),
+            template_alias,
+            template_model: model,
+            headers: &headers,
+        };
+        self.post("/email/withTemplate", &request_body).await?;
+
+        Ok(message_id)
+    }
+
+    async fn send(
+        &self,
+        envelope: Envelope<'_>,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        attachments: &[Attachment],
+        headers: &[EmailHeader<'_>],
+    ) -> Result<String, SendEmailError> {
+        // A runaway template is better caught here than rejected by Postmark.
+        let size = html_content.len() + text_content.len();
+        match self.max_body_size {
+            Some(max) if size > max => return Err(SendEmailError::BodyTooLarge { size, max }),
+            _ => {}
+        }
+        let size = attachments.iter().map(|a| a.content.len()).sum();
+        if size > MAX_ATTACHMENTS_SIZE {
+            return Err(SendEmailError::AttachmentsTooLarge {
+                size,
+                max: MAX_ATTACHMENTS_SIZE,
+            });
+        }
+
+        let message_id = self.message_id();
+        if !self.is_allowed_recipient(envelope.to) {
+            return Ok(message_id);
+        }
+        let mut headers = headers.to_vec();
+        headers.push(EmailHeader {
+            name: "Message-ID",
+            value: &message_id,
+        });
+        let request_body = SendEmailRequest {
+            from: &envelope.from,
+            to: envelope.to.as_ref(),
+            reply_to: self.reply_to.as_deref(),
             subject,
             html_body: html_content,
             text_body: text_content,
+            attachments,
+            headers: &headers,
         };
+        self.post("/email", &request_body).await?;
 
-        let _builder = self
+        Ok(message_id)
+    }
+
+    async fn post(&self, path: &str, body: &impl serde::Serialize) -> Result<(), SendEmailError> {
+        let url = self.base_url.join(path).unwrap();
+        let response = self
             .http_client
             .post(url)
+            .timeout(self.request_timeout)
             .header(
                 "X-Postmark-Server-Token",
                 self.authorization_token.expose_secret(),
             )
-            .json(&request_body)
+            .json(body)
             .send()
-            .await?
-            .error_for_status()?;
-
+            .await?;
+        if let Err(e) = response.error_for_status_ref() {
+            let error_code = response
+                .json::<PostmarkError>()
+                .await
+                .ok()
+                .map(|body| body.error_code);
+            return Err(SendEmailError::from_rejection(e, error_code));
+        }
         Ok(())
     }
+
+    /// Logs the emails that are dropped because of the recipient allowlist.
+    fn is_allowed_recipient(&self, recipient: &SubscriberEmail) -> bool {
+        if self.recipient_allowlist.is_empty() {
+            return true;
+        }
+        let address = recipient.as_ref().to_lowercase();
+        let domain = address
+            .rsplit_once('@')
+            .map(|(_, domain)| domain)
+            .unwrap_or_default();
+        let is_allowed = self
+            .recipient_allowlist
+            .iter()
+            .any(|entry| *entry == address || entry == domain);
+        if !is_allowed {
+            tracing::warn!(
+                recipient = %recipient.as_ref(),
+                "The recipient is not on the allowlist. Dropping the email."
+            );
+        }
+        is_allowed
+    }
+
+    /// A new, globally unique `Message-ID` (RFC 5322) on our sending domain.
+    fn message_id(&self) -> String {
+        let domain = self
+            .sender
+            .as_ref()
+            .rsplit_once('@')
+            .map(|(_, domain)| domain)
+            .unwrap_or("localhost");
+        format!("<{}@{domain}>", Uuid::new_v4())
+    }
+}
+
+/// Who an email is from - a display name may come with the address - and who it is for.
+struct Envelope<'a> {
+    from: String,
+    to: &'a SubscriberEmail,
 }
 
+/// The body of Postmark's `POST /email`. Optional fields are left out of the payload when unset,
+/// for Postmark to apply its defaults.
 #[derive(serde::Serialize)]
 #[serde(rename_all = "PascalCase")]
 struct SendEmailRequest<'a> {
     from: &'a str,
     to: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a str>,
     subject: &'a str,
     html_body: &'a str,
     text_body: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    attachments: &'a [Attachment],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [EmailHeader<'a>],
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailWithTemplateRequest<'a, M> {
+    from: &'a str,
+    to: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a str>,
+    template_alias: &'a str,
+    template_model: &'a M,
+    headers: &'a [EmailHeader<'a>],
+}
+
+/// A custom header, in the format expected by Postmark's `Headers` array.
+#[derive(serde::Serialize, Clone, Copy)]
+#[serde(rename_all = "PascalCase")]
+struct EmailHeader<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+/// Assembles the `List-Unsubscribe` header value (RFC 2369) out of the available options, if any.
+/// Replies to the `mailto:` address are expected to be processed by a rule on the operator's mail
+/// server.
+fn list_unsubscribe_header(mailto: Option<&str>, url: Option<&str>) -> Option<String> {
+    let options: Vec<String> = mailto
+        .map(|address| format!("<mailto:{address}?subject=unsubscribe>"))
+        .into_iter()
+        .chain(url.map(|url| format!("<{url}>")))
+        .collect();
+    if options.is_empty() {
+        None
+    } else {
+        Some(options.join(", "))
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SenderSignatures {
+    sender_signatures: Vec<SenderSignature>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SenderSignature {
+    email_address: String,
+    confirmed: bool,
 }
 
 #[cfg(test)]
@@ -78,7 +514,7 @@ mod tests {
     use fake::faker::internet::en::SafeEmail;
     use fake::faker::lorem::en::{Paragraph, Sentence};
     use fake::{Fake, Faker};
-    use wiremock::matchers::{any, header, header_exists, method, path};
+    use wiremock::matchers::{any, body_partial_json, header, header_exists, method, path};
     use wiremock::{Mock, MockServer, Request, ResponseTemplate};
 
     struct SendEmailBodyMatcher;
@@ -125,6 +561,9 @@ mod tests {
             email(),
             Secret::new(Faker.fake()),
             std::time::Duration::from_millis(200),
+            std::time::Duration::from_millis(200),
+            None,
+            None,
         )
         .unwrap()
     }
@@ -147,11 +586,387 @@ mod tests {
 
         // Act
         let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(&email(), &subject(), &content(), &content(), &[])
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_template_sends_the_alias_and_the_model_to_the_template_endpoint() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let recipient = email();
+        let model = serde_json::json!({ "confirmation_link": "https://example.com/confirm" });
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(path("/email/withTemplate"))
+            .and(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "To": recipient.as_ref(),
+                "TemplateAlias": "welcome",
+                "TemplateModel": model,
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_template(&recipient, "welcome", &model)
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[test]
+    fn send_email_request_serializes_to_the_postmark_shape() {
+        let attachments = [Attachment::new(
+            "issue.txt".into(),
+            "text/plain".into(),
+            b"Hello",
+        )];
+        let headers = [EmailHeader {
+            name: "Message-ID",
+            value: "<id@example.com>",
+        }];
+        let request = SendEmailRequest {
+            from: "sender@example.com",
+            to: "recipient@example.com",
+            reply_to: Some("replies@example.com"),
+            subject: "Subject",
+            html_body: "<p>Body</p>",
+            text_body: "Body",
+            attachments: &attachments,
+            headers: &headers,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "From": "sender@example.com",
+                "To": "recipient@example.com",
+                "ReplyTo": "replies@example.com",
+                "Subject": "Subject",
+                "HtmlBody": "<p>Body</p>",
+                "TextBody": "Body",
+                "Attachments": [{
+                    "Name": "issue.txt",
+                    "Content": "SGVsbG8=",
+                    "ContentType": "text/plain",
+                }],
+                "Headers": [{ "Name": "Message-ID", "Value": "<id@example.com>" }],
+            })
+        );
+    }
+
+    #[test]
+    fn send_email_request_leaves_unset_optionals_out() {
+        let request = SendEmailRequest {
+            from: "sender@example.com",
+            to: "recipient@example.com",
+            reply_to: None,
+            subject: "Subject",
+            html_body: "<p>Body</p>",
+            text_body: "Body",
+            attachments: &[],
+            headers: &[],
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "From": "sender@example.com",
+                "To": "recipient@example.com",
+                "Subject": "Subject",
+                "HtmlBody": "<p>Body</p>",
+                "TextBody": "Body",
+            })
+        );
+    }
+
+    #[test]
+    fn the_client_can_require_a_minimum_tls_version() {
+        for min_tls_version in [tls::Version::TLS_1_2, tls::Version::TLS_1_3] {
+            let outcome = EmailClient::new(
+                "https://api.postmarkapp.com",
+                email(),
+                Secret::new(Faker.fake()),
+                std::time::Duration::from_millis(200),
+                std::time::Duration::from_millis(200),
+                None,
+                Some(min_tls_version),
+            );
+
+            assert_ok!(outcome);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_goes_through_the_configured_proxy() {
+        // Arrange
+        let proxy_server = MockServer::start().await;
+        // `.invalid` is reserved: the request can only succeed by going through the proxy.
+        let email_client = EmailClient::new(
+            "http://postmark.invalid",
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            std::time::Duration::from_millis(200),
+            Some(&proxy_server.uri()),
+            None,
+        )
+        .unwrap();
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .and(header("Host", "postmark.invalid"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&proxy_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), &[])
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[test]
+    fn list_unsubscribe_header_contains_all_the_available_options() {
+        let header = list_unsubscribe_header(
+            Some("unsubscribe@example.com"),
+            Some("https://example.com/unsubscribe"),
+        );
+
+        assert_eq!(
+            header.as_deref(),
+            Some(
+                "<mailto:unsubscribe@example.com?subject=unsubscribe>, \
+                <https://example.com/unsubscribe>"
+            )
+        );
+        assert_eq!(list_unsubscribe_header(None, None), None);
+    }
+
+    #[tokio::test]
+    async fn send_newsletter_sets_the_list_unsubscribe_headers() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri())
+            .with_unsubscribe_mailto(Some("unsubscribe@example.com"));
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_newsletter(
+                &email(),
+                None,
+                &subject(),
+                &content(),
+                &content(),
+                Some("https://example.com/unsubscribe?subscription_token=a-token"),
+            )
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let headers = body["Headers"].as_array().unwrap();
+        assert_eq!(headers[0]["Name"], "List-Unsubscribe");
+        let list_unsubscribe = headers[0]["Value"].as_str().unwrap();
+        assert!(list_unsubscribe.contains("<mailto:unsubscribe@example.com"));
+        assert!(list_unsubscribe
+            .contains("<https://example.com/unsubscribe?subscription_token=a-token>"));
+        assert_eq!(headers[1]["Name"], "List-Unsubscribe-Post");
+    }
+
+    #[tokio::test]
+    async fn emails_to_recipients_off_the_allowlist_are_dropped() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri())
+            .with_recipient_allowlist(vec!["qa@example.com".into(), "@staging.example.com".into()]);
+        let allowed = [
+            SubscriberEmail::parse("QA@example.com".into()).unwrap(),
+            SubscriberEmail::parse("ursula@staging.example.com".into()).unwrap(),
+        ];
+        let dropped = [
+            SubscriberEmail::parse("ursula_le_guin@gmail.com".into()).unwrap(),
+            SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+        ];
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        for recipient in allowed.iter().chain(dropped.iter()) {
+            let outcome = email_client
+                .send_email(recipient, &subject(), &content(), &content(), &[])
+                .await;
+            assert_ok!(outcome);
+        }
+
+        // Assert
+        let recipients: Vec<String> = mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| {
+                let body: serde_json::Value = serde_json::from_slice(&r.body).unwrap();
+                body["To"].as_str().unwrap().to_owned()
+            })
+            .collect();
+        assert_eq!(
+            recipients,
+            vec!["QA@example.com", "ursula@staging.example.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn is_sender_verified_looks_for_a_confirmed_sender_signature() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let signatures = serde_json::json!({
+            "TotalCount": 2,
+            "SenderSignatures": [
+                {"EmailAddress": email().as_ref(), "Confirmed": true},
+                {"EmailAddress": email_client.sender().as_ref(), "Confirmed": false}
+            ]
+        });
+
+        Mock::given(header_exists("X-Postmark-Account-Token"))
+            .and(path("/senders"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(signatures))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .is_sender_verified(&Secret::new(Faker.fake()))
+            .await;
+
+        // Assert
+        assert!(!outcome.unwrap());
+    }
+
+    #[tokio::test]
+    async fn send_email_serializes_attachments() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let attachment = Attachment::new(
+            "issue.pdf".into(),
+            "application/pdf".into(),
+            b"%PDF-1.4 not really a pdf",
+        );
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), &[attachment])
             .await;
 
         // Assert
         assert_ok!(outcome);
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let attachments = body["Attachments"].as_array().unwrap();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0]["Name"], "issue.pdf");
+        assert_eq!(attachments[0]["ContentType"], "application/pdf");
+        assert_eq!(
+            attachments[0]["Content"],
+            base64::encode(b"%PDF-1.4 not really a pdf")
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_rejects_attachments_above_the_size_limit() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let attachment = Attachment::new(
+            "huge.bin".into(),
+            "application/octet-stream".into(),
+            &vec![0; MAX_ATTACHMENTS_SIZE],
+        );
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), &[attachment])
+            .await;
+
+        // Assert
+        assert!(matches!(
+            outcome,
+            Err(SendEmailError::AttachmentsTooLarge { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_email_rejects_bodies_above_the_size_limit_without_sending() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri()).with_max_body_size(1024);
+        let html_content = "a".repeat(1000);
+        let text_content = "a".repeat(100);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &html_content, &text_content, &[])
+            .await;
+
+        // Assert
+        let error = outcome.unwrap_err();
+        assert!(matches!(
+            error,
+            SendEmailError::BodyTooLarge {
+                size: 1100,
+                max: 1024
+            }
+        ));
+        assert!(error.is_permanent());
     }
 
     #[tokio::test]
@@ -169,12 +984,147 @@ mod tests {
             .await;
 
         let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(&email(), &subject(), &content(), &content(), &[])
             .await;
 
         assert_err!(outcome);
     }
 
+    #[tokio::test]
+    async fn send_email_gives_up_connecting_after_the_connect_timeout() {
+        // Arrange
+        // Packets to this TEST-NET-1 address (RFC 5737) go unanswered: connecting never completes.
+        // Some networks reject them right away instead, hence only the connect error is asserted.
+        let email_client = EmailClient::new(
+            "http://192.0.2.1",
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            std::time::Duration::from_secs(30),
+            None,
+            None,
+        )
+        .unwrap();
+
+        // Act
+        let start = std::time::Instant::now();
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), &[])
+            .await;
+
+        // Assert
+        match &outcome {
+            Err(SendEmailError::Transient(e)) => assert!(e.is_connect()),
+            _ => panic!("Expected a transient error, got {outcome:?}"),
+        }
+        // Well below the request timeout, with some leeway for slow CI runners.
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_transiently_if_postmark_cannot_be_reached() {
+        // Arrange
+        // Nothing can be resolved under the reserved `.invalid` top-level domain (RFC 2606).
+        let email_client = email_client("http://postmark.invalid".into());
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), &[])
+            .await;
+
+        // Assert
+        let error = assert_err!(outcome);
+        assert!(matches!(error, SendEmailError::Transient(_)));
+        assert!(!error.is_permanent());
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_transiently_if_the_url_is_malformed() {
+        // Arrange
+        let email_client = email_client("ftp://postmark.example.com".into());
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), &[])
+            .await;
+
+        // Assert - Every other email would fail the same way: it is our configuration that is wrong
+        let error = assert_err!(outcome);
+        assert!(matches!(error, SendEmailError::Transient(_)));
+        assert!(!error.is_permanent());
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_permanently_if_postmark_rejects_the_recipient() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "ErrorCode": 406,
+                "Message": "You tried to send to recipient(s) that have been marked as inactive."
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), &[])
+            .await;
+
+        // Assert
+        assert!(assert_err!(outcome).is_permanent());
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_transiently_if_postmark_rejects_the_account() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "ErrorCode": 412,
+                "Message": "Your account is pending approval."
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), &[])
+            .await;
+
+        // Assert
+        assert!(!assert_err!(outcome).is_permanent());
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_transiently_if_the_server_token_is_rejected() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+                "ErrorCode": 10,
+                "Message": "The Server Token you provided in the X-Postmark-Server-Token request header was invalid."
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content(), &[])
+            .await;
+
+        // Assert - The issue should not be dropped for every subscriber
+        let error = assert_err!(outcome);
+        assert!(matches!(error, SendEmailError::Transient(_)));
+        assert!(!error.is_permanent());
+    }
+
     #[tokio::test]
     async fn send_email_fails_if_the_server_returns_500() {
         // Arrange
@@ -189,10 +1139,24 @@ mod tests {
 
         // Act
         let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
+            .send_email(&email(), &subject(), &content(), &content(), &[])
             .await;
 
         // Assert
         assert_err!(outcome);
     }
+
+    #[test]
+    fn newsletters_are_sent_under_the_name_configured_for_the_locale() {
+        let email_client = email_client("http://localhost".into())
+            .with_sender_names(HashMap::from([("fr".into(), "Notre \"bulletin\"".into())]));
+        let sender = email_client.sender().as_ref();
+
+        assert_eq!(
+            email_client.localized_sender(Some("fr-CA")),
+            format!("\"Notre \\\"bulletin\\\"\" <{sender}>")
+        );
+        assert_eq!(email_client.localized_sender(Some("de")), sender);
+        assert_eq!(email_client.localized_sender(None), sender);
+    }
 }
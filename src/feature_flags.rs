@@ -0,0 +1,64 @@
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// Subscribers must confirm their email address before getting our newsletter. Defaults to
+/// `subscriptions.double_opt_in`, i.e. on unless configured otherwise.
+pub const DOUBLE_OPT_IN: &str = "double_opt_in";
+
+/// # Feature Flags
+/// Each environment sets the defaults of its feature flags in the `features` section of its
+/// configuration file, e.g. `local.yaml`. A row in `feature_flags` overrides the default, without a
+/// deployment. Flags that are neither configured nor overridden are off.
+#[derive(Debug)]
+pub struct FeatureFlags {
+    defaults: HashMap<String, bool>,
+}
+
+impl FeatureFlags {
+    pub fn new(defaults: HashMap<String, bool>) -> Self {
+        Self { defaults }
+    }
+
+    /// What the configuration says, overrides aside.
+    pub fn default_for(&self, name: &str) -> bool {
+        self.defaults.get(name).copied().unwrap_or(false)
+    }
+
+    #[tracing::instrument(name = "Check a feature flag", skip(self, pool))]
+    pub async fn is_enabled(&self, pool: &PgPool, name: &str) -> Result<bool, sqlx::Error> {
+        let r = sqlx::query!("SELECT enabled FROM feature_flags WHERE name = $1", name)
+            .fetch_optional(pool)
+            .await?;
+        Ok(r.map_or_else(|| self.default_for(name), |r| r.enabled))
+    }
+
+    /// Like `is_enabled`, but falls back to the configured default if the database cannot tell:
+    /// an override that cannot be looked up must not fail the request.
+    pub async fn is_enabled_or_default(&self, pool: &PgPool, name: &str) -> bool {
+        match self.is_enabled(pool, name).await {
+            Ok(enabled) => enabled,
+            Err(e) => {
+                tracing::warn!(error.cause_chain = ?e, error.message = %e, flag = name,
+                    "Failed to check a feature flag. Falling back to its default.");
+                self.default_for(name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_default_to_their_configured_value_or_off() {
+        let flags = FeatureFlags::new(HashMap::from([
+            ("on".to_string(), true),
+            ("off".to_string(), false),
+        ]));
+
+        assert!(flags.default_for("on"));
+        assert!(!flags.default_for("off"));
+        assert!(!flags.default_for("unknown"));
+    }
+}
@@ -12,6 +12,10 @@ impl TryFrom<String> for IdempotencyKey {
         if s.len() >= max_length {
             anyhow::bail!("The idempotency key must be shorter than {max_length} characters");
         }
+        // Keys travel in headers too, where anything else would have to be escaped.
+        if !s.chars().all(|c| c.is_ascii_graphic()) {
+            anyhow::bail!("The idempotency key can only contain visible ASCII characters");
+        }
         Ok(Self(s))
     }
 }
@@ -27,3 +31,21 @@ impl AsRef<str> for IdempotencyKey {
         &self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::IdempotencyKey;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn uuids_are_valid_keys() {
+        assert_ok!(IdempotencyKey::try_from(uuid::Uuid::new_v4().to_string()));
+    }
+
+    #[test]
+    fn empty_long_or_non_ascii_keys_are_rejected() {
+        for key in [String::new(), "a".repeat(50), "a key".into(), "clé".into()] {
+            assert_err!(IdempotencyKey::try_from(key));
+        }
+    }
+}
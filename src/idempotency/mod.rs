@@ -2,5 +2,6 @@ mod key;
 mod persistence;
 
 pub use key::IdempotencyKey;
-pub use persistence::{get_saved_response, save_response};
+pub use persistence::get_recent_keys;
+pub use persistence::save_response;
 pub use persistence::{try_processing, NextAction};
@@ -1,8 +1,10 @@
 use super::IdempotencyKey;
+use crate::configuration::IdempotencySettings;
 use actix_web::{body::to_bytes, http::StatusCode, HttpResponse};
 use anyhow::anyhow;
 use sqlx::postgres::PgTypeInfo;
 use sqlx::{postgres::PgHasArrayType, PgPool, Postgres, Transaction};
+use std::time::Duration;
 use uuid::Uuid;
 
 #[derive(Debug, sqlx::Type)]
@@ -12,6 +14,7 @@ struct HeaderPairRecord {
     value: Vec<u8>,
 }
 
+/// Returns `None` if there is no saved response yet - e.g. the request is still being processed.
 pub async fn get_saved_response(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
@@ -26,7 +29,8 @@ pub async fn get_saved_response(
         FROM idempotency
         WHERE
             user_id = $1 AND
-            idempotency_key = $2
+            idempotency_key = $2 AND
+            response_status_code IS NOT NULL
         "#,
         user_id,
         idempotency_key.as_ref()
@@ -66,16 +70,25 @@ pub async fn get_saved_response(
 /// * Buffer the whole body in memory via to_bytes;
 /// * *Do whatever you have to do with the body;*
 /// * Re-assemble the response using .set_body() on the request head.
+///
+/// Bodies larger than `max_body_bytes` are not stored: the key is marked as not replayable, and
+/// `try_processing` has retries processed again.
 pub async fn save_response(
     mut transaction: Transaction<'static, Postgres>,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
     http_response: HttpResponse,
+    max_body_bytes: usize,
 ) -> Result<HttpResponse, anyhow::Error> {
     let (response_head, body) = http_response.into_parts();
     // `MessageBody::Error` is not `Send` + `Sync`, therefore it doesn't play nicely with `anyhow`
     let body = to_bytes(body).await.map_err(|e| anyhow::anyhow!("{e}"))?;
     let status_code = response_head.status().as_u16() as i16;
+    if body.len() > max_body_bytes {
+        mark_as_not_replayable(&mut transaction, idempotency_key, user_id, status_code).await?;
+        transaction.commit().await?;
+        return Ok(response_head.set_body(body).map_into_boxed_body());
+    }
     let headers = {
         let mut h = Vec::with_capacity(response_head.headers().len());
         for (name, value) in response_head.headers().iter() {
@@ -94,7 +107,8 @@ pub async fn save_response(
         SET
             response_status_code = $3,
             response_headers = $4,
-            response_body = $5
+            response_body = $5,
+            replayable = true
         WHERE
             user_id = $1 AND idempotency_key = $2
         "#,
@@ -106,18 +120,102 @@ pub async fn save_response(
     )
     .execute(&mut transaction)
     .await?;
+    transaction.commit().await?;
 
     // We need `.map_into_boxed_body` to go from `HttpResponse<Bytes>` to `HttpResponse<BoxBody>`
     let http_response = response_head.set_body(body).map_into_boxed_body();
     Ok(http_response)
 }
 
+/// The status code is still recorded, to show the request has been processed.
+async fn mark_as_not_replayable(
+    transaction: &mut Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    status_code: i16,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE idempotency
+        SET
+            response_status_code = $3,
+            response_headers = NULL,
+            response_body = NULL,
+            replayable = false
+        WHERE
+            user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        status_code
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
 impl PgHasArrayType for HeaderPairRecord {
     fn array_type_info() -> PgTypeInfo {
         sqlx::postgres::PgTypeInfo::with_name("_header_pair")
     }
 }
 
+/// Replays are surfaced to admins to help debugging retries.
+async fn record_replay(
+    pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE idempotency
+        SET replay_count = replay_count + 1
+        WHERE
+            user_id = $1 AND
+            idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub struct IdempotencyRecord {
+    pub idempotency_key: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    // `None` while the request is still being processed.
+    pub response_status_code: Option<i16>,
+    pub replay_count: i32,
+}
+
+/// The most recent idempotency keys used by `user_id`, newest first. The saved responses are left
+/// out on purpose: they might contain sensitive data.
+pub async fn get_recent_keys(
+    pool: &PgPool,
+    user_id: Uuid,
+    limit: i64,
+) -> Result<Vec<IdempotencyRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        IdempotencyRecord,
+        r#"
+        SELECT idempotency_key, created_at, response_status_code, replay_count
+        FROM idempotency
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+        user_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum NextAction {
     // Return transaction for later usage
     StartProcessing(Transaction<'static, Postgres>),
@@ -128,6 +226,7 @@ pub async fn try_processing(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
+    settings: &IdempotencySettings,
 ) -> Result<NextAction, anyhow::Error> {
     let mut transaction = pool.begin().await?;
     let n_inserted_rows = sqlx::query!(
@@ -147,12 +246,66 @@ pub async fn try_processing(
     .await?
     .rows_affected();
 
-    if n_inserted_rows > 0 {
+    // Responses too large to be stored cannot be replayed: the request is processed again. The
+    // row stays locked until then, hence concurrent retries wait for us to be done.
+    if n_inserted_rows > 0 || !is_replayable(&mut transaction, idempotency_key, user_id).await? {
         Ok(NextAction::StartProcessing(transaction))
     } else {
-        let saved_response = get_saved_response(pool, idempotency_key, user_id)
-            .await?
-            .ok_or_else(|| anyhow!("We expected a saved response, we didn't find it"))?;
+        // `is_replayable` locked the row: we must release it before `record_replay` updates it over
+        // another connection, or we would wait on ourselves.
+        transaction.rollback().await?;
+        let saved_response =
+            wait_for_saved_response(pool, idempotency_key, user_id, settings).await?;
+        record_replay(pool, idempotency_key, user_id).await?;
         Ok(NextAction::ReturnSavedResponse(saved_response))
     }
 }
+
+async fn is_replayable(
+    transaction: &mut Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let r = sqlx::query!(
+        r#"
+        SELECT replayable
+        FROM idempotency
+        WHERE
+            user_id = $1 AND
+            idempotency_key = $2
+        FOR UPDATE
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .fetch_one(transaction)
+    .await?;
+
+    Ok(r.replayable)
+}
+
+/// # Racing Retries
+/// The request we are a retry of might not have saved its response yet, e.g. because it committed
+/// the idempotency record before processing it. Rather than failing, we check again a few times,
+/// backing off in between, until its response shows up.
+///
+/// We must not hold the row lock while we wait: the request we are waiting on needs it to save its
+/// response.
+async fn wait_for_saved_response(
+    pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    settings: &IdempotencySettings,
+) -> Result<HttpResponse, anyhow::Error> {
+    let mut backoff = Duration::from_millis(settings.saved_response_backoff_milliseconds);
+    for _ in 0..settings.saved_response_retries {
+        if let Some(saved_response) = get_saved_response(pool, idempotency_key, user_id).await? {
+            return Ok(saved_response);
+        }
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+    get_saved_response(pool, idempotency_key, user_id)
+        .await?
+        .ok_or_else(|| anyhow!("We expected a saved response, we didn't find it"))
+}
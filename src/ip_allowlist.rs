@@ -0,0 +1,34 @@
+use crate::client_ip::client_ip;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{web, HttpResponse};
+use actix_web_lab::middleware::Next;
+use ipnet::IpNet;
+
+/// The networks the admin panel can be reached from. Empty if it is reachable from anywhere.
+pub struct AdminAllowedNetworks(pub Vec<IpNet>);
+
+/// # Defense in Depth
+/// The admin panel is protected by a login already: restricting it to known networks (e.g. the
+/// office) means a leaked password is not enough to get in.
+///
+/// Requests from outside the allowed networks get a `403 Forbidden`, before authentication.
+pub async fn reject_disallowed_networks(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let is_allowed = match req.app_data::<web::Data<AdminAllowedNetworks>>() {
+        Some(allowed) if !allowed.0.is_empty() => client_ip(req.request())
+            .map(|ip| allowed.0.iter().any(|network| network.contains(&ip)))
+            .unwrap_or(false),
+        _ => true,
+    };
+    if !is_allowed {
+        tracing::warn!(client_ip = ?client_ip(req.request()), "Admin panel request from a disallowed network.");
+        let response = HttpResponse::Forbidden().finish();
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
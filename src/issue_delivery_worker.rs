@@ -1,11 +1,27 @@
+use crate::configuration::{NewsletterSettings, QuietHoursSettings, Settings, WarmUpSettings};
+use crate::confirmation_retries::confirmation_retry_loop;
 use crate::domain::SubscriberEmail;
 use crate::email_client::EmailClient;
-use crate::{configuration::Settings, startup::get_connection_pool};
-use sqlx::{PgPool, Postgres, Transaction};
+use crate::retry_budget::RetryBudget;
+use crate::signed_token::{manage_data_link, unsubscribe_link};
+use crate::startup::{get_connection_pool, load_templates};
+use crate::welcome_series::{try_execute_welcome_step, validate_welcome_series};
+use crate::worker_pause::WorkerPause;
+use anyhow::Context as _;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
+use tera::{Context, Tera};
 use tracing::{field::display, Span};
 use uuid::Uuid;
 
+/// How long a delivery is deferred after a transient failure.
+const RETRY_DELAY_SECONDS: i64 = 60;
+/// How long a delivery is deferred after a transient failure once the retry budget is exhausted:
+/// long enough for the budget to refill, e.g. once our email provider has recovered.
+const EXHAUSTED_BUDGET_RETRY_DELAY_SECONDS: i64 = 600;
+
 pub enum ExecutionOutcome {
     TaskCompleted,
     EmptyQueue,
@@ -22,33 +38,126 @@ pub enum ExecutionOutcome {
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    retry_budget: &RetryBudget,
+    configuration: &Settings,
+    templates: &Tera,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
+    let settings = &configuration.newsletter;
     let task = dequeue_task(pool).await?;
     if task.is_none() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
 
-    let (transaction, issue_id, email) = task.unwrap();
+    let (mut transaction, issue_id, email, n_retries) = task.unwrap();
 
     {
         Span::current()
-            .record("newsletter_issue_id", &display(issue_id))
-            .record("subscriber_email", &display(&email));
+            .record("newsletter_issue_id", display(issue_id))
+            .record("subscriber_email", display(&email));
 
         match SubscriberEmail::parse(email.clone()) {
-            Ok(email) => {
-                let issue = get_issue(pool, issue_id).await?;
-                if let Err(e) = email_client
-                    .send_email(
-                        &email,
-                        &issue.title,
-                        &issue.html_content,
-                        &issue.text_content,
-                    )
-                    .await
+            Ok(subscriber_email) => {
+                // The same (issue, subscriber) pair might have been enqueued more than once - e.g. due
+                // to a bug in the enqueuing logic. We never want to send the same issue twice.
+                if is_already_delivered(&mut transaction, issue_id, &email).await? {
+                    tracing::info!(
+                        "The issue has already been delivered to this subscriber. Skipping."
+                    );
+                } else if has_reached_frequency_cap(&mut transaction, issue_id, &email, settings)
+                    .await?
+                {
+                    tracing::info!(
+                        "The subscriber got another issue too recently. Skipping and recording it."
+                    );
+                    record_skipped_delivery(&mut transaction, issue_id, &email).await?;
+                } else if let Some(execute_after) = quiet_hours_deferral(
+                    &mut transaction,
+                    configuration.quiet_hours.as_ref(),
+                    issue_id,
+                    &email,
+                )
+                .await?
+                {
+                    tracing::info!(%execute_after, "It is quiet hours. Deferring.");
+                    defer_task(transaction, issue_id, &email, execute_after).await?;
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                } else if let Some(execute_after) =
+                    warm_up_deferral(&mut transaction, configuration.warm_up.as_ref()).await?
                 {
-                    tracing::error!(error.cause_chain = ?e, error.message = %e,
-                        "Failed to deliver issue to confirmed subscriber. Skipping.");
+                    tracing::info!(%execute_after, "The warm-up daily limit has been reached. Deferring.");
+                    defer_task(transaction, issue_id, &email, execute_after).await?;
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                } else {
+                    let issue = get_issue(pool, issue_id).await?;
+                    let subscriber =
+                        get_subscriber_profile(&mut transaction, &issue.tenant_id, &email).await?;
+                    let base_url = configuration.base_url_of_tenant(&issue.tenant_id);
+                    let hmac_secret = &configuration.application.hmac_secret;
+                    let unsubscribe_link = subscriber
+                        .id
+                        .map(|id| unsubscribe_link(base_url, hmac_secret, id))
+                        .transpose()
+                        .map_err(anyhow::Error::msg)?;
+                    let manage_data_link = subscriber
+                        .id
+                        .map(|id| manage_data_link(base_url, hmac_secret, id))
+                        .transpose()
+                        .map_err(anyhow::Error::msg)?
+                        .unwrap_or_default();
+                    match issue.personalize(
+                        &subscriber.name,
+                        unsubscribe_link.as_deref().unwrap_or_default(),
+                        &manage_data_link,
+                        settings,
+                        templates,
+                    ) {
+                        Ok((html_content, text_content)) => match email_client
+                            .send_newsletter(
+                                &subscriber_email,
+                                subscriber.locale.as_deref(),
+                                &issue.title,
+                                &html_content,
+                                &text_content,
+                                unsubscribe_link.as_deref(),
+                            )
+                            .await
+                        {
+                            Ok(message_id) => {
+                                record_delivery(&mut transaction, issue_id, &email, &message_id)
+                                    .await?
+                            }
+                            Err(e) if e.is_permanent() => {
+                                tracing::error!(error.cause_chain = ?e, error.message = %e,
+                                    %issue_id, "The issue cannot be delivered as is. Skipping.");
+                            }
+                            Err(e) if n_retries >= settings.max_retries_per_delivery as i32 => {
+                                tracing::error!(error.cause_chain = ?e, error.message = %e,
+                                    n_retries, "Failed to deliver issue to confirmed subscriber. \
+                                    It has been retried too many times. Skipping.");
+                            }
+                            Err(e) => {
+                                let retry_delay = if retry_budget.try_acquire() {
+                                    tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                                        "Failed to deliver issue to confirmed subscriber. \
+                                        Retrying later.");
+                                    RETRY_DELAY_SECONDS
+                                } else {
+                                    tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                                        "Failed to deliver issue to confirmed subscriber. \
+                                        The retry budget is exhausted. Retrying much later.");
+                                    EXHAUSTED_BUDGET_RETRY_DELAY_SECONDS
+                                };
+                                let execute_after =
+                                    Utc::now() + chrono::Duration::seconds(retry_delay);
+                                retry_task(transaction, issue_id, &email, execute_after).await?;
+                                return Ok(ExecutionOutcome::TaskCompleted);
+                            }
+                        },
+                        Err(e) => {
+                            tracing::error!(error.cause_chain = ?e, error.message = %e,
+                                "Failed to render the issue for a confirmed subscriber. Skipping.");
+                        }
+                    }
                 }
             }
             Err(e) => {
@@ -64,34 +173,70 @@ pub async fn try_execute_task(
 
 type PgTransaction = Transaction<'static, Postgres>;
 
+/// # Claiming Tasks
+/// A task is claimed, and the claim committed, before it is processed: we do not want to hold a
+/// transaction open while we wait for Postmark. If the worker crashes mid-send, the task stays
+/// claimed until `requeue_stale_claims` makes it available again.
 #[tracing::instrument(skip_all)]
 async fn dequeue_task(
     pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
-    let mut transaction = pool.begin().await?;
+) -> Result<Option<(PgTransaction, Uuid, String, i32)>, anyhow::Error> {
     let r = sqlx::query!(
         r#"
-        SELECT newsletter_issue_id, subscriber_email
-        FROM issue_delivery_queue
-        FOR UPDATE
-        SKIP LOCKED
-        LIMIT 1
+        UPDATE issue_delivery_queue
+        SET claimed_at = now()
+        WHERE (newsletter_issue_id, subscriber_email) = (
+            SELECT newsletter_issue_id, subscriber_email
+            FROM issue_delivery_queue
+            WHERE execute_after <= now() AND claimed_at IS NULL
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT 1
+        )
+        RETURNING newsletter_issue_id, subscriber_email, n_retries
         "#,
     )
-    .fetch_optional(&mut transaction)
+    .fetch_optional(pool)
     .await?;
 
     if let Some(r) = r {
         Ok(Some((
-            transaction,
+            pool.begin().await?,
             r.newsletter_issue_id,
             r.subscriber_email,
+            r.n_retries,
         )))
     } else {
         Ok(None)
     }
 }
 
+/// Releases the tasks that have been claimed for longer than `claim_timeout`, e.g. because the
+/// worker that claimed them crashed. Returns the number of released tasks.
+#[tracing::instrument(skip(pool))]
+pub async fn requeue_stale_claims(
+    pool: &PgPool,
+    claim_timeout: Duration,
+) -> Result<u64, anyhow::Error> {
+    let claimed_before = Utc::now() - chrono::Duration::from_std(claim_timeout)?;
+    let n_requeued = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET claimed_at = NULL
+        WHERE claimed_at < $1
+        "#,
+        claimed_before
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    if n_requeued > 0 {
+        tracing::warn!(n_requeued, "Re-queued delivery tasks with a stale claim.");
+    }
+    Ok(n_requeued)
+}
+
 #[tracing::instrument(skip_all)]
 async fn delete_task(
     mut transaction: PgTransaction,
@@ -115,18 +260,447 @@ async fn delete_task(
     Ok(())
 }
 
+/// Puts the task back in the queue, to be picked up again at `execute_after`.
+#[tracing::instrument(skip_all)]
+async fn defer_task(
+    mut transaction: PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    execute_after: DateTime<Utc>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET execute_after = $3, claimed_at = NULL
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        issue_id,
+        email,
+        execute_after
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Puts a task that failed back in the queue, to be retried at `execute_after` - see
+/// `max_retries_per_delivery`.
+#[tracing::instrument(skip_all)]
+async fn retry_task(
+    mut transaction: PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    execute_after: DateTime<Utc>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET execute_after = $3, claimed_at = NULL, n_retries = n_retries + 1
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        issue_id,
+        email,
+        execute_after
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// While warming up, sends above today's limit are deferred to the start of the next day (UTC).
+#[tracing::instrument(skip_all)]
+async fn warm_up_deferral(
+    transaction: &mut PgTransaction,
+    warm_up: Option<&WarmUpSettings>,
+) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let warm_up = match warm_up {
+        Some(warm_up) => warm_up,
+        None => return Ok(None),
+    };
+    let today = Utc::now().date_naive();
+    let daily_limit = match warm_up.daily_limit(today).map_err(anyhow::Error::msg)? {
+        Some(daily_limit) => daily_limit,
+        None => return Ok(None),
+    };
+
+    let r = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "sent_today!"
+        FROM newsletter_deliveries
+        WHERE delivered_at >= $1 AND status = 'delivered'
+        "#,
+        start_of_day(today)
+    )
+    .fetch_one(transaction)
+    .await?;
+
+    if r.sent_today < daily_limit as i64 {
+        Ok(None)
+    } else {
+        Ok(Some(start_of_day(today.succ_opt().unwrap())))
+    }
+}
+
+/// Sends during quiet hours are deferred to their end, in the time zone of the subscriber - or the
+/// configured one, if they did not tell us theirs.
+#[tracing::instrument(skip_all)]
+async fn quiet_hours_deferral(
+    transaction: &mut PgTransaction,
+    quiet_hours: Option<&QuietHoursSettings>,
+    issue_id: Uuid,
+    email: &str,
+) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let quiet_hours = match quiet_hours {
+        Some(quiet_hours) => quiet_hours,
+        None => return Ok(None),
+    };
+    let time_zone = sqlx::query!(
+        r#"
+        SELECT s.time_zone
+        FROM subscriptions s
+        JOIN newsletter_issues i ON i.tenant_id = s.tenant_id
+        WHERE i.newsletter_issue_id = $1 AND s.email = $2
+        "#,
+        issue_id,
+        email
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    .and_then(|r| r.time_zone)
+    .unwrap_or_else(|| quiet_hours.time_zone.clone());
+    quiet_hours_end(transaction, quiet_hours, &time_zone, Utc::now()).await
+}
+
+/// The end of the quiet hours, if `now` falls within them in `time_zone`. Postgres converts between
+/// UTC and local times: its time zone database knows when daylight saving time starts and ends.
+pub async fn quiet_hours_end(
+    connection: &mut PgConnection,
+    quiet_hours: &QuietHoursSettings,
+    time_zone: &str,
+    now: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let local_now = sqlx::query!(
+        r#"SELECT $1::timestamptz AT TIME ZONE $2 AS "local_now!""#,
+        now,
+        time_zone
+    )
+    .fetch_one(&mut *connection)
+    .await?
+    .local_now;
+    let local_end = match quiet_hours
+        .deferral(local_now)
+        .map_err(anyhow::Error::msg)?
+    {
+        Some(local_end) => local_end,
+        None => return Ok(None),
+    };
+    let end = sqlx::query!(
+        r#"SELECT $1::timestamp AT TIME ZONE $2 AS "end!""#,
+        local_end,
+        time_zone
+    )
+    .fetch_one(connection)
+    .await?
+    .end;
+    Ok(Some(end))
+}
+
+fn start_of_day(day: NaiveDate) -> DateTime<Utc> {
+    DateTime::from_utc(day.and_hms_opt(0, 0, 0).unwrap(), Utc)
+}
+
+#[tracing::instrument(skip_all)]
+async fn is_already_delivered(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+) -> Result<bool, anyhow::Error> {
+    let r = sqlx::query!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM newsletter_deliveries
+            WHERE
+                newsletter_issue_id = $1 AND
+                subscriber_email = $2 AND
+                status = 'delivered'
+        ) AS "already_delivered!"
+        "#,
+        issue_id,
+        email
+    )
+    .fetch_one(transaction)
+    .await?;
+
+    Ok(r.already_delivered)
+}
+
+/// # Frequency Cap
+/// To avoid fatigue, subscribers get at most one issue every `frequency_cap_seconds`: any other
+/// issue of the same tenant they are due within that period is skipped, not deferred.
+#[tracing::instrument(skip_all)]
+async fn has_reached_frequency_cap(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    settings: &NewsletterSettings,
+) -> Result<bool, anyhow::Error> {
+    if settings.frequency_cap_seconds == 0 {
+        return Ok(false);
+    }
+    let since = Utc::now() - chrono::Duration::seconds(settings.frequency_cap_seconds);
+    let r = sqlx::query!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM newsletter_deliveries d
+            JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+            WHERE
+                d.newsletter_issue_id <> $1 AND
+                i.tenant_id = (
+                    SELECT tenant_id FROM newsletter_issues WHERE newsletter_issue_id = $1
+                ) AND
+                d.subscriber_email = $2 AND
+                d.status = 'delivered' AND
+                d.delivered_at > $3
+        ) AS "capped!"
+        "#,
+        issue_id,
+        email,
+        since
+    )
+    .fetch_one(transaction)
+    .await?;
+
+    Ok(r.capped)
+}
+
+#[tracing::instrument(skip_all)]
+async fn record_skipped_delivery(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_deliveries (
+            newsletter_issue_id,
+            subscriber_email,
+            status,
+            delivered_at
+        )
+        VALUES ($1, $2, 'skipped_frequency_cap', now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO NOTHING
+        "#,
+        issue_id,
+        email
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    message_id: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_deliveries (
+            newsletter_issue_id,
+            subscriber_email,
+            status,
+            delivered_at,
+            message_id
+        )
+        VALUES ($1, $2, 'delivered', now(), $3)
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET status = 'delivered', delivered_at = now(), message_id = $3
+        "#,
+        issue_id,
+        email,
+        message_id
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
 struct NewsletterIssue {
+    tenant_id: String,
     title: String,
     text_content: String,
     html_content: String,
 }
 
+impl NewsletterIssue {
+    /// Returns the HTML and plain text content, rendered for a single subscriber and followed by our
+    /// footer.
+    fn personalize(
+        &self,
+        name: &str,
+        unsubscribe_link: &str,
+        manage_data_link: &str,
+        settings: &NewsletterSettings,
+        templates: &Tera,
+    ) -> Result<(String, String), tera::Error> {
+        let html_content = sanitize_issue_html(
+            &render_issue_content(&self.html_content, name, unsubscribe_link, true)?,
+            settings,
+        );
+        let text_content = render_issue_content(&self.text_content, name, unsubscribe_link, false)?;
+        append_footer(
+            html_content,
+            text_content,
+            unsubscribe_link,
+            manage_data_link,
+            &settings.company_address,
+            templates,
+        )
+    }
+}
+
+/// # Sanitization
+/// The HTML content of issues is stripped of any tag or attribute the install does not allow -
+/// `allowed_html_tags` and `allowed_html_attributes`. What is allowed varies: some installs want
+/// images in their issues, others do not.
+///
+/// Issue content is a template: it is sanitized when it is published, for the stored issue to show
+/// what will be sent, and once more after it has been rendered for each subscriber - that is what
+/// actually goes out, whatever the template produces and whatever was allowed when the issue was
+/// published. Our footer is appended afterwards, and is not sanitized.
+pub fn sanitize_issue_html(html_content: &str, settings: &NewsletterSettings) -> String {
+    let tags: HashSet<&str> = settings
+        .allowed_html_tags
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let tag_attributes: HashMap<&str, HashSet<&str>> = settings
+        .allowed_html_attributes
+        .iter()
+        .map(|(tag, attributes)| {
+            (
+                tag.as_str(),
+                attributes.iter().map(String::as_str).collect(),
+            )
+        })
+        .collect();
+    ammonia::Builder::default()
+        // Tags whose content would be dropped altogether, e.g. `<style>`, can be allowed too.
+        .rm_clean_content_tags(&tags)
+        .tags(tags)
+        .tag_attributes(tag_attributes)
+        // `rel` is meaningless in an email, and would conflict with allowing it on links.
+        .link_rel(None)
+        .clean(html_content)
+        .to_string()
+}
+
+/// # Footer
+/// Every email we send to subscribers beyond their confirmation - newsletter issues and welcome
+/// series steps alike - ends with our footer: our address, and the links to unsubscribe and to
+/// manage their data. Fails if the plain text content does not carry the unsubscribe link on a line
+/// of its own, e.g. because a customised footer dropped it.
+pub fn append_footer(
+    html_content: String,
+    text_content: String,
+    unsubscribe_link: &str,
+    manage_data_link: &str,
+    company_address: &str,
+    templates: &Tera,
+) -> Result<(String, String), tera::Error> {
+    let mut context = Context::new();
+    context.insert("unsubscribe_link", unsubscribe_link);
+    context.insert("manage_data_link", manage_data_link);
+    context.insert("company_address", company_address);
+    let html_content = html_content + &templates.render("newsletter_footer.html", &context)?;
+    let text_content = text_content + &templates.render("newsletter_footer.txt", &context)?;
+    if !unsubscribe_link.is_empty() && !has_link_on_its_own_line(&text_content, unsubscribe_link) {
+        return Err(tera::Error::msg(
+            "The plain text content does not carry the unsubscribe link on a line of its own.",
+        ));
+    }
+    Ok((html_content, text_content))
+}
+
+/// Plain text email clients only make links clickable if they can tell where they start and end:
+/// a link on a line of its own is always detected, whatever punctuation surrounds it elsewhere.
+fn has_link_on_its_own_line(text: &str, link: &str) -> bool {
+    text.lines().any(|line| line.trim() == link)
+}
+
+/// # Personalization
+/// Issue content is a Tera template, rendered for each subscriber with `name` and
+/// `unsubscribe_link` in its context - e.g. `Hi {{ name }}!`.
+///
+/// Subscriber names are user input: they are escaped when rendering HTML content. Every issue is
+/// rendered in isolation, so it cannot `include` or `extend` any of our own templates.
+pub fn render_issue_content(
+    content: &str,
+    name: &str,
+    unsubscribe_link: &str,
+    autoescape: bool,
+) -> Result<String, tera::Error> {
+    let mut context = Context::new();
+    context.insert("name", name);
+    context.insert("unsubscribe_link", unsubscribe_link);
+    Tera::one_off(content, &context, autoescape)
+}
+
+/// What we know of a subscriber - nothing, if they have been deleted after the task was enqueued.
+/// The same address can subscribe to more than one tenant: only the issue's tenant is looked up.
+#[derive(Default)]
+struct SubscriberProfile {
+    id: Option<Uuid>,
+    name: String,
+    locale: Option<String>,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_subscriber_profile(
+    transaction: &mut PgTransaction,
+    tenant_id: &str,
+    email: &str,
+) -> Result<SubscriberProfile, anyhow::Error> {
+    let r = sqlx::query!(
+        r#"
+        SELECT id, name, locale
+        FROM subscriptions
+        WHERE tenant_id = $1 AND email = $2
+        "#,
+        tenant_id,
+        email
+    )
+    .fetch_optional(transaction)
+    .await?;
+
+    Ok(r.map(|r| SubscriberProfile {
+        id: Some(r.id),
+        name: r.name,
+        locale: r.locale,
+    })
+    .unwrap_or_default())
+}
+
 #[tracing::instrument(skip_all)]
 async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
     let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"
-        SELECT title, text_content, html_content
+        SELECT tenant_id, title, text_content, html_content
         FROM newsletter_issues
         WHERE
             newsletter_issue_id = $1
@@ -139,10 +713,46 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     Ok(issue)
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    worker_pause: WorkerPause,
+    templates: Tera,
+    configuration: Settings,
+) -> Result<(), anyhow::Error> {
+    let retry_budget = RetryBudget::new(configuration.newsletter.retry_budget_per_minute);
+    let claim_timeout = configuration.newsletter.claim_timeout();
     loop {
-        match try_execute_task(&pool, &email_client).await {
+        // We keep sending if we cannot tell: a Redis outage should not take deliveries down too.
+        match worker_pause.is_paused().await {
+            Ok(true) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                    "Failed to check whether the delivery worker is paused. Carrying on.");
+            }
+        }
+        // Welcome series steps are due at a set time: they do not queue behind newsletter issues.
+        if let Ok(ExecutionOutcome::TaskCompleted) =
+            try_execute_welcome_step(&pool, &email_client, &templates, &configuration).await
+        {
+            continue;
+        }
+        match try_execute_task(
+            &pool,
+            &email_client,
+            &retry_budget,
+            &configuration,
+            &templates,
+        )
+        .await
+        {
             Ok(ExecutionOutcome::EmptyQueue) => {
+                // Errors are already logged - we will try again next time the queue is empty.
+                let _ = requeue_stale_claims(&pool, claim_timeout).await;
                 tokio::time::sleep(Duration::from_secs(10)).await;
             }
             Err(_) => {
@@ -155,7 +765,40 @@ async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyh
 
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
-    let email_client = configuration.email_client.client();
+    if let Some(quiet_hours) = &configuration.quiet_hours {
+        // Fail fast, rather than on every task - e.g. if Postgres does not know the time zone.
+        let mut connection = connection_pool.acquire().await?;
+        quiet_hours_end(
+            &mut connection,
+            quiet_hours,
+            &quiet_hours.time_zone,
+            Utc::now(),
+        )
+        .await
+        .context("The quiet hours are invalid.")?;
+    }
+    let email_client = configuration.email_client.clone().client();
+    let worker_pause = WorkerPause::new(
+        &configuration.redis_uri,
+        configuration.newsletter.worker_pause_key.clone(),
+    )?;
 
-    worker_loop(connection_pool, email_client).await
+    let templates = load_templates(&configuration.application.templates_dir)?;
+    validate_welcome_series(&templates, &configuration.subscriptions.welcome_series)?;
+
+    // Pausing deliveries leaves confirmation emails going out.
+    let confirmations = confirmation_retry_loop(
+        connection_pool.clone(),
+        configuration.email_client.clone().client(),
+        configuration.subscriptions.clone(),
+    );
+    let deliveries = worker_loop(
+        connection_pool,
+        email_client,
+        worker_pause,
+        templates,
+        configuration,
+    );
+    tokio::try_join!(deliveries, confirmations)?;
+    Ok(())
 }
@@ -1,13 +1,36 @@
+pub mod audit_log;
 pub mod authentication;
+pub mod cache_control;
+pub mod canonical_url;
+pub mod client_ip;
+pub mod compression;
 pub mod configuration;
+pub mod confirmation_retries;
+pub mod conversion_funnel;
+pub mod daily_stats;
 pub mod domain;
+pub mod duplicate_submissions;
 pub mod email_client;
+pub mod feature_flags;
 mod idempotency;
+pub mod ip_allowlist;
 pub mod issue_delivery_worker;
+pub mod load_shedding;
+pub mod retry_budget;
 pub mod routes;
+pub mod security_headers;
 pub mod session_state;
+pub mod signed_token;
 pub mod startup;
+pub mod subscribe_rate_limit;
+pub mod subscriber_repository;
+mod subscription_events;
 pub mod telemetry;
+pub mod tenant;
+#[cfg(feature = "test-support")]
+pub mod test_support;
 mod utils;
+pub mod welcome_series;
+pub mod worker_pause;
 
 extern crate tera;
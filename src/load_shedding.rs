@@ -0,0 +1,53 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{web, HttpResponse};
+use actix_web_lab::middleware::Next;
+use tokio::sync::Semaphore;
+
+/// How long clients should wait before retrying when we are overloaded, in seconds.
+const OVERLOAD_RETRY_AFTER: u32 = 1;
+
+/// Caps the number of requests being processed at the same time, across all workers.
+pub struct InFlightRequestLimit(Semaphore);
+
+impl InFlightRequestLimit {
+    pub fn new(max_in_flight_requests: usize) -> Self {
+        Self(Semaphore::new(max_in_flight_requests))
+    }
+}
+
+/// # Load Shedding
+/// Under overload, queueing requests only makes every one of them slower until they all time out.
+/// Requests above the configured limit are rejected straight away with a 503 and a `Retry-After`
+/// header instead, while those already accepted carry on unaffected.
+///
+/// Health checks are never rejected: an overloaded instance is not a dead one.
+pub async fn shed_load(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let limit = match req.app_data::<web::Data<InFlightRequestLimit>>() {
+        Some(limit) if req.path() != "/health_check" => limit.clone(),
+        _ => {
+            return next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        }
+    };
+    // The permit is held until the response is returned.
+    let _permit = match limit.0.try_acquire() {
+        Ok(permit) => permit,
+        Err(_) => {
+            tracing::warn!("Too many requests in flight. Shedding load.");
+            let response = HttpResponse::ServiceUnavailable()
+                .insert_header((RETRY_AFTER, OVERLOAD_RETRY_AFTER))
+                .finish();
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    };
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
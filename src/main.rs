@@ -1,5 +1,6 @@
 use std::fmt::{Debug, Display};
 use tokio::task::JoinError;
+use zero2prod::daily_stats::run_stats_aggregator_until_stopped;
 use zero2prod::issue_delivery_worker::run_worker_until_stopped;
 use zero2prod::{configuration, startup::Application, telemetry};
 
@@ -32,20 +33,48 @@ async fn main() -> anyhow::Result<()> {
         configuration.application.host, configuration.application.port
     );
 
-    let subscriber = telemetry::get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
-    telemetry::init_subscriber(subscriber);
+    let log_sample_ratio = configuration.application.log_sample_ratio;
+    let telemetry_guard = match configuration.application.log_buffered_lines {
+        0 => {
+            let subscriber = telemetry::get_subscriber(
+                "zero2prod".into(),
+                "info".into(),
+                log_sample_ratio,
+                std::io::stdout,
+            );
+            telemetry::init_subscriber(subscriber);
+            None
+        }
+        buffered_lines => {
+            let (sink, guard) = telemetry::non_blocking_sink(std::io::stdout(), buffered_lines);
+            let subscriber = telemetry::get_subscriber(
+                "zero2prod".into(),
+                "info".into(),
+                log_sample_ratio,
+                sink,
+            );
+            telemetry::init_subscriber(subscriber);
+            Some(guard)
+        }
+    };
 
     let application = Application::build(configuration.clone()).await?;
     let port = application.port();
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
+    let stats_task = tokio::spawn(run_stats_aggregator_until_stopped(configuration));
 
     tokio::select! {
         o = application_task => report_exit("API", o),
         o = worker_task => report_exit("Background worker", o),
+        o = stats_task => report_exit("Stats aggregator", o),
     };
 
     println!("Running the server on: {address}:{port}");
+    // Whatever was logged while shutting down must not be lost.
+    if let Some(guard) = telemetry_guard {
+        telemetry::shutdown(guard);
+    }
 
     Ok(())
 }
@@ -0,0 +1,89 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// # Retry Budget
+/// Transient delivery failures are retried, as long as the budget allows it. During a provider
+/// outage every task fails: retrying all of them would only pile more load on the provider - a
+/// retry storm - and the tasks failing once the budget is exhausted are deferred for longer
+/// instead.
+///
+/// The budget is a token bucket, shared by all the tasks of a worker: it holds up to
+/// `retries_per_minute` tokens, refilled continuously at that rate, and every retry takes one.
+pub struct RetryBudget {
+    retries_per_minute: u32,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RetryBudget {
+    /// The bucket starts full.
+    pub fn new(retries_per_minute: u32) -> Self {
+        Self {
+            retries_per_minute,
+            bucket: Mutex::new(Bucket {
+                tokens: retries_per_minute as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token from the budget, if there is any left.
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+        let capacity = self.retries_per_minute as f64;
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        let refill = elapsed.as_secs_f64() / Duration::from_secs(60).as_secs_f64() * capacity;
+        bucket.tokens = (bucket.tokens + refill).min(capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryBudget;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn retries_are_rejected_once_the_budget_is_exhausted() {
+        let budget = RetryBudget::new(2);
+        let now = Instant::now();
+
+        assert!(budget.try_acquire_at(now));
+        assert!(budget.try_acquire_at(now));
+        assert!(!budget.try_acquire_at(now));
+    }
+
+    #[test]
+    fn the_budget_is_refilled_over_time_up_to_its_capacity() {
+        let budget = RetryBudget::new(2);
+        let now = Instant::now();
+        assert!(budget.try_acquire_at(now));
+        assert!(budget.try_acquire_at(now));
+
+        // Half a minute is worth one retry.
+        let later = now + Duration::from_secs(30);
+        assert!(budget.try_acquire_at(later));
+        assert!(!budget.try_acquire_at(later));
+
+        // The unused budget does not pile up beyond the capacity.
+        let much_later = later + Duration::from_secs(600);
+        assert!(budget.try_acquire_at(much_later));
+        assert!(budget.try_acquire_at(much_later));
+        assert!(!budget.try_acquire_at(much_later));
+    }
+}
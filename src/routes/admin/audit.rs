@@ -0,0 +1,72 @@
+use crate::audit_log::get_audit_entries;
+use crate::utils::e400;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
+
+/// The number of entries fetched from the database at a time.
+const EXPORT_BATCH_SIZE: i64 = 500;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ExportParameters {
+    // `YYYY-MM-DD`, in UTC. Both ends are included.
+    from: Option<String>,
+    to: Option<String>,
+}
+
+/// # Audit Log Export
+/// The audit log as newline-delimited JSON, one entry per line, oldest first. Security teams ship it
+/// to their own tooling, authenticating with one of the configured audit API keys if they are not
+/// logged in.
+///
+/// The log is streamed `EXPORT_BATCH_SIZE` entries at a time: we never hold all of it in memory.
+#[tracing::instrument(name = "Export the audit log", skip(pool))]
+pub async fn export_audit_log(
+    parameters: web::Query<ExportParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let from = parse_date(parameters.from.as_deref()).map_err(e400)?;
+    let to = parse_date(parameters.to.as_deref())
+        .map_err(e400)?
+        .map(|to| to + chrono::Duration::days(1));
+
+    let pool = pool.into_inner();
+    // The state is the id of the last exported entry, until there is nothing left to export.
+    let lines = futures_util::stream::unfold(Some(0), move |after_event_id| {
+        let pool = pool.clone();
+        async move {
+            let after_event_id = after_event_id?;
+            let entries =
+                match get_audit_entries(&pool, after_event_id, from, to, EXPORT_BATCH_SIZE).await {
+                    Ok(entries) => entries,
+                    Err(e) => return Some((Err(anyhow::Error::from(e)), None)),
+                };
+            let last_event_id = entries.last()?.event_id;
+            let mut body = Vec::new();
+            for entry in &entries {
+                if let Err(e) = serde_json::to_writer(&mut body, entry) {
+                    return Some((Err(e.into()), None));
+                }
+                body.push(b'\n');
+            }
+            let next = (entries.len() as i64 == EXPORT_BATCH_SIZE).then_some(last_event_id);
+            Some((Ok(Bytes::from(body)), next))
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(lines))
+}
+
+fn parse_date(date: Option<&str>) -> Result<Option<DateTime<Utc>>, String> {
+    let date = match date {
+        Some(date) => date,
+        None => return Ok(None),
+    };
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("{date} is not a date, formatted as YYYY-MM-DD."))?;
+    let midnight = date.and_hms_opt(0, 0, 0).expect("Midnight is a valid time");
+    Ok(Some(DateTime::from_utc(midnight, Utc)))
+}
@@ -1,16 +1,22 @@
 use crate::authentication::UserId;
+use crate::daily_stats::get_recent_daily_stats;
 use crate::utils::e500;
+use crate::worker_pause::WorkerPause;
 use actix_web::http::header::{ContentType, LOCATION};
 use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
 use anyhow::Context;
 use sqlx::PgPool;
+use std::fmt::Write;
 use tera::{Context as tcontext, Tera};
 use uuid::Uuid;
 
 pub async fn admin_dashboard(
     user_id: web::ReqData<UserId>,
     pool: web::Data<PgPool>,
-    templates: web::Data<&Tera>,
+    templates: web::Data<Tera>,
+    worker_pause: web::Data<WorkerPause>,
+    flash_messages: IncomingFlashMessages,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
     let username = match get_username(*user_id, &pool).await.map_err(e500) {
@@ -22,8 +28,32 @@ pub async fn admin_dashboard(
         }
     };
 
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    }
+
+    // The dashboard is still useful if Redis is not reachable.
+    let worker_state = match worker_pause.is_paused().await {
+        Ok(true) => "paused",
+        Ok(false) => "running",
+        Err(e) => {
+            tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                "Failed to check whether the delivery worker is paused.");
+            "unknown"
+        }
+    };
+
+    let daily_stats = get_recent_daily_stats(&pool, 7)
+        .await
+        .context("Failed to retrieve the daily stats")
+        .map_err(e500)?;
+
     let mut template_context = tcontext::new();
     template_context.insert("username", &username);
+    template_context.insert("msg_html", &msg_html);
+    template_context.insert("worker_state", worker_state);
+    template_context.insert("daily_stats", &daily_stats);
     let html_body = templates
         .render("admin_dashboard.html", &template_context)
         .context("Error rendering admin_dashboard html")
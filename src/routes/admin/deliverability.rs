@@ -0,0 +1,51 @@
+use crate::configuration::DeliverabilitySettings;
+use crate::email_client::EmailClient;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context as anyhow_ctx;
+use tera::{Context, Tera};
+
+#[derive(serde::Serialize)]
+struct DnsRecord {
+    name: String,
+    record_type: &'static str,
+    // `None` if only the email provider knows it, e.g. the public key of a DKIM record.
+    value: Option<String>,
+}
+
+/// Lists the DNS records the sending domain needs for our emails to be delivered, to help admins
+/// check their setup. They are derived from the configuration: we do not look the actual records up.
+#[tracing::instrument(name = "Report the expected deliverability setup", skip_all)]
+pub async fn deliverability_report(
+    email_client: web::Data<EmailClient>,
+    settings: web::Data<DeliverabilitySettings>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let sending_domain = email_client.sender().domain().to_owned();
+    let mut records = vec![DnsRecord {
+        name: sending_domain.clone(),
+        record_type: "TXT",
+        value: Some(format!("v=spf1 a mx include:{} ~all", settings.spf_include)),
+    }];
+    if let Some(selector) = &settings.dkim_selector {
+        records.push(DnsRecord {
+            name: format!("{selector}._domainkey.{sending_domain}"),
+            record_type: "TXT",
+            value: None,
+        });
+    }
+
+    let mut context = Context::new();
+    context.insert("sending_domain", &sending_domain);
+    context.insert("records", &records);
+    context.insert("dkim_configured", &settings.dkim_selector.is_some());
+    let html_body = templates
+        .render("deliverability.html", &context)
+        .context("Error rendering deliverability html")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
@@ -0,0 +1,52 @@
+use crate::authentication::UserId;
+use crate::idempotency::get_recent_keys;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context as anyhow_ctx;
+use sqlx::PgPool;
+use tera::{Context, Tera};
+
+/// We only list the most recent keys - enough to debug a replay that just happened.
+const MAX_KEYS: i64 = 50;
+
+#[derive(serde::Serialize)]
+struct IdempotencyKeyRow {
+    idempotency_key: String,
+    created_at: String,
+    status_code: Option<i16>,
+    replay_count: i32,
+}
+
+/// Lists the idempotency keys recently used by the current user, to help debugging replays.
+#[tracing::instrument(name = "List idempotency keys", skip_all, fields(user_id=%*user_id))]
+pub async fn list_idempotency_keys(
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let keys: Vec<_> = get_recent_keys(&pool, *user_id, MAX_KEYS)
+        .await
+        .context("Failed to retrieve the recent idempotency keys.")
+        .map_err(e500)?
+        .into_iter()
+        .map(|r| IdempotencyKeyRow {
+            idempotency_key: r.idempotency_key,
+            created_at: r.created_at.to_rfc3339(),
+            status_code: r.response_status_code,
+            replay_count: r.replay_count,
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context.insert("keys", &keys);
+    let html_body = templates
+        .render("idempotency_keys.html", &context)
+        .context("Error rendering idempotency keys html")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
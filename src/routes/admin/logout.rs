@@ -1,5 +1,6 @@
 use crate::authentication::UserId;
 use crate::session_state::TypedSession;
+use crate::startup::LogoutRedirect;
 use crate::utils::see_other;
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
@@ -8,9 +9,10 @@ use actix_web_flash_messages::FlashMessage;
 pub async fn log_out(
     userid: web::ReqData<UserId>,
     session: TypedSession,
+    redirect: web::Data<LogoutRedirect>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let _user_id = userid.into_inner();
     session.log_out();
     FlashMessage::info("You have successfully logged out.").send();
-    Ok(see_other("/login"))
+    Ok(see_other(&redirect.0))
 }
@@ -0,0 +1,68 @@
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context as anyhow_ctx;
+use sqlx::PgPool;
+use tera::{Context, Tera};
+
+#[derive(serde::Serialize)]
+struct MigrationRecord {
+    version: i64,
+    description: String,
+    // Hex-encoded
+    checksum: String,
+    installed_on: String,
+    // A migration is dirty if it failed half-way through: the schema might be in an unknown state.
+    dirty: bool,
+}
+
+/// Lists the migrations `sqlx` has applied to the database, oldest first.
+#[tracing::instrument(name = "List applied migrations", skip(pool, templates))]
+pub async fn list_migrations(
+    pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let migrations = get_applied_migrations(&pool)
+        .await
+        .context("Failed to retrieve the applied migrations.")
+        .map_err(e500)?;
+
+    let mut context = Context::new();
+    context.insert("migrations", &migrations);
+    context.insert(
+        "dirty_count",
+        &migrations.iter().filter(|m| m.dirty).count(),
+    );
+    let html_body = templates
+        .render("migrations.html", &context)
+        .context("Error rendering migrations html")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_applied_migrations(pool: &PgPool) -> Result<Vec<MigrationRecord>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT version, description, checksum, installed_on, success
+        FROM _sqlx_migrations
+        ORDER BY version
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| MigrationRecord {
+            version: r.version,
+            description: r.description,
+            checksum: r.checksum.iter().map(|b| format!("{b:02x}")).collect(),
+            installed_on: r.installed_on.to_rfc3339(),
+            dirty: !r.success,
+        })
+        .collect())
+}
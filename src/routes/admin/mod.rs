@@ -1,9 +1,25 @@
+mod audit;
 mod dashboard;
+mod deliverability;
+mod idempotency;
 mod logout;
+mod migrations;
 mod newsletter;
 mod password;
+mod queue;
+mod stats;
+mod subscribers;
+mod worker;
 
+pub use audit::export_audit_log;
 pub use dashboard::admin_dashboard;
+pub use deliverability::deliverability_report;
+pub use idempotency::list_idempotency_keys;
 pub use logout::*;
+pub use migrations::list_migrations;
 pub use newsletter::*;
 pub use password::*;
+pub use queue::queue_backlog;
+pub use stats::subscriber_stats;
+pub use subscribers::*;
+pub use worker::{pause_worker, resume_worker};
@@ -0,0 +1,48 @@
+use super::get::{render_newsletter_form, NewsletterDraft};
+use crate::tenant::Tenant;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use tera::Tera;
+use uuid::Uuid;
+
+/// Shows the newsletter form pre-filled with a past issue, for the admin to edit it and publish it
+/// as a new issue. Nothing is published until the form is submitted. Only the tenant's own issues
+/// can be cloned.
+#[tracing::instrument(name = "Clone a newsletter issue", skip(tenant, pool, templates))]
+pub async fn clone_newsletter_issue(
+    tenant: Tenant,
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT title, text_content, html_content
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND tenant_id = $2
+        "#,
+        issue_id.into_inner(),
+        tenant.id()
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the newsletter issue.")
+    .map_err(e500)?
+    .ok_or_else(|| actix_web::error::ErrorNotFound("There is no such newsletter issue."))?;
+
+    let draft = NewsletterDraft {
+        title: &issue.title,
+        text_content: &issue.text_content,
+        html_content: &issue.html_content,
+        scheduled_for: "",
+    };
+    Ok(render_newsletter_form(
+        HttpResponse::Ok(),
+        &templates,
+        "",
+        None,
+        &draft,
+    ))
+}
@@ -1,26 +1,53 @@
 use actix_web::http::header::ContentType;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpResponse, HttpResponseBuilder};
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 use tera::{Context, Tera};
 
+/// The values the form is pre-filled with.
+#[derive(Default, serde::Serialize)]
+pub(super) struct NewsletterDraft<'a> {
+    pub(super) title: &'a str,
+    pub(super) text_content: &'a str,
+    pub(super) html_content: &'a str,
+    pub(super) scheduled_for: &'a str,
+}
+
 pub async fn publish_newsletter_form(
     flash_messages: IncomingFlashMessages,
-    templates: web::Data<&Tera>,
+    templates: web::Data<Tera>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
     }
 
+    Ok(render_newsletter_form(
+        HttpResponse::Ok(),
+        &templates,
+        &msg_html,
+        None,
+        &NewsletterDraft::default(),
+    ))
+}
+
+/// Every rendering of the form carries a fresh idempotency key: a submission rejected before being
+/// processed does not use up its key, but there is no reason to reuse it either.
+pub(super) fn render_newsletter_form(
+    mut response: HttpResponseBuilder,
+    templates: &Tera,
+    msg_html: &str,
+    error: Option<&str>,
+    draft: &NewsletterDraft<'_>,
+) -> HttpResponse {
     let idempotency_key = uuid::Uuid::new_v4();
 
     let mut context = Context::new();
     context.insert("msg_html", &msg_html);
+    context.insert("error", &error);
+    context.insert("draft", draft);
     context.insert("idempotency_key", &idempotency_key);
 
     let html_body = templates.render("newsletter_form.html", &context).unwrap();
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(html_body))
+    response.content_type(ContentType::html()).body(html_body)
 }
@@ -0,0 +1,127 @@
+use crate::tenant::Tenant;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Serialize)]
+struct NewsletterIssueSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: String,
+    // `completed` once no delivery task is left in the queue, `in_progress` otherwise.
+    status: &'static str,
+    delivered: i64,
+    pending: i64,
+}
+
+#[derive(serde::Serialize)]
+struct NewsletterIssues {
+    issues: Vec<NewsletterIssueSummary>,
+}
+
+#[derive(serde::Serialize)]
+struct ScheduledIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    scheduled_for: String,
+}
+
+#[derive(serde::Serialize)]
+struct ScheduledIssues {
+    issues: Vec<ScheduledIssue>,
+}
+
+/// Lists the newsletter issues published to the tenant, most recent first, with how far their
+/// delivery got - as JSON, for the admin front-end.
+#[tracing::instrument(name = "List newsletter issues", skip_all)]
+pub async fn list_newsletter_issues(
+    tenant: Tenant,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issues = get_newsletter_issues(&pool, &tenant)
+        .await
+        .context("Failed to retrieve the newsletter issues.")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(NewsletterIssues { issues }))
+}
+
+/// Lists the tenant's issues scheduled for later, soonest first - as JSON, for the admin calendar.
+#[tracing::instrument(name = "List scheduled newsletter issues", skip_all)]
+pub async fn list_scheduled_newsletter_issues(
+    tenant: Tenant,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issues = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, scheduled_for AS "scheduled_for!"
+        FROM newsletter_issues
+        WHERE tenant_id = $1 AND scheduled_for > now()
+        ORDER BY scheduled_for
+        "#,
+        tenant.id()
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to retrieve the scheduled newsletter issues.")
+    .map_err(e500)?
+    .into_iter()
+    .map(|r| ScheduledIssue {
+        newsletter_issue_id: r.newsletter_issue_id,
+        title: r.title,
+        scheduled_for: r.scheduled_for.to_rfc3339(),
+    })
+    .collect();
+
+    Ok(HttpResponse::Ok().json(ScheduledIssues { issues }))
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_newsletter_issues(
+    pool: &PgPool,
+    tenant: &Tenant,
+) -> Result<Vec<NewsletterIssueSummary>, sqlx::Error> {
+    // `published_at` is stored as text.
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            i.newsletter_issue_id,
+            i.title,
+            i.published_at::timestamptz AS "published_at!",
+            (
+                SELECT COUNT(*)
+                FROM newsletter_deliveries d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id AND d.status = 'delivered'
+            ) AS "delivered!",
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "pending!"
+        FROM newsletter_issues i
+        WHERE i.tenant_id = $1
+        ORDER BY i.published_at::timestamptz DESC
+        "#,
+        tenant.id()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| NewsletterIssueSummary {
+            newsletter_issue_id: r.newsletter_issue_id,
+            title: r.title,
+            published_at: r.published_at.to_rfc3339(),
+            status: if r.pending == 0 {
+                "completed"
+            } else {
+                "in_progress"
+            },
+            delivered: r.delivered,
+            pending: r.pending,
+        })
+        .collect())
+}
@@ -1,5 +1,9 @@
+mod clone;
 mod get;
+mod history;
 mod post;
 
+pub use clone::clone_newsletter_issue;
 pub use get::publish_newsletter_form;
+pub use history::{list_newsletter_issues, list_scheduled_newsletter_issues};
 pub use post::publish_newsletter;
@@ -1,10 +1,18 @@
+use super::get::{render_newsletter_form, NewsletterDraft};
+use crate::audit_log::{record_audit_event, AuditAction};
 use crate::authentication::UserId;
+use crate::configuration::{IdempotencySettings, NewsletterSettings};
 use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
+use crate::issue_delivery_worker::{render_issue_content, sanitize_issue_html};
+use crate::tenant::Tenant;
 use crate::utils::{e400, e500, see_other};
-use actix_web::{web, web::ReqData, HttpResponse};
+use actix_web::{web, web::ReqData, FromRequest, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
 use sqlx::{PgPool, Postgres, Transaction};
+use tera::Tera;
 use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
@@ -12,72 +20,353 @@ pub struct FormData {
     title: String,
     text_content: String,
     html_content: String,
-    idempotency_key: String,
+    // API clients can send the `Idempotency-Key` header instead.
+    #[serde(default)]
+    idempotency_key: Option<String>,
+    // Set to publish an issue identical to a recent one.
+    #[serde(default)]
+    force: bool,
+    // RFC 3339, e.g. `2026-10-16T09:00:00Z`, to deliver the issue later. Empty to deliver it now.
+    #[serde(default)]
+    scheduled_for: String,
 }
 
 /// # Idempotency
 /// An API endpoint is retry-safe(or **idempotent**) if the caller has no way to **observe** if a
 /// request has been sent to the server once or multiple times.
+///
+/// The issue is published to the tenant serving the request, and only delivered to its subscribers.
 #[tracing::instrument(
     name = "Publish a newsletter issue",
     skip_all,
     fields(user_id=%*user_id)
 )]
 pub async fn publish_newsletter(
+    request: HttpRequest,
     form: web::Form<FormData>,
     user_id: ReqData<UserId>,
     pool: web::Data<PgPool>,
+    settings: web::Data<NewsletterSettings>,
+    idempotency_settings: web::Data<IdempotencySettings>,
+    templates: web::Data<Tera>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
+    let tenant = Tenant::extract(&request).await?;
     // We must destructure the form to avoid upsetting the borrow-checker
     let FormData {
         title,
         text_content,
         html_content,
         idempotency_key,
+        force,
+        scheduled_for,
     } = form.0;
-    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
+    let idempotency_key: IdempotencyKey = match idempotency_key {
+        Some(idempotency_key) => idempotency_key.try_into().map_err(e400)?,
+        None => idempotency_key_header(&request)?
+            .ok_or_else(|| e400("The idempotency key is missing."))?,
+    };
 
-    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
-        .await
-        .map_err(e500)?
+    let sanitized_html_content = sanitize_issue_html(&html_content, &settings);
+    // The form is shown again, filled in with what was submitted, for the admin to fix it - the
+    // idempotency key has not been used yet.
+    let scheduled_for = match validate_issue(&title, &text_content, &sanitized_html_content)
+        .and_then(|_| parse_scheduled_for(&scheduled_for))
     {
-        NextAction::StartProcessing(t) => t,
-        NextAction::ReturnSavedResponse(saved_response) => {
-            success_message().send();
-            return Ok(saved_response);
+        Ok(scheduled_for) => scheduled_for,
+        Err(e) => {
+            let draft = NewsletterDraft {
+                title: &title,
+                text_content: &text_content,
+                html_content: &html_content,
+                scheduled_for: &scheduled_for,
+            };
+            return Ok(render_newsletter_form(
+                HttpResponse::UnprocessableEntity(),
+                &templates,
+                "",
+                Some(&e),
+                &draft,
+            ));
         }
     };
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
-        .await
-        .context("Failed to store newsletter issue details")
-        .map_err(e500)?;
+    let mut transaction =
+        match try_processing(&pool, &idempotency_key, *user_id, &idempotency_settings)
+            .await
+            .map_err(e500)?
+        {
+            NextAction::StartProcessing(t) => t,
+            NextAction::ReturnSavedResponse(saved_response) => {
+                success_message().send();
+                return Ok(saved_response);
+            }
+        };
 
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    let html_content = sanitized_html_content;
+    let content_hash = content_hash(&title, &text_content, &html_content);
+    if !force {
+        let since =
+            chrono::Utc::now() - chrono::Duration::seconds(settings.duplicate_window_seconds);
+        if is_recent_duplicate(&mut transaction, &tenant, &content_hash, since)
+            .await
+            .context("Failed to look for recent duplicate issues")
+            .map_err(e500)?
+        {
+            // Dropping the transaction rolls back the idempotency key insertion - the form can be
+            // submitted again with `force` set.
+            FlashMessage::warning(
+                "An identical newsletter issue has been published recently. \
+                Tick \"Publish anyway\" to send it again.",
+            )
+            .send();
+            return Ok(see_other("/admin/newsletters"));
+        }
+        if let Some(next_publication) =
+            next_publication_allowed_at(&mut transaction, *user_id, settings.publish_cooldown())
+                .await
+                .context("Failed to look up the last publication of the user")
+                .map_err(e500)?
+        {
+            FlashMessage::warning(format!(
+                "You published a newsletter issue recently. You can publish again after {}, or \
+                tick \"Publish anyway\" to send it now.",
+                next_publication.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ))
+            .send();
+            return Ok(see_other("/admin/newsletters"));
+        }
+        if let Some(confirmed) = confirmed_subscribers_below(
+            &mut transaction,
+            &tenant,
+            settings.min_confirmed_to_publish,
+        )
         .await
-        .context("Failed to enqueue delivery tasks")
-        .map_err(e500)?;
+        .context("Failed to count the confirmed subscribers")
+        .map_err(e500)?
+        {
+            FlashMessage::warning(format!(
+                "The newsletter issue would only reach {confirmed} confirmed subscriber(s), \
+                fewer than the expected {}. Tick \"Publish anyway\" to send it regardless.",
+                settings.min_confirmed_to_publish.unwrap_or_default()
+            ))
+            .send();
+            return Ok(see_other("/admin/newsletters"));
+        }
+    }
+
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &tenant,
+        &title,
+        &text_content,
+        &html_content,
+        &content_hash,
+        scheduled_for,
+    )
+    .await
+    .context("Failed to store newsletter issue details")
+    .map_err(e500)?;
+
+    let new_subscriber_delay = chrono::Duration::seconds(settings.new_subscriber_delay_seconds);
+    enqueue_delivery_tasks(
+        &mut transaction,
+        &tenant,
+        issue_id,
+        new_subscriber_delay,
+        scheduled_for,
+    )
+    .await
+    .context("Failed to enqueue delivery tasks")
+    .map_err(e500)?;
+    record_audit_event(
+        &mut transaction,
+        *user_id,
+        AuditAction::NewsletterPublished,
+        Some(&issue_id.to_string()),
+    )
+    .await
+    .context("Failed to record the publication in the audit log")
+    .map_err(e500)?;
 
     let response = see_other("/admin/newsletters");
-    let response = save_response(transaction, &idempotency_key, *user_id, response)
-        .await
-        .map_err(e500)?;
+    let response = save_response(
+        transaction,
+        &idempotency_key,
+        *user_id,
+        response,
+        idempotency_settings.max_stored_body_bytes,
+    )
+    .await
+    .map_err(e500)?;
     success_message().send();
 
     Ok(response)
 }
 
+/// The `Idempotency-Key` header is only used if the form does not carry an idempotency key. It is
+/// held to the same rules: invalid keys are rejected with a `400`.
+fn idempotency_key_header(
+    request: &HttpRequest,
+) -> Result<Option<IdempotencyKey>, actix_web::Error> {
+    let header = match request.headers().get("Idempotency-Key") {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let idempotency_key = header.to_str().map_err(|_| {
+        e400("Invalid Idempotency-Key header: it can only contain visible ASCII characters.")
+    })?;
+    IdempotencyKey::try_from(idempotency_key.to_owned())
+        .map(Some)
+        .map_err(|e| e400(format!("Invalid Idempotency-Key header: {e}.")))
+}
+
+/// Returns a message for the admin if the issue cannot be published.
+fn validate_issue(title: &str, text_content: &str, html_content: &str) -> Result<(), String> {
+    if title.trim().is_empty() {
+        return Err("The newsletter issue title is missing.".into());
+    }
+    if text_content.trim().is_empty() || html_content.trim().is_empty() {
+        return Err("The newsletter issue needs both a plain text and an HTML content.".into());
+    }
+    // We'd rather find out now than when the worker renders the issue for each subscriber.
+    validate_issue_content(html_content, text_content)
+        .map_err(|e| format!("The newsletter issue content is not a valid template: {e}"))
+}
+
+/// `None` if the issue is to be delivered right away.
+fn parse_scheduled_for(scheduled_for: &str) -> Result<Option<DateTime<Utc>>, String> {
+    if scheduled_for.trim().is_empty() {
+        return Ok(None);
+    }
+    let scheduled_for = DateTime::parse_from_rfc3339(scheduled_for.trim())
+        .map_err(|_| {
+            format!("{scheduled_for} is not a date and time, formatted as 2026-10-16T09:00:00Z.")
+        })?
+        .with_timezone(&Utc);
+    if scheduled_for <= Utc::now() {
+        return Err("A newsletter issue can only be scheduled in the future.".into());
+    }
+    Ok(Some(scheduled_for))
+}
+
+fn validate_issue_content(html_content: &str, text_content: &str) -> Result<(), tera::Error> {
+    render_issue_content(html_content, "Subscriber", "https://example.com", true)?;
+    render_issue_content(text_content, "Subscriber", "https://example.com", false)?;
+    Ok(())
+}
+
 fn success_message() -> FlashMessage {
     FlashMessage::info("The newsletter issue has been accepted - emails will go out shortly.")
 }
 
+/// Hex-encoded SHA-256 of the issue content. Fields are NUL-separated to tell apart, say, a title
+/// ending with the first characters of the text content from a shorter title.
+fn content_hash(title: &str, text_content: &str, html_content: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(title);
+    hasher.update([0]);
+    hasher.update(text_content);
+    hasher.update([0]);
+    hasher.update(html_content);
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[tracing::instrument(skip_all)]
+async fn is_recent_duplicate(
+    transaction: &mut Transaction<'_, Postgres>,
+    tenant: &Tenant,
+    content_hash: &str,
+    since: chrono::DateTime<chrono::Utc>,
+) -> Result<bool, sqlx::Error> {
+    // `published_at` is stored as text.
+    let r = sqlx::query!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM newsletter_issues
+            WHERE
+                tenant_id = $1 AND
+                content_hash = $2 AND
+                published_at::timestamptz >= $3
+        ) AS "is_duplicate!"
+        "#,
+        tenant.id(),
+        content_hash,
+        since
+    )
+    .fetch_one(transaction)
+    .await?;
+
+    Ok(r.is_duplicate)
+}
+
+/// `None` if the user can publish right away: they have not published anything within `cooldown`.
+#[tracing::instrument(skip(transaction))]
+async fn next_publication_allowed_at(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    cooldown: Option<chrono::Duration>,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let cooldown = match cooldown {
+        Some(cooldown) => cooldown,
+        None => return Ok(None),
+    };
+    let r = sqlx::query!(
+        r#"
+        SELECT MAX(occurred_at) AS last_published_at
+        FROM audit_log
+        WHERE user_id = $1 AND action = $2
+        "#,
+        user_id,
+        AuditAction::NewsletterPublished.as_str()
+    )
+    .fetch_one(transaction)
+    .await?;
+
+    Ok(r.last_published_at
+        .map(|last_published_at| last_published_at + cooldown)
+        .filter(|next_publication| *next_publication > Utc::now()))
+}
+
+/// The number of confirmed subscribers if there are fewer than `minimum`, `None` otherwise.
+#[tracing::instrument(skip(transaction))]
+async fn confirmed_subscribers_below(
+    transaction: &mut Transaction<'_, Postgres>,
+    tenant: &Tenant,
+    minimum: Option<i64>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let minimum = match minimum {
+        Some(minimum) => minimum,
+        None => return Ok(None),
+    };
+    let r = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "confirmed!"
+        FROM subscriptions
+        WHERE tenant_id = $1 AND status = 'confirmed'
+        "#,
+        tenant.id()
+    )
+    .fetch_one(transaction)
+    .await?;
+
+    Ok((r.confirmed < minimum).then_some(r.confirmed))
+}
+
 #[tracing::instrument(skip_all)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
+    tenant: &Tenant,
     title: &str,
     text_content: &str,
     html_content: &str,
+    content_hash: &str,
+    scheduled_for: Option<DateTime<Utc>>,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
     sqlx::query!(
@@ -87,14 +376,20 @@ async fn insert_newsletter_issue(
             title,
             text_content,
             html_content,
-            published_at
+            published_at,
+            content_hash,
+            scheduled_for,
+            tenant_id
         )
-        VALUES ($1, $2, $3, $4, now())
+        VALUES ($1, $2, $3, $4, now(), $5, $6, $7)
         "#,
         newsletter_issue_id,
         title,
         text_content,
-        html_content
+        html_content,
+        content_hash,
+        scheduled_for,
+        tenant.id()
     )
     .execute(transaction)
     .await?;
@@ -102,22 +397,42 @@ async fn insert_newsletter_issue(
     Ok(newsletter_issue_id)
 }
 
+/// Subscribers who confirmed less than `new_subscriber_delay` ago get the issue once they have been
+/// confirmed for that long. Those who confirmed before we recorded subscription events are not new.
+/// Nobody gets a scheduled issue before `scheduled_for`.
 #[tracing::instrument(skip_all)]
 async fn enqueue_delivery_tasks(
     transaction: &mut Transaction<'_, Postgres>,
+    tenant: &Tenant,
     newsletter_issue_id: Uuid,
+    new_subscriber_delay: chrono::Duration,
+    scheduled_for: Option<DateTime<Utc>>,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
         INSERT INTO issue_delivery_queue (
             newsletter_issue_id,
-            subscriber_email
+            subscriber_email,
+            execute_after
         )
-        SELECT $1, email
-        FROM subscriptions
-        WHERE status = 'confirmed'
+        SELECT
+            $1,
+            s.email,
+            GREATEST(
+                COALESCE($3, now()),
+                (
+                    SELECT max(e.occurred_at)
+                    FROM subscription_events e
+                    WHERE e.subscriber_id = s.id AND e.event_type = 'confirmed'
+                ) + $2::float8 * interval '1 second'
+            )
+        FROM subscriptions s
+        WHERE s.tenant_id = $4 AND s.status = 'confirmed'
         "#,
         newsletter_issue_id,
+        new_subscriber_delay.num_seconds() as f64,
+        scheduled_for,
+        tenant.id(),
     )
     .execute(transaction)
     .await?;
@@ -6,7 +6,7 @@ use std::fmt::Write;
 use tera::{Context, Tera};
 
 pub async fn change_password_form(
-    templates: web::Data<&Tera>,
+    templates: web::Data<Tera>,
     user_id: web::ReqData<UserId>,
     flash_messages: IncomingFlashMessages,
 ) -> Result<HttpResponse, actix_web::Error> {
@@ -1,3 +1,4 @@
+use crate::audit_log::{record_audit_event, AuditAction};
 use crate::authentication::{validate_credentials, AuthError, Credentials, UserId};
 use crate::routes::admin::dashboard::get_username;
 use crate::utils::{e500, see_other};
@@ -49,6 +50,9 @@ pub async fn change_password(
     crate::authentication::change_password(*user_id, form.0.new_password, &pool)
         .await
         .map_err(e500)?;
+    record_audit_event(pool.get_ref(), *user_id, AuditAction::PasswordChanged, None)
+        .await
+        .map_err(e500)?;
 
     FlashMessage::error("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
@@ -0,0 +1,46 @@
+use crate::configuration::NewsletterSettings;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+#[derive(serde::Serialize)]
+struct QueueBacklog {
+    depth: i64,
+    // `None` if the queue is empty.
+    oldest_pending_age_seconds: Option<i64>,
+    status: &'static str,
+}
+
+/// Reports how many delivery tasks are waiting in `issue_delivery_queue`, as JSON. The status is
+/// `degraded` if the backlog is above the configured threshold, so that we can alert on it.
+#[tracing::instrument(name = "Report the delivery queue backlog", skip_all)]
+pub async fn queue_backlog(
+    pool: web::Data<PgPool>,
+    settings: web::Data<NewsletterSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let r = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "depth!",
+            EXTRACT(EPOCH FROM now() - MIN(created_at))::bigint AS oldest_pending_age_seconds
+        FROM issue_delivery_queue
+        "#
+    )
+    .fetch_one(pool.get_ref())
+    .await
+    .context("Failed to compute the delivery queue backlog.")
+    .map_err(e500)?;
+
+    let status = if r.depth > settings.queue_backlog_threshold {
+        "degraded"
+    } else {
+        "ok"
+    };
+
+    Ok(HttpResponse::Ok().json(QueueBacklog {
+        depth: r.depth,
+        oldest_pending_age_seconds: r.oldest_pending_age_seconds,
+        status,
+    }))
+}
@@ -0,0 +1,49 @@
+use crate::tenant::Tenant;
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+#[derive(serde::Serialize)]
+struct SubscriberStats {
+    total: i64,
+    by_source: Vec<SourceCount>,
+}
+
+#[derive(serde::Serialize)]
+struct SourceCount {
+    // `None` for the subscribers who did not tell us where they came from.
+    source: Option<String>,
+    utm_campaign: Option<String>,
+    subscribers: i64,
+}
+
+/// Reports how many confirmed subscribers the tenant has, broken down by the `source` and
+/// `utm_campaign` they subscribed with, as JSON. The largest groups come first. Pending and
+/// unsubscribed subscribers are not counted.
+#[tracing::instrument(name = "Report subscriber stats", skip_all)]
+pub async fn subscriber_stats(
+    tenant: Tenant,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let by_source = sqlx::query_as!(
+        SourceCount,
+        r#"
+        SELECT source, utm_campaign, COUNT(*) AS "subscribers!"
+        FROM subscriptions
+        WHERE tenant_id = $1 AND status = 'confirmed'
+        GROUP BY source, utm_campaign
+        ORDER BY 3 DESC, source, utm_campaign
+        "#,
+        tenant.id()
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to break subscribers down by source.")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(SubscriberStats {
+        total: by_source.iter().map(|s| s.subscribers).sum(),
+        by_source,
+    }))
+}
@@ -0,0 +1,111 @@
+use crate::audit_log::{record_audit_event, AuditAction};
+use crate::authentication::UserId;
+use crate::configuration::ListCleaningSettings;
+use crate::domain::SubscriberEmail;
+use crate::subscription_events::{record_subscription_event, SubscriptionEventType};
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Serialize)]
+struct CleaningReport {
+    invalidated: usize,
+}
+
+/// # List Cleaning
+/// Addresses we accepted in the past may no longer pass our checks - e.g. they were imported, or our
+/// validation got stricter. Before a big send, admins re-validate every confirmed subscriber: those
+/// failing `SubscriberEmail::parse`, or the checks enabled in `ListCleaningSettings`, are marked
+/// `invalid` and no longer get our newsletter.
+///
+/// Returns how many subscribers have been marked `invalid`, as JSON.
+#[tracing::instrument(name = "Clean the subscriber list", skip_all, fields(user_id=%*user_id))]
+pub async fn clean_subscribers(
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<ListCleaningSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    // Locked until we are done: a subscriber cannot be confirmed again behind our back.
+    let subscribers = sqlx::query!(
+        r#"
+        SELECT id, email
+        FROM subscriptions
+        WHERE status = 'confirmed'
+        FOR UPDATE
+        "#
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to retrieve the confirmed subscribers.")
+    .map_err(e500)?;
+
+    let invalid: Vec<Uuid> = subscribers
+        .into_iter()
+        .filter_map(|s| match check_address(&s.email, &settings) {
+            Ok(()) => None,
+            Err(reason) => {
+                tracing::info!(subscriber_id = %s.id, %reason, "Flagging a subscriber as invalid.");
+                Some(s.id)
+            }
+        })
+        .collect();
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'invalid' WHERE id = ANY($1)"#,
+        &invalid
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to mark the subscribers as invalid.")
+    .map_err(e500)?;
+    for subscriber_id in &invalid {
+        record_subscription_event(
+            &mut transaction,
+            *subscriber_id,
+            SubscriptionEventType::Invalidated,
+            "list_cleaning",
+        )
+        .await
+        .context("Failed to record the invalidation event.")
+        .map_err(e500)?;
+    }
+    record_audit_event(
+        &mut transaction,
+        **user_id,
+        AuditAction::SubscribersCleaned,
+        Some(&invalid.len().to_string()),
+    )
+    .await
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to clean the subscriber list.")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(CleaningReport {
+        invalidated: invalid.len(),
+    }))
+}
+
+/// Why `email` should no longer get our newsletter, if it should not.
+fn check_address(email: &str, settings: &ListCleaningSettings) -> Result<(), String> {
+    let email = SubscriberEmail::parse(email.to_owned())?;
+    if settings.flag_role_accounts && email.is_role_account() {
+        return Err(format!("{email} is a role account."));
+    }
+    if settings
+        .disposable_domains
+        .iter()
+        .any(|domain| domain.eq_ignore_ascii_case(email.domain()))
+    {
+        return Err(format!("{email} is on a disposable email domain."));
+    }
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use crate::subscription_events::{get_subscription_events, SubscriptionEvent};
+use crate::utils::e500;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Serialize)]
+struct SubscriberData {
+    subscriber: SubscriberProfile,
+    events: Vec<SubscriptionEvent>,
+    deliveries: Vec<NewsletterDelivery>,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberProfile {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: String,
+    consented_at: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct NewsletterDelivery {
+    newsletter_title: String,
+    status: String,
+    delivered_at: String,
+}
+
+/// # Access Requests
+/// Everything we hold on a subscriber, as a single JSON document, to fulfil their requests to access
+/// their personal data: their profile, the history of their subscription and the newsletter issues
+/// we sent them, oldest first. We do not track opens nor clicks, hence there are none to export.
+///
+/// Internal identifiers (e.g. our ids, or the `Message-ID`s of the emails) are left out: they are
+/// meaningless to the subscriber.
+#[tracing::instrument(name = "Export a subscriber's data", skip(pool))]
+pub async fn export_subscriber_data(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let subscriber = get_subscriber_profile(&pool, subscriber_id)
+        .await
+        .context("Failed to retrieve the subscriber.")
+        .map_err(e500)?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("There is no such subscriber."))?;
+    let events = get_subscription_events(&pool, subscriber_id)
+        .await
+        .context("Failed to retrieve the subscription events.")
+        .map_err(e500)?;
+    let deliveries = get_newsletter_deliveries(&pool, &subscriber.email)
+        .await
+        .context("Failed to retrieve the newsletter deliveries.")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(SubscriberData {
+        subscriber,
+        events,
+        deliveries,
+    }))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_subscriber_profile(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberProfile>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email, name, status, subscribed_at, consented_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| SubscriberProfile {
+        email: r.email,
+        name: r.name,
+        status: r.status,
+        subscribed_at: r.subscribed_at.to_rfc3339(),
+        consented_at: r.consented_at.map(|c| c.to_rfc3339()),
+    }))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_newsletter_deliveries(
+    pool: &PgPool,
+    subscriber_email: &str,
+) -> Result<Vec<NewsletterDelivery>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT i.title, d.status, d.delivered_at
+        FROM newsletter_deliveries d
+        JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+        WHERE d.subscriber_email = $1
+        ORDER BY d.delivered_at
+        "#,
+        subscriber_email
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| NewsletterDelivery {
+            newsletter_title: r.title,
+            status: r.status,
+            delivered_at: r.delivered_at.to_rfc3339(),
+        })
+        .collect())
+}
@@ -0,0 +1,69 @@
+use crate::subscription_events::get_subscription_events;
+use crate::utils::e500;
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context as anyhow_ctx;
+use sqlx::PgPool;
+use tera::{Context, Tera};
+use uuid::Uuid;
+
+#[derive(serde::Serialize)]
+struct SubscriberDetails {
+    email: String,
+    name: String,
+    status: String,
+    // The confirmation email could not be sent, even after retrying.
+    confirmation_failed: bool,
+    // Where they came from, if they told us.
+    source: Option<String>,
+    utm_campaign: Option<String>,
+}
+
+/// A subscriber's current state, followed by the timeline of their subscription events.
+#[tracing::instrument(name = "Show subscriber details", skip(pool, templates))]
+pub async fn subscriber_details(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let subscriber = get_subscriber(&pool, subscriber_id)
+        .await
+        .context("Failed to retrieve the subscriber.")
+        .map_err(e500)?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("There is no such subscriber."))?;
+    let events = get_subscription_events(&pool, subscriber_id)
+        .await
+        .context("Failed to retrieve the subscription events.")
+        .map_err(e500)?;
+
+    let mut context = Context::new();
+    context.insert("subscriber", &subscriber);
+    context.insert("events", &events);
+    let html_body = templates
+        .render("subscriber.html", &context)
+        .context("Error rendering subscriber html")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberDetails>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriberDetails,
+        r#"
+        SELECT email, name, status, confirmation_failed, source, utm_campaign
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+}
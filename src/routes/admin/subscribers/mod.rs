@@ -0,0 +1,11 @@
+mod clean;
+mod data_export;
+mod detail;
+mod export;
+mod search;
+
+pub use clean::clean_subscribers;
+pub use data_export::export_subscriber_data;
+pub use detail::subscriber_details;
+pub use export::export_subscribers;
+pub use search::search_subscribers;
@@ -0,0 +1,105 @@
+use crate::utils::{e400, e500};
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context as anyhow_ctx;
+use sqlx::PgPool;
+use tera::{Context, Tera};
+use uuid::Uuid;
+
+/// Shorter queries match (almost) every subscriber - they are both useless and expensive.
+const MIN_QUERY_LENGTH: usize = 3;
+/// We never return more than a page worth of results per request.
+const PAGE_SIZE: i64 = 20;
+
+#[derive(serde::Deserialize)]
+pub struct SearchParameters {
+    q: String,
+    #[serde(default = "first_page")]
+    page: u32,
+}
+
+fn first_page() -> u32 {
+    1
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberRecord {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+}
+
+/// Case-insensitive substring search on the subscribers' email and name.
+#[tracing::instrument(name = "Search subscribers", skip(parameters, pool, templates))]
+pub async fn search_subscribers(
+    parameters: web::Query<SearchParameters>,
+    pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let SearchParameters { q, page } = parameters.0;
+    let q = q.trim();
+    if q.chars().count() < MIN_QUERY_LENGTH {
+        return Err(e400(format!(
+            "The search query must be at least {MIN_QUERY_LENGTH} characters long."
+        )));
+    }
+    let page = page.max(1);
+    let offset = (page as i64 - 1) * PAGE_SIZE;
+
+    // We fetch one extra row to find out if there is a next page.
+    let mut subscribers = find_subscribers(&pool, q, PAGE_SIZE + 1, offset)
+        .await
+        .context("Failed to search subscribers.")
+        .map_err(e500)?;
+    let has_next_page = subscribers.len() as i64 > PAGE_SIZE;
+    subscribers.truncate(PAGE_SIZE as usize);
+
+    let mut context = Context::new();
+    context.insert("query", q);
+    context.insert("encoded_query", &urlencoding::encode(q));
+    context.insert("subscribers", &subscribers);
+    context.insert("page", &page);
+    context.insert("has_next_page", &has_next_page);
+    let html_body = templates
+        .render("subscribers.html", &context)
+        .context("Error rendering subscribers html")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(skip_all)]
+async fn find_subscribers(
+    pool: &PgPool,
+    query: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<SubscriberRecord>, sqlx::Error> {
+    // `%` and `_` are wildcards for `ILIKE` - we want to match them literally.
+    let pattern = format!(
+        "%{}%",
+        query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    sqlx::query_as!(
+        SubscriberRecord,
+        r#"
+        SELECT id, email, name, status
+        FROM subscriptions
+        WHERE email ILIKE $1 OR name ILIKE $1
+        ORDER BY email
+        LIMIT $2
+        OFFSET $3
+        "#,
+        pattern,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
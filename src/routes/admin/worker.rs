@@ -0,0 +1,34 @@
+use crate::audit_log::{record_audit_event, AuditAction};
+use crate::authentication::UserId;
+use crate::utils::{e500, see_other};
+use crate::worker_pause::WorkerPause;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+/// Deliveries stop once the worker is done with the task at hand, if any.
+pub async fn pause_worker(
+    user_id: web::ReqData<UserId>,
+    worker_pause: web::Data<WorkerPause>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    worker_pause.pause().await.map_err(e500)?;
+    record_audit_event(pool.get_ref(), **user_id, AuditAction::WorkerPaused, None)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The delivery worker has been paused.").send();
+    Ok(see_other("/admin/dashboard"))
+}
+
+pub async fn resume_worker(
+    user_id: web::ReqData<UserId>,
+    worker_pause: web::Data<WorkerPause>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    worker_pause.resume().await.map_err(e500)?;
+    record_audit_event(pool.get_ref(), **user_id, AuditAction::WorkerResumed, None)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("The delivery worker has been resumed.").send();
+    Ok(see_other("/admin/dashboard"))
+}
@@ -0,0 +1,99 @@
+use crate::configuration::ApiSettings;
+use crate::tenant::Tenant;
+use crate::utils::{e400, e500};
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Parameters {
+    // 1-based.
+    page: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+struct ConfirmedSubscriber {
+    email: String,
+    name: String,
+    // RFC 3339. Unknown for subscribers who confirmed before we recorded subscription events.
+    confirmed_at: Option<String>,
+}
+
+/// # Partner Sync
+/// The confirmed subscribers, `page_size` at a time, for data partners to sync our list. Those who
+/// unsubscribed, or whose address bounced, are left out.
+///
+/// `next_page` is `null` on the last page. Partners only see the subscribers of the tenant they
+/// are calling.
+#[tracing::instrument(name = "List confirmed subscribers", skip(pool, settings))]
+pub async fn list_confirmed_subscribers(
+    tenant: Tenant,
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<ApiSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = parameters.page.unwrap_or(1);
+    if page < 1 {
+        return Err(e400("Pages start at 1."));
+    }
+    let mut subscribers = get_confirmed_subscribers_page(&pool, &tenant, page, settings.page_size)
+        .await
+        .context("Failed to retrieve a page of confirmed subscribers.")
+        .map_err(e500)?;
+    // We fetched one more than a page to find out whether there is a next one.
+    let has_next_page = subscribers.len() as i64 > settings.page_size;
+    subscribers.truncate(settings.page_size as usize);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "page": page,
+        "next_page": has_next_page.then_some(page + 1),
+        "subscribers": subscribers,
+    })))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn get_confirmed_subscribers_page(
+    pool: &PgPool,
+    tenant: &Tenant,
+    page: i64,
+    page_size: i64,
+) -> Result<Vec<ConfirmedSubscriber>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            s.email,
+            s.name,
+            (
+                SELECT max(e.occurred_at)
+                FROM subscription_events e
+                WHERE e.subscriber_id = s.id AND e.event_type = 'confirmed'
+            ) AS confirmed_at
+        FROM subscriptions s
+        WHERE
+            s.tenant_id = $3 AND
+            s.status = 'confirmed' AND
+            NOT EXISTS (
+                SELECT 1
+                FROM subscription_events e
+                WHERE e.subscriber_id = s.id AND e.event_type = 'bounced'
+            )
+        ORDER BY s.subscribed_at, s.id
+        LIMIT $1
+        OFFSET $2
+        "#,
+        page_size + 1,
+        (page - 1) * page_size,
+        tenant.id()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| ConfirmedSubscriber {
+            email: r.email,
+            name: r.name,
+            confirmed_at: r.confirmed_at.map(|t| t.to_rfc3339()),
+        })
+        .collect())
+}
@@ -1,8 +1,20 @@
+use crate::session_state::TypedSession;
+use crate::utils::e500;
 use actix_web::http::header::ContentType;
-use actix_web::HttpResponse;
+use actix_web::{web, HttpResponse};
+use tera::{Context, Tera};
 
-pub async fn home() -> HttpResponse {
-    HttpResponse::Ok()
+/// Logged-in admins get a link to their dashboard. The session is only looked at, never required:
+/// if it cannot be read, the visitor gets the anonymous home page.
+pub async fn home(
+    session: TypedSession,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let is_admin = matches!(session.get_user_id(), Ok(Some(_)));
+    let mut context = Context::new();
+    context.insert("is_admin", &is_admin);
+    let html_body = templates.render("home.html", &context).map_err(e500)?;
+    Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(include_str!("home.html"))
+        .body(html_body))
 }
@@ -29,7 +29,7 @@ use tera::{Context, Tera};
 /// resulting hash is then concatenated to the secret and hashed again - the output is message tag.
 pub async fn login_form(
     flash_messages: IncomingFlashMessages,
-    templates: web::Data<&Tera>,
+    templates: web::Data<Tera>,
 ) -> Result<HttpResponse, LoginError> {
     let mut error_html = String::new();
     // Display all messages, not just errors!
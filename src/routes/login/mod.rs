@@ -2,4 +2,4 @@ mod get;
 mod post;
 
 pub use get::*;
-pub use post::*;
+pub use post::{login, LoginError};
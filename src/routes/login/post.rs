@@ -39,18 +39,27 @@ pub async fn login(
         password: form.0.password,
     };
 
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
 
     match authentication::validate_credentials(credentials, &pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
             session.renew();
             session
                 .insert_user_id(user_id)
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            let now = chrono::Utc::now().timestamp();
+            session
+                .insert_logged_in_at(now)
+                .and_then(|_| session.insert_last_seen(now))
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
 
+            let target = session
+                .take_login_redirect()
+                .filter(|t| authentication::is_safe_login_redirect(t))
+                .unwrap_or_else(|| "/admin/dashboard".into());
             Ok(HttpResponse::SeeOther()
-                .insert_header((LOCATION, "/admin/dashboard"))
+                .insert_header((LOCATION, target))
                 .finish())
         }
         Err(e) => {
@@ -1,13 +1,23 @@
 mod admin;
+mod api;
 mod health_check;
 mod home;
 mod login;
 mod subscription_confirm;
+mod subscription_preferences;
+mod subscription_unsubscribe;
 mod subscriptions;
+mod webhooks;
+mod widget;
 
 pub use admin::*;
+pub use api::*;
 pub use health_check::*;
 pub use home::*;
 pub use login::*;
 pub use subscription_confirm::*;
+pub use subscription_preferences::*;
+pub use subscription_unsubscribe::*;
 pub use subscriptions::*;
+pub use webhooks::*;
+pub use widget::*;
@@ -1,8 +1,17 @@
+use crate::conversion_funnel::{record_funnel_step, FunnelStep};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
 use crate::routes::subscriptions::error_chain_fmt;
+use crate::startup::WelcomeEmails;
+use crate::subscriber_repository::{SubscriberRecord, SubscriberRepository, SubscriberStatus};
+use crate::tenant::Tenant;
+use crate::welcome_series::enqueue_welcome_series;
+use actix_web::http::header::ContentType;
 use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse, ResponseError};
 use anyhow::Context;
 use sqlx::PgPool;
+use tera::Tera;
 use uuid::Uuid;
 
 /// The `Parameters` struct defines all the query parameters that we *expect* to see in the incoming
@@ -39,46 +48,307 @@ impl ResponseError for ConfirmationError {
     }
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
+/// # Idempotent Confirmations
+/// Email clients pre-fetch links to scan them, hence the link can be followed more than once. Only
+/// the first request confirms the subscriber, sends the welcome email and queues the welcome series,
+/// if enabled: the following ones are told the subscription was already confirmed.
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(parameters, repository, pool, email_client, templates, welcome_emails)
+)]
 pub async fn confirm(
+    tenant: Tenant,
     parameters: web::Query<Parameters>,
+    repository: web::Data<dyn SubscriberRepository>,
     pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Tera>,
+    welcome_emails: web::Data<WelcomeEmails>,
 ) -> Result<HttpResponse, ConfirmationError> {
-    let subscriber_id = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
+    let confirmed_subscriber = confirm_subscription(
+        repository.get_ref(),
+        Some(&tenant),
+        &parameters.subscription_token,
+    )
+    .await?;
+
+    let already_confirmed = confirmed_subscriber.is_none();
+    if let Some(subscriber) = &confirmed_subscriber {
+        record_funnel_step(FunnelStep::Confirmed, subscriber.id);
+        // As for the welcome email, the subscription is confirmed whether or not this succeeds.
+        if let Err(e) =
+            enqueue_welcome_series(pool.get_ref(), subscriber.id, &welcome_emails.series).await
+        {
+            tracing::error!(error.cause_chain = ?e, error.message = %e,
+                "Failed to queue the welcome series of a confirmed subscriber.");
+        }
+    }
+    if let (Some(subscriber), true) = (confirmed_subscriber, welcome_emails.send_welcome_email) {
+        // The subscription is confirmed: a failure to welcome them should not surface as an error.
+        if let Err(e) = send_welcome(&email_client, &templates, subscriber).await {
+            tracing::error!(error.cause_chain = ?e, error.message = %e,
+                "Failed to send a welcome email to a confirmed subscriber.");
+        }
+    }
+
+    let mut context = tera::Context::new();
+    context.insert("already_confirmed", &already_confirmed);
+    let html_body = templates
+        .render("subscription_confirmed.html", &context)
+        .context("Failed to render the subscription confirmed page.")?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+/// Returns `None` if the subscriber was already confirmed: clicking on the link more than once is
+/// not a state change. Nor is clicking on it once they are no longer pending - e.g. they
+/// unsubscribed, or their address bounced.
+async fn confirm_subscription(
+    repository: &dyn SubscriberRepository,
+    tenant: Option<&Tenant>,
+    subscription_token: &str,
+) -> Result<Option<SubscriberRecord>, ConfirmationError> {
+    let subscriber_id = repository
+        .find_by_token(tenant, subscription_token)
         .await
         .context("Failed to retrieve the subscriber id associated with the provided token.")?
         .ok_or(ConfirmationError::UnknownToken)?;
-
-    confirm_subscriber(&pool, subscriber_id)
+    let confirmed_subscriber = repository
+        .set_status(
+            subscriber_id,
+            SubscriberStatus::Confirmed,
+            "confirmation_link",
+        )
         .await
         .context("Failed to update the subscriber status to `confirmed`.")?;
-
-    Ok(HttpResponse::Ok().finish())
+    Ok(confirmed_subscriber)
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
-        subscriber_id,
-    )
-    .execute(pool)
-    .await?;
-
+#[tracing::instrument(name = "Send a welcome email", skip_all)]
+async fn send_welcome(
+    email_client: &EmailClient,
+    templates: &Tera,
+    subscriber: SubscriberRecord,
+) -> Result<(), anyhow::Error> {
+    let email = SubscriberEmail::parse(subscriber.email).map_err(anyhow::Error::msg)?;
+    let mut context = tera::Context::new();
+    context.insert("name", &subscriber.name);
+    let html_body = templates
+        .render("welcome.html", &context)
+        .context("Failed to render the html welcome email.")?;
+    let plain_body = templates
+        .render("welcome.txt", &context)
+        .context("Failed to render the plain text welcome email.")?;
+    email_client
+        .send_email(
+            &email,
+            "Your subscription is confirmed",
+            &html_body,
+            &plain_body,
+            &[],
+        )
+        .await
+        .context("Failed to send the welcome email.")?;
     Ok(())
 }
 
+/// Tokens issued to the subscribers of another tenant are unknown to `tenant`, if any.
 #[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
-async fn get_subscriber_id_from_token(
+pub(crate) async fn get_subscriber_id_from_token(
     pool: &PgPool,
+    tenant: Option<&Tenant>,
     subscription_token: &str,
 ) -> Result<Option<Uuid>, sqlx::Error> {
     let result = sqlx::query!(
-        "SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1",
+        r#"
+        SELECT t.subscriber_id
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE t.subscription_token = $1 AND ($2::text IS NULL OR s.tenant_id = $2)
+        "#,
         subscription_token,
+        tenant.map(Tenant::id),
     )
     .fetch_optional(pool)
     .await?;
 
     Ok(result.map(|r| r.subscriber_id))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::NewSubscriber;
+    use crate::routes::SubscribeError;
+    use crate::subscriber_repository::StoredSubscription;
+    use claims::{assert_none, assert_some};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Keeps subscribers in memory, and the status changes it was asked for.
+    #[derive(Default)]
+    struct FakeSubscriberRepository {
+        subscribers: Mutex<HashMap<Uuid, SubscriberRecord>>,
+        tokens: HashMap<String, Uuid>,
+        status_changes: Mutex<Vec<(Uuid, SubscriberStatus)>>,
+    }
+
+    impl FakeSubscriberRepository {
+        fn with_pending_subscriber(subscription_token: &str) -> (Self, Uuid) {
+            let subscriber = SubscriberRecord {
+                id: Uuid::new_v4(),
+                email: "ursula_le_guin@gmail.com".into(),
+                name: "le guin".into(),
+                status: "pending_confirmation".into(),
+            };
+            let id = subscriber.id;
+            let repository = Self {
+                subscribers: Mutex::new(HashMap::from([(id, subscriber)])),
+                tokens: HashMap::from([(subscription_token.to_owned(), id)]),
+                ..Default::default()
+            };
+            (repository, id)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SubscriberRepository for FakeSubscriberRepository {
+        async fn insert(
+            &self,
+            _tenant: &Tenant,
+            new_subscriber: &NewSubscriber,
+            _consent: bool,
+            _max_per_domain: Option<i64>,
+            _double_opt_in: bool,
+            _source: &str,
+        ) -> Result<StoredSubscription, SubscribeError> {
+            let subscriber = SubscriberRecord {
+                id: Uuid::new_v4(),
+                email: new_subscriber.email.as_ref().to_owned(),
+                name: new_subscriber.name.as_ref().to_owned(),
+                status: "pending_confirmation".into(),
+            };
+            let subscriber_id = subscriber.id;
+            self.subscribers
+                .lock()
+                .unwrap()
+                .insert(subscriber_id, subscriber);
+            Ok(StoredSubscription {
+                subscriber_id,
+                is_new: true,
+                subscription_token: None,
+            })
+        }
+
+        async fn find_by_email(
+            &self,
+            _tenant: &Tenant,
+            email: &SubscriberEmail,
+        ) -> Result<Option<SubscriberRecord>, anyhow::Error> {
+            Ok(self
+                .subscribers
+                .lock()
+                .unwrap()
+                .values()
+                .find(|s| s.email == email.as_ref())
+                .cloned())
+        }
+
+        async fn find_by_token(
+            &self,
+            _tenant: Option<&Tenant>,
+            subscription_token: &str,
+        ) -> Result<Option<Uuid>, anyhow::Error> {
+            Ok(self.tokens.get(subscription_token).copied())
+        }
+
+        async fn set_status(
+            &self,
+            subscriber_id: Uuid,
+            status: SubscriberStatus,
+            _source: &str,
+        ) -> Result<Option<SubscriberRecord>, anyhow::Error> {
+            self.status_changes
+                .lock()
+                .unwrap()
+                .push((subscriber_id, status));
+            let mut subscribers = self.subscribers.lock().unwrap();
+            Ok(subscribers
+                .get_mut(&subscriber_id)
+                .filter(|s| s.status != status.as_str())
+                .filter(|s| match status.required_previous_status() {
+                    Some(previous) => s.status == previous,
+                    None => true,
+                })
+                .map(|s| {
+                    s.status = status.as_str().into();
+                    s.clone()
+                }))
+        }
+
+        async fn list(&self, _tenant: &Tenant) -> Result<Vec<SubscriberRecord>, anyhow::Error> {
+            Ok(self.subscribers.lock().unwrap().values().cloned().collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn a_known_token_confirms_its_subscriber() {
+        let (repository, subscriber_id) =
+            FakeSubscriberRepository::with_pending_subscriber("token");
+
+        let confirmed = confirm_subscription(&repository, None, "token")
+            .await
+            .unwrap();
+
+        assert_some!(confirmed);
+        assert_eq!(
+            vec![(subscriber_id, SubscriberStatus::Confirmed)],
+            *repository.status_changes.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn confirming_twice_is_not_a_state_change() {
+        let (repository, _) = FakeSubscriberRepository::with_pending_subscriber("token");
+
+        confirm_subscription(&repository, None, "token")
+            .await
+            .unwrap();
+        let confirmed = confirm_subscription(&repository, None, "token")
+            .await
+            .unwrap();
+
+        assert_none!(confirmed);
+    }
+
+    #[tokio::test]
+    async fn an_unsubscribed_subscriber_cannot_be_confirmed() {
+        let (repository, subscriber_id) =
+            FakeSubscriberRepository::with_pending_subscriber("token");
+        repository
+            .set_status(subscriber_id, SubscriberStatus::Unsubscribed, "test")
+            .await
+            .unwrap();
+
+        let confirmed = confirm_subscription(&repository, None, "token")
+            .await
+            .unwrap();
+
+        assert_none!(confirmed);
+        assert_eq!(
+            "unsubscribed",
+            repository.subscribers.lock().unwrap()[&subscriber_id].status
+        );
+    }
+
+    #[tokio::test]
+    async fn an_unknown_token_is_rejected_without_changing_any_status() {
+        let (repository, _) = FakeSubscriberRepository::with_pending_subscriber("token");
+
+        let outcome = confirm_subscription(&repository, None, "another-token").await;
+
+        assert!(matches!(outcome, Err(ConfirmationError::UnknownToken)));
+        assert!(repository.status_changes.lock().unwrap().is_empty());
+    }
+}
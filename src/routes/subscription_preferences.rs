@@ -0,0 +1,77 @@
+use crate::routes::subscriptions::error_chain_fmt;
+use crate::signed_token::{verify_subscriber_token, MANAGE_DATA_SCOPE};
+use crate::startup::HmacSecret;
+use actix_web::http::header::ContentType;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context as _;
+use sqlx::PgPool;
+use tera::{Context, Tera};
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesParameters {
+    token: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("The link is invalid, or the subscriber no longer exists")]
+    InvalidToken,
+}
+
+impl std::fmt::Debug for PreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PreferencesError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidToken => StatusCode::UNAUTHORIZED,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Subscribers land here from the signed "manage your data" link in our emails, and see everything
+/// we hold on them.
+#[tracing::instrument(name = "Show the preferences of a subscriber", skip_all)]
+pub async fn subscription_preferences(
+    parameters: web::Query<PreferencesParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id =
+        verify_subscriber_token(&hmac_secret.0, MANAGE_DATA_SCOPE, &parameters.token)
+            .ok_or(PreferencesError::InvalidToken)?;
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT email, name, status, locale, subscribed_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the subscriber.")?
+    .ok_or(PreferencesError::InvalidToken)?;
+
+    let mut context = Context::new();
+    context.insert("email", &subscriber.email);
+    context.insert("name", &subscriber.name);
+    context.insert("status", &subscriber.status);
+    context.insert("locale", &subscriber.locale);
+    context.insert("subscribed_at", &subscriber.subscribed_at.to_rfc3339());
+    let html_body = templates
+        .render("subscription_preferences.html", &context)
+        .context("Failed to render the preferences page.")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
@@ -0,0 +1,149 @@
+use crate::routes::subscription_confirm::get_subscriber_id_from_token;
+use crate::routes::subscriptions::error_chain_fmt;
+use crate::signed_token::{verify_subscriber_token, UNSUBSCRIBE_SCOPE};
+use crate::startup::HmacSecret;
+use crate::subscription_events::{record_subscription_event, SubscriptionEventType};
+use actix_web::http::header::ContentType;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context as _;
+use sqlx::{PgPool, Postgres, Transaction};
+use tera::{Context, Tera};
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParameters {
+    subscription_token: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("There is no subscriber associated with the provided token")]
+    UnknownToken,
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for UnsubscribeError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnknownToken => StatusCode::UNAUTHORIZED,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// # Unsubscribing
+/// Following the link only shows a page asking to confirm: email clients and security scanners
+/// pre-fetch links, and must not unsubscribe anybody. The page posts back to the same URL, as mail
+/// clients do for a one-click unsubscribe (RFC 8058) - the token is always in the query string.
+///
+/// Subscribers are identified either by the token signed for them in the unsubscribe link of
+/// newsletter issues, or by the same token we sent them to confirm their subscription.
+#[tracing::instrument(
+    name = "Show the unsubscribe form",
+    skip(parameters, pool, hmac_secret, templates)
+)]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    get_subscriber_id(&pool, &hmac_secret, &parameters.subscription_token).await?;
+
+    let mut context = Context::new();
+    context.insert("subscription_token", &parameters.subscription_token);
+    let html_body = templates
+        .render("unsubscribe.html", &context)
+        .context("Failed to render the unsubscribe form.")?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber",
+    skip(parameters, pool, hmac_secret, templates)
+)]
+pub async fn unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let subscriber_id =
+        get_subscriber_id(&pool, &hmac_secret, &parameters.subscription_token).await?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if unsubscribe_subscriber(&mut transaction, subscriber_id)
+        .await
+        .context("Failed to update the subscriber status to `unsubscribed`.")?
+    {
+        record_subscription_event(
+            &mut transaction,
+            subscriber_id,
+            SubscriptionEventType::Unsubscribed,
+            "unsubscribe_link",
+        )
+        .await
+        .context("Failed to record the unsubscription event.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to unsubscribe a subscriber.")?;
+
+    let html_body = templates
+        .render("unsubscribed.html", &Context::new())
+        .context("Failed to render the unsubscribed page.")?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+async fn get_subscriber_id(
+    pool: &PgPool,
+    hmac_secret: &HmacSecret,
+    subscription_token: &str,
+) -> Result<Uuid, UnsubscribeError> {
+    if let Some(subscriber_id) =
+        verify_subscriber_token(&hmac_secret.0, UNSUBSCRIBE_SCOPE, subscription_token)
+    {
+        return Ok(subscriber_id);
+    }
+    // Subscription tokens are unique across tenants: they are looked up whichever tenant the
+    // subscriber belongs to.
+    get_subscriber_id_from_token(pool, None, subscription_token)
+        .await
+        .context("Failed to retrieve the subscriber id associated with the provided token.")?
+        .ok_or(UnsubscribeError::UnknownToken)
+}
+
+/// Returns `false` if the subscriber had already unsubscribed.
+#[tracing::instrument(
+    name = "Mark subscriber as unsubscribed",
+    skip(subscriber_id, transaction)
+)]
+async fn unsubscribe_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1 AND status <> 'unsubscribed'"#,
+        subscriber_id,
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -1,12 +1,29 @@
-use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::client_ip::client_ip;
+use crate::confirmation_retries::{enqueue_confirmation_retry, ConfirmationEmail};
+use crate::conversion_funnel::{record_funnel_step, FunnelStep};
+use crate::domain::{
+    AttributionTag, ConfirmationLink, NewSubscriber, SubscriberEmail, SubscriberEmailPolicy,
+    SubscriberLocale, SubscriberName, SubscriberTimeZone,
+};
+use crate::duplicate_submissions::DuplicateSubmissions;
 use crate::email_client::EmailClient;
-use crate::startup::ApplicationBaseUrl;
-use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use crate::feature_flags::DOUBLE_OPT_IN;
+use crate::signed_token::manage_data_link;
+use crate::startup::{HoneypotField, SubscribeState, SubscribeSuccessRedirect};
+use crate::subscribe_rate_limit::SubscribeRateLimit;
+use crate::subscriber_repository::StoredSubscription;
+use crate::subscription_events::{record_subscription_event, SubscriptionEventType};
+use crate::tenant::Tenant;
+use actix_web::http::header::{ContentType, ACCEPT, LOCATION, RETRY_AFTER};
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, ResponseError};
 use anyhow::Context as anyhow_ctx;
 use chrono;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
+use secrecy::Secret;
+use serde_aux::field_attributes::deserialize_bool_from_anything;
 use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 use tera::{Context, Tera};
 use uuid::Uuid;
 
@@ -51,13 +68,18 @@ impl std::fmt::Display for StoreTokenError {
 /// * #[ error(/* */) ] defines the `Display` representation of the enum variant it is applied to.
 /// * #[ source ] is used to denote what should be returned as root cause in `Error::source`;
 /// * #[ from ] automatically derives an implementation of From for the type it has been applied to
-/// into the top-level error type(e.g. impl From<StoreTokenError> for SubscribeError {/* */}). The
-/// field annotated with #[ from ] is also used as error source, saving us from having to use two
-/// annotations on the same field.
+///   into the top-level error type(e.g. impl From<StoreTokenError> for SubscribeError {/* */}). The
+///   field annotated with #[ from ] is also used as error source, saving us from having to use two
+///   annotations on the same field.
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    // The same email address subscribed to the same tenant concurrently, and got there first.
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Too many subscription attempts. Try again in {retry_after} seconds.")]
+    RateLimited { retry_after: u64 },
     // Transparent delegates both `Display`'s and `source`'s implementation to the type wrapped by
     // `UnexpectedError`.
     /// We are wrapping dyn std::error::Error into a `Box` because the size of trait objects is not
@@ -80,9 +102,22 @@ impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::Conflict(_) => StatusCode::CONFLICT,
+            SubscribeError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    /// Clients over the rate limit are told when to retry, with a `Retry-After` header.
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let SubscribeError::RateLimited { retry_after } = self {
+            response.insert_header((RETRY_AFTER, *retry_after));
+        }
+        response
+            .content_type(ContentType::plaintext())
+            .body(self.to_string())
+    }
 }
 
 /// The `Error` trait is, first and foremost, a way to **semantically** mark our type as being an error.
@@ -129,6 +164,37 @@ pub(in crate::routes) fn error_chain_fmt(
 pub struct FormData {
     email: String,
     name: String,
+    // Unchecked checkboxes are not submitted at all. `flatten` has every field deserialized from a
+    // string, booleans included.
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    consent: bool,
+    // e.g. `fr-CA`. Optional: empty if not submitted.
+    #[serde(default)]
+    locale: String,
+    // e.g. `Europe/Paris`. Optional: empty if not submitted.
+    #[serde(default)]
+    time_zone: String,
+    // Where they came from, e.g. `twitter` and `spring-sale`. Optional: empty if not submitted.
+    #[serde(default)]
+    source: String,
+    #[serde(default)]
+    utm_campaign: String,
+    // Every other submitted field, e.g. the honeypot.
+    #[serde(flatten)]
+    other_fields: HashMap<String, String>,
+}
+
+/// Humans do not see the honeypot field, and leave it empty. `other_fields` are the submitted
+/// fields a form does not know about.
+fn fills_in_honeypot(
+    other_fields: &HashMap<String, String>,
+    honeypot_field: &HoneypotField,
+) -> bool {
+    honeypot_field
+        .0
+        .as_ref()
+        .and_then(|field| other_fields.get(field))
+        .map_or(false, |value| !value.is_empty())
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -136,13 +202,58 @@ impl TryFrom<FormData> for NewSubscriber {
 
     /// This refactoring gives us a clearer separation of concerns:
     /// * `try_from` takes care of the conversion from our *wire format*(the url-decoded data
-    /// collected from a HTML form) to our *domain model*(`NewSubscriber`);
+    ///   collected from a HTML form) to our *domain model*(`NewSubscriber`);
     /// * `subscribe` remains in charge of generating the HTTP response to the incoming HTTP request.
     fn try_from(value: FormData) -> Result<Self, Self::Error> {
         let name = SubscriberName::parse(value.name)?;
         let email = SubscriberEmail::parse(value.email)?;
+        let locale = Some(value.locale)
+            .filter(|locale| !locale.trim().is_empty())
+            .map(SubscriberLocale::parse)
+            .transpose()?;
+        let time_zone = Some(value.time_zone)
+            .filter(|time_zone| !time_zone.trim().is_empty())
+            .map(SubscriberTimeZone::parse)
+            .transpose()?;
+        let source = parse_attribution_tag(value.source)?;
+        let utm_campaign = parse_attribution_tag(value.utm_campaign)?;
+
+        Ok(NewSubscriber {
+            email,
+            name,
+            locale,
+            time_zone,
+            source,
+            utm_campaign,
+        })
+    }
+}
+
+/// `None` if nothing was submitted.
+fn parse_attribution_tag(tag: String) -> Result<Option<AttributionTag>, String> {
+    Some(tag)
+        .filter(|tag| !tag.trim().is_empty())
+        .map(AttributionTag::parse)
+        .transpose()
+}
+
+/// Runs the same validation as `subscribe` - without storing anything or sending emails - to give
+/// live feedback in the subscription form. Unlike `try_from`, it reports every invalid field.
+#[tracing::instrument(name = "Validate a subscription form", skip_all)]
+pub async fn validate_subscription(
+    form: web::Form<FormData>,
+    email_policy: web::Data<SubscriberEmailPolicy>,
+) -> HttpResponse {
+    let errors: std::collections::BTreeMap<_, _> = invalid_fields(&form, &email_policy, false)
+        .into_iter()
+        .map(|e| (e.field, e.message))
+        .collect();
 
-        Ok(NewSubscriber { email, name })
+    if errors.is_empty() {
+        HttpResponse::Ok().json(serde_json::json!({ "valid": true }))
+    } else {
+        HttpResponse::UnprocessableEntity()
+            .json(serde_json::json!({ "valid": false, "errors": errors }))
     }
 }
 
@@ -177,53 +288,703 @@ impl TryFrom<FormData> for NewSubscriber {
 /// while the function body focuses on the actual business logic.
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url, templates),
+    skip_all,
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
     )
 )]
 pub async fn subscribe(
+    request: HttpRequest,
+    tenant: Tenant,
     form: web::Form<FormData>,
     // Retrieving a connection from the application state!
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
-    base_url: web::Data<ApplicationBaseUrl>,
-    templates: web::Data<&Tera>,
+    templates: web::Data<Tera>,
+    state: web::Data<SubscribeState>,
 ) -> Result<HttpResponse, SubscribeError> {
+    if let Some(retry_after) = is_rate_limited(&state.rate_limit, &request).await {
+        return Err(SubscribeError::RateLimited { retry_after });
+    }
+    // Bots are told they subscribed, so that they do not try again.
+    if fills_in_honeypot(&form.other_fields, &state.honeypot_field) {
+        tracing::info!("The honeypot field was filled in. Ignoring the subscription.");
+        return subscribe_success_response(
+            &request,
+            &state.success_redirect,
+            &templates,
+            None,
+            false,
+        );
+    }
+    let consent = form.consent;
+    let mut errors = invalid_fields(&form, &state.email_policy, state.require_consent.0);
+    if errors.is_empty()
+        && !is_known_time_zone(&pool, &form.time_zone)
+            .await
+            .context("Failed to look up the time zone of the subscriber.")?
+    {
+        errors.push(FieldError {
+            field: "time_zone",
+            message: format!("{} is not a known time zone.", form.time_zone.trim()),
+        });
+    }
+    if !errors.is_empty() {
+        return subscribe_rejected_response(&request, &templates, errors);
+    }
     // We no longer have `#[from]` for `ValidationError`, so we need to map the error explicitly.
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    let new_subscriber: NewSubscriber =
+        form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    if !is_first_submission(
+        &state.duplicate_submissions,
+        &tenant,
+        &request,
+        &new_subscriber,
+    )
+    .await
+    {
+        return subscribe_success_response(
+            &request,
+            &state.success_redirect,
+            &templates,
+            None,
+            false,
+        );
+    }
+    let double_opt_in = state
+        .feature_flags
+        .is_enabled_or_default(&pool, DOUBLE_OPT_IN)
+        .await;
+    let stored = state
+        .repository
+        .insert(
+            &tenant,
+            &new_subscriber,
+            consent,
+            state.max_per_domain.0,
+            double_opt_in,
+            "subscription_form",
+        )
+        .await?;
+    // Subscribing again is not a new entry into the funnel.
+    if stored.is_new {
+        record_funnel_step(FunnelStep::Subscribed, stored.subscriber_id);
+    }
+
+    // There is nothing left to confirm.
+    let subscription_token = match stored.subscription_token {
+        Some(subscription_token) => subscription_token,
+        None => {
+            if stored.is_new && !double_opt_in {
+                record_funnel_step(FunnelStep::Confirmed, stored.subscriber_id);
+            }
+            return subscribe_success_response(
+                &request,
+                &state.success_redirect,
+                &templates,
+                stored.is_new.then_some(stored.subscriber_id),
+                !double_opt_in,
+            );
+        }
+    };
+
+    let links = ConfirmationEmailLinks {
+        base_url: tenant.base_url().unwrap_or(&state.base_url.0),
+        confirmation_path: &state.confirmation_path.0,
+        app_link_template: state.app_link_template.0.as_deref(),
+        hmac_secret: &state.hmac_secret.0,
+    };
+    let email = confirmation_email(
+        &email_client,
+        &links,
+        stored.subscriber_id,
+        &subscription_token,
+        &templates,
+    )?;
+    send_confirmation_email(
+        &pool,
+        &email_client,
+        state.confirmation_retry_backoff,
+        stored.subscriber_id,
+        &new_subscriber.email,
+        email,
+    )
+    .await
+    .context("Failed to send a confirmation mail.")?;
+
+    subscribe_success_response(
+        &request,
+        &state.success_redirect,
+        &templates,
+        stored.is_new.then_some(stored.subscriber_id),
+        false,
+    )
+}
+
+#[derive(serde::Serialize)]
+struct FieldError {
+    field: &'static str,
+    message: String,
+}
+
+/// Quiet hours are computed in the time zone of the subscriber by Postgres: it has to be in its time
+/// zone database. Nothing to check if no time zone was submitted.
+async fn is_known_time_zone(pool: &PgPool, time_zone: &str) -> Result<bool, sqlx::Error> {
+    let time_zone = time_zone.trim();
+    if time_zone.is_empty() {
+        return Ok(true);
+    }
+    let known = sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM pg_timezone_names WHERE name = $1) AS "known!""#,
+        time_zone
+    )
+    .fetch_one(pool)
+    .await?
+    .known;
+    Ok(known)
+}
+
+/// Every invalid field of the subscription form, in the order they are checked - empty if the form
+/// is valid.
+fn invalid_fields(
+    form: &FormData,
+    email_policy: &SubscriberEmailPolicy,
+    require_consent: bool,
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let mut check = |field, outcome: Result<(), String>| {
+        if let Err(message) = outcome {
+            errors.push(FieldError { field, message });
+        }
+    };
+    if require_consent && !form.consent {
+        check(
+            "consent",
+            Err("You must consent to receive our newsletter.".into()),
+        );
+    }
+    check("name", SubscriberName::parse(form.name.clone()).map(|_| ()));
+    check(
+        "email",
+        SubscriberEmail::parse(form.email.clone()).and_then(|email| email_policy.check(&email)),
+    );
+    if !form.locale.trim().is_empty() {
+        check(
+            "locale",
+            SubscriberLocale::parse(form.locale.clone()).map(|_| ()),
+        );
+    }
+    if !form.time_zone.trim().is_empty() {
+        check(
+            "time_zone",
+            SubscriberTimeZone::parse(form.time_zone.clone()).map(|_| ()),
+        );
+    }
+    check(
+        "source",
+        parse_attribution_tag(form.source.clone()).map(|_| ()),
+    );
+    check(
+        "utm_campaign",
+        parse_attribution_tag(form.utm_campaign.clone()).map(|_| ()),
+    );
+    errors
+}
+
+/// # Branded Rejections
+/// Browsers - asking for `text/html` - are shown `subscribe_error.html`, with a message for each
+/// invalid field: installs brand it like the rest of their templates, from `templates_dir`. Other
+/// clients get a plain `400 Bad Request`, with the first of these messages.
+fn subscribe_rejected_response(
+    request: &HttpRequest,
+    templates: &Tera,
+    errors: Vec<FieldError>,
+) -> Result<HttpResponse, SubscribeError> {
+    let wants_html = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.contains("text/html"))
+        .unwrap_or(false);
+    if !wants_html {
+        let message = errors.into_iter().next().map(|e| e.message);
+        return Err(SubscribeError::ValidationError(message.unwrap_or_default()));
+    }
+
+    let mut context = Context::new();
+    context.insert("errors", &errors);
+    let html_body = templates
+        .render("subscribe_error.html", &context)
+        .context("Failed to render the subscription error page.")?;
+    Ok(HttpResponse::BadRequest()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+/// We would rather process a duplicate than drop a submission: if Redis is not reachable, every
+/// submission is a first one.
+async fn is_first_submission(
+    duplicates: &DuplicateSubmissions,
+    tenant: &Tenant,
+    request: &HttpRequest,
+    new_subscriber: &NewSubscriber,
+) -> bool {
+    match duplicates
+        .is_first_submission(
+            tenant.id(),
+            client_ip(request),
+            new_subscriber.email.as_ref(),
+        )
+        .await
+    {
+        Ok(true) => true,
+        Ok(false) => {
+            tracing::info!("Ignoring a duplicate submission of the subscription form.");
+            false
+        }
+        Err(e) => {
+            tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                "Failed to check for a duplicate submission of the subscription form.");
+            true
+        }
+    }
+}
+
+/// Returns how many seconds the client has to wait, if it is over the limit. We would rather let a
+/// submission through than reject it: if Redis is not reachable, no client is over the limit.
+async fn is_rate_limited(rate_limit: &SubscribeRateLimit, request: &HttpRequest) -> Option<u64> {
+    match rate_limit.check(client_ip(request)).await {
+        Ok(retry_after) => retry_after,
+        Err(e) => {
+            tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                "Failed to check the subscription rate limit.");
+            None
+        }
+    }
+}
+
+/// The most email addresses a household signup can carry.
+const MAX_HOUSEHOLD_SIZE: usize = 10;
+
+#[derive(serde::Deserialize)]
+pub struct HouseholdFormData {
+    name: String,
+    // Comma-separated.
+    emails: String,
+    // As for `FormData`, `flatten` has every field deserialized from a string.
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    consent: bool,
+    // Every other submitted field, e.g. the honeypot.
+    #[serde(flatten)]
+    other_fields: HashMap<String, String>,
+}
+
+#[derive(serde::Serialize)]
+struct HouseholdMemberResult {
+    email: String,
+    /// `pending_confirmation`, `confirmed`, `invalid`, `rejected` (the domain is full), `duplicate`
+    /// (the same client submitted it moments ago) or `failed`.
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl HouseholdMemberResult {
+    fn error(email: String, status: &'static str, error: String) -> Self {
+        Self {
+            email,
+            status,
+            error: Some(error),
+        }
+    }
+}
+
+/// # Household Signups
+/// Partners sign up small groups, sharing a name, in one go: `emails` is a comma-separated list of
+/// up to `MAX_HOUSEHOLD_SIZE` addresses. Every address is handled as if it had been submitted on its
+/// own to `POST /subscriptions` - in a single transaction - and an invalid address does not stop
+/// the others from being subscribed.
+///
+/// The response is a JSON summary, with the outcome for each address in the order they have been
+/// submitted.
+///
+/// The same guards apply as to `POST /subscriptions`: the rate limit, the honeypot field and the
+/// collapsing of double submits, for each address.
+#[tracing::instrument(
+    name = "Adding a household of subscribers",
+    skip_all,
+    fields(subscriber_name = %form.name)
+)]
+pub async fn subscribe_household(
+    request: HttpRequest,
+    tenant: Tenant,
+    form: web::Form<HouseholdFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Tera>,
+    state: web::Data<SubscribeState>,
+) -> Result<HttpResponse, SubscribeError> {
+    if let Some(retry_after) = is_rate_limited(&state.rate_limit, &request).await {
+        return Err(SubscribeError::RateLimited { retry_after });
+    }
+    let HouseholdFormData {
+        name,
+        emails,
+        consent,
+        other_fields,
+    } = form.0;
+    if state.require_consent.0 && !consent {
+        return Err(SubscribeError::ValidationError(
+            "You must consent to receive our newsletter.".into(),
+        ));
+    }
+    SubscriberName::parse(name.clone()).map_err(SubscribeError::ValidationError)?;
+    let mut emails: Vec<&str> = emails
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .collect();
+    // Each address gets a single confirmation email, however many times it has been listed.
+    let mut seen = std::collections::HashSet::new();
+    emails.retain(|e| seen.insert(e.to_lowercase()));
+    if emails.is_empty() {
+        return Err(SubscribeError::ValidationError(
+            "No email address has been provided.".into(),
+        ));
+    }
+    if emails.len() > MAX_HOUSEHOLD_SIZE {
+        return Err(SubscribeError::ValidationError(format!(
+            "A household can sign up at most {MAX_HOUSEHOLD_SIZE} email addresses at once."
+        )));
+    }
+    // Bots are told they subscribed, so that they do not try again.
+    if fills_in_honeypot(&other_fields, &state.honeypot_field) {
+        tracing::info!("The honeypot field was filled in. Ignoring the household signup.");
+        let results: Vec<_> = emails
+            .into_iter()
+            .map(|email| HouseholdMemberResult {
+                email: email.into(),
+                status: "pending_confirmation",
+                error: None,
+            })
+            .collect();
+        return Ok(HttpResponse::Ok().json(serde_json::json!({ "results": results })));
+    }
+
+    let double_opt_in = state
+        .feature_flags
+        .is_enabled_or_default(&pool, DOUBLE_OPT_IN)
+        .await;
+    let mut results = Vec::with_capacity(emails.len());
+    let mut pending = Vec::new();
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    for email in emails {
+        let new_subscriber = match SubscriberEmail::parse(email.into())
+            .and_then(|e| state.email_policy.check(&e).map(|_| e))
+        {
+            Ok(email) => NewSubscriber {
+                email,
+                name: SubscriberName::parse(name.clone())
+                    .map_err(SubscribeError::ValidationError)?,
+                locale: None,
+                time_zone: None,
+                source: None,
+                utm_campaign: None,
+            },
+            Err(e) => {
+                results.push(HouseholdMemberResult::error(email.into(), "invalid", e));
+                continue;
+            }
+        };
+        if !is_first_submission(
+            &state.duplicate_submissions,
+            &tenant,
+            &request,
+            &new_subscriber,
+        )
         .await
-        .context("Failed to insert new subscriber in the database.")?;
-    let subscription_token = generate_subscription_token();
-
-    // The `?` operator transparently invokes the `Into` trait on our behalf - we don't need an
-    // explicit `map_err` anymore.
-    store_token(&mut transaction, subscriber_id, &subscription_token)
+        {
+            results.push(HouseholdMemberResult::error(
+                email.into(),
+                "duplicate",
+                "This address has just been submitted.".into(),
+            ));
+            continue;
+        }
+        let stored = match store_subscription(
+            &mut transaction,
+            &tenant,
+            &new_subscriber,
+            consent,
+            state.max_per_domain.0,
+            double_opt_in,
+            "household_form",
+        )
         .await
-        .context("Failed to store the confirmation token for a new subscriber.")?;
-
+        {
+            Ok(stored) => stored,
+            Err(SubscribeError::ValidationError(e)) => {
+                results.push(HouseholdMemberResult::error(email.into(), "rejected", e));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        results.push(HouseholdMemberResult {
+            email: email.into(),
+            status: if stored.subscription_token.is_some() {
+                "pending_confirmation"
+            } else {
+                "confirmed"
+            },
+            error: None,
+        });
+        if let Some(subscription_token) = stored.subscription_token {
+            pending.push((
+                results.len() - 1,
+                new_subscriber,
+                stored.subscriber_id,
+                subscription_token,
+            ));
+        }
+    }
     transaction
         .commit()
         .await
-        .context("Failed to commit SQL transaction to store a new subscriber.")?;
+        .context("Failed to commit SQL transaction to store a household of subscribers.")?;
 
-    send_confirmation_email(
-        &email_client,
-        new_subscriber,
-        &base_url.as_ref().0,
-        &subscription_token,
-        &templates,
-    )
-    .await
-    .context("Failed to send a confirmation mail.")?;
+    // Submitting the same household again, once the double submit window is over, resends the
+    // confirmation emails that failed.
+    let links = ConfirmationEmailLinks {
+        base_url: tenant.base_url().unwrap_or(&state.base_url.0),
+        confirmation_path: &state.confirmation_path.0,
+        app_link_template: state.app_link_template.0.as_deref(),
+        hmac_secret: &state.hmac_secret.0,
+    };
+    for (index, new_subscriber, subscriber_id, subscription_token) in pending {
+        let outcome = match confirmation_email(
+            &email_client,
+            &links,
+            subscriber_id,
+            &subscription_token,
+            &templates,
+        ) {
+            Ok(email) => {
+                send_confirmation_email(
+                    &pool,
+                    &email_client,
+                    state.confirmation_retry_backoff,
+                    subscriber_id,
+                    &new_subscriber.email,
+                    email,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = outcome {
+            tracing::error!(error.cause_chain = ?e, error.message = %e,
+                "Failed to send a confirmation email to a household member.");
+            let result = &mut results[index];
+            result.status = "failed";
+            result.error = Some("Failed to send the confirmation email.".into());
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "results": results })))
+}
+
+/// Stores `new_subscriber`, unless they subscribed already to `tenant`. Returns their id, with the
+/// token to send them a confirmation email with if they still have to confirm their subscription.
+///
+/// New subscribers are rejected once their email domain has `max_per_domain` subscribers: existing
+/// ones can always ask for their confirmation email again.
+///
+/// Without `double_opt_in`, new subscribers are stored as confirmed right away. Subscribers still
+/// pending from before it was turned off are sent their confirmation email as usual.
+pub(crate) async fn store_subscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    tenant: &Tenant,
+    new_subscriber: &NewSubscriber,
+    consent: bool,
+    max_per_domain: Option<i64>,
+    double_opt_in: bool,
+    source: &str,
+) -> Result<StoredSubscription, SubscribeError> {
+    let existing_subscriber = get_existing_subscriber(transaction, tenant, new_subscriber)
+        .await
+        .context("Failed to look for an existing subscriber with the same email.")?;
+    let stored = match existing_subscriber {
+        None => {
+            if let Some(max_per_domain) = max_per_domain {
+                let domain = new_subscriber.email.domain();
+                let subscribers = count_subscribers_with_domain(transaction, tenant, domain)
+                    .await
+                    .context("Failed to count the subscribers sharing an email domain.")?;
+                if subscribers >= max_per_domain {
+                    return Err(SubscribeError::ValidationError(format!(
+                        "No more subscribers with a {domain} email address are accepted."
+                    )));
+                }
+            }
+            let status = if double_opt_in {
+                "pending_confirmation"
+            } else {
+                "confirmed"
+            };
+            let subscriber_id =
+                insert_subscriber(transaction, tenant, new_subscriber, consent, status)
+                    .await
+                    .context("Failed to insert new subscriber in the database.")?
+                    .ok_or_else(|| {
+                        SubscribeError::Conflict(format!(
+                            "{} is already being subscribed.",
+                            new_subscriber.email.as_ref()
+                        ))
+                    })?;
+            record_subscription_event(
+                &mut *transaction,
+                subscriber_id,
+                SubscriptionEventType::Subscribed,
+                source,
+            )
+            .await
+            .context("Failed to record the subscription event.")?;
+            if !double_opt_in {
+                record_subscription_event(
+                    &mut *transaction,
+                    subscriber_id,
+                    SubscriptionEventType::Confirmed,
+                    source,
+                )
+                .await
+                .context("Failed to record the confirmation event.")?;
+                return Ok(StoredSubscription {
+                    subscriber_id,
+                    is_new: true,
+                    subscription_token: None,
+                });
+            }
+            let subscription_token = generate_subscription_token();
+
+            // The `?` operator transparently invokes the `Into` trait on our behalf - we don't need an
+            // explicit `map_err` anymore.
+            store_token(transaction, subscriber_id, &subscription_token)
+                .await
+                .context("Failed to store the confirmation token for a new subscriber.")?;
+            StoredSubscription {
+                subscriber_id,
+                is_new: true,
+                subscription_token: Some(subscription_token),
+            }
+        }
+        Some(subscriber) if subscriber.status == "confirmed" => StoredSubscription {
+            subscriber_id: subscriber.id,
+            is_new: false,
+            subscription_token: None,
+        },
+        // We resend the confirmation email with the token we already issued: exactly one token is
+        // ever valid per pending subscriber, hence a resend cannot race with a confirmation using
+        // the token sent earlier.
+        //
+        // Subscribers who unsubscribed, or whose address bounced, are pending again: only a
+        // pending subscriber can be confirmed.
+        Some(subscriber) => {
+            if subscriber.status != "pending_confirmation" {
+                reset_to_pending_confirmation(transaction, subscriber.id)
+                    .await
+                    .context("Failed to move a returning subscriber back to pending.")?;
+                record_subscription_event(
+                    &mut *transaction,
+                    subscriber.id,
+                    SubscriptionEventType::Subscribed,
+                    source,
+                )
+                .await
+                .context("Failed to record the subscription event.")?;
+            }
+            let subscription_token = match get_token(transaction, subscriber.id)
+                .await
+                .context("Failed to retrieve the confirmation token of a pending subscriber.")?
+            {
+                Some(subscription_token) => subscription_token,
+                None => {
+                    let subscription_token = generate_subscription_token();
+                    store_token(transaction, subscriber.id, &subscription_token)
+                        .await
+                        .context(
+                            "Failed to store the confirmation token for a pending subscriber.",
+                        )?;
+                    subscription_token
+                }
+            };
+            StoredSubscription {
+                subscriber_id: subscriber.id,
+                is_new: false,
+                subscription_token: Some(subscription_token),
+            }
+        }
+    };
+
+    Ok(stored)
+}
+
+/// API clients asking for JSON get a JSON body, with the subscriber's `id` if this request stored
+/// them. Callers never learn the id of someone who subscribed already: anybody can submit their
+/// email address. Browsers are either redirected, if configured, or shown a page asking them to
+/// confirm their subscription - unless it is `confirmed` already.
+fn subscribe_success_response(
+    request: &HttpRequest,
+    success_redirect: &SubscribeSuccessRedirect,
+    templates: &Tera,
+    subscriber_id: Option<Uuid>,
+    confirmed: bool,
+) -> Result<HttpResponse, SubscribeError> {
+    let wants_json = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.contains("application/json"))
+        .unwrap_or(false);
+    if wants_json {
+        let status = if confirmed {
+            "confirmed"
+        } else {
+            "pending_confirmation"
+        };
+        let mut body = serde_json::json!({ "status": status });
+        if let Some(subscriber_id) = subscriber_id {
+            body["id"] = subscriber_id.to_string().into();
+        }
+        return Ok(HttpResponse::Ok().json(body));
+    }
+
+    if let Some(redirect) = &success_redirect.0 {
+        return Ok(HttpResponse::Found()
+            .insert_header((LOCATION, redirect.as_str()))
+            .finish());
+    }
+
+    let mut context = Context::new();
+    context.insert("confirmed", &confirmed);
+    let html_body = templates
+        .render("subscribe_success.html", &context)
+        .context("Failed to render the subscription success page.")?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
 
-    Ok(HttpResponse::Ok().finish())
+/// Where the links of a confirmation email point to.
+struct ConfirmationEmailLinks<'a> {
+    base_url: &'a str,
+    confirmation_path: &'a str,
+    app_link_template: Option<&'a str>,
+    // Signs the "manage your data" link.
+    hmac_secret: &'a Secret<String>,
 }
 
 /// # Database Transcations
@@ -254,35 +1015,99 @@ pub async fn subscribe(
 /// all-or-nothing operation, they also hide the effect of uncommitted changes from other queries that
 /// might be running, concurrently, against the same tables.
 #[tracing::instrument(
-    name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token, templates)
+    name = "Render a confirmation email",
+    skip(email_client, links, subscription_token, templates)
 )]
-async fn send_confirmation_email(
+fn confirmation_email(
     email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
-    base_url: &String,
+    links: &ConfirmationEmailLinks<'_>,
+    subscriber_id: Uuid,
     subscription_token: &str,
     templates: &Tera,
-) -> Result<(), SubscribeError> {
+) -> Result<ConfirmationEmail, SubscribeError> {
     // Build a confirmation link with a dynamic root
     let confirmation_link =
-        format!("{base_url}/subscriptions/confirm?subscription_token={subscription_token}");
+        ConfirmationLink::new(links.base_url, links.confirmation_path, subscription_token)
+            .map_err(anyhow::Error::msg)
+            .context("Failed to build the confirmation link.")?;
+
+    // Mobile apps can register a custom scheme to handle the confirmation themselves.
+    let app_link = links
+        .app_link_template
+        .map(|template| template.replace("{subscription_token}", subscription_token));
+
+    let manage_data_link = manage_data_link(links.base_url, links.hmac_secret, subscriber_id)
+        .map_err(anyhow::Error::msg)
+        .context("Failed to build the manage-data link.")?;
+
+    // Installs managing their templates on Postmark let it render the email.
+    if let Some(template_alias) = email_client.confirmation_template() {
+        let model = serde_json::json!({
+            "confirmation_link": confirmation_link.as_ref(),
+            "app_link": app_link,
+            "manage_data_link": manage_data_link,
+        });
+        return Ok(ConfirmationEmail::Template {
+            alias: template_alias.into(),
+            model,
+        });
+    }
 
     let mut template_context = Context::new();
-    template_context.insert("confirmation_link", &confirmation_link);
+    template_context.insert("confirmation_link", confirmation_link.as_ref());
+    template_context.insert("app_link", &app_link);
+    template_context.insert("manage_data_link", &manage_data_link);
     let html_body = templates
         .render("confirmation.html", &template_context)
         .context("Error rendering html email template.")?;
 
-    let plain_body = templates
+    let text_body = templates
         .render("confirmation.txt", &template_context)
         .context("Error rendering plain text email template.")?;
 
-    // We are ignoring email delivery errors for now.
-    email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body, &plain_body)
-        .await
-        .context("Error sending email")?;
+    Ok(ConfirmationEmail::Rendered {
+        subject: "Welcome!".into(),
+        html_body,
+        text_body,
+    })
+}
+
+/// If sending fails and `retry_backoff` is set, the email is queued for the delivery worker to
+/// retry - see `confirmation_retries`.
+#[tracing::instrument(
+    name = "Send a confirmation email to a new subscriber",
+    skip(pool, email_client, recipient, email)
+)]
+async fn send_confirmation_email(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    retry_backoff: Option<u64>,
+    subscriber_id: Uuid,
+    recipient: &SubscriberEmail,
+    email: ConfirmationEmail,
+) -> Result<(), SubscribeError> {
+    let e = match email.send(email_client, recipient).await {
+        Ok(()) => {
+            record_funnel_step(FunnelStep::ConfirmationSent, subscriber_id);
+            return Ok(());
+        }
+        Err(e) => e,
+    };
+    let backoff_seconds = match retry_backoff {
+        Some(backoff_seconds) => backoff_seconds,
+        None => return Err(anyhow::Error::new(e).context("Error sending email").into()),
+    };
+    tracing::warn!(error.cause_chain = ?e, error.message = %e,
+        "Failed to send a confirmation email. Queuing it for a retry.");
+    enqueue_confirmation_retry(
+        pool,
+        subscriber_id,
+        recipient.as_ref(),
+        &email,
+        backoff_seconds,
+    )
+    .await
+    .context("Failed to queue the confirmation email for a retry.")?;
 
     Ok(())
 }
@@ -300,24 +1125,116 @@ async fn send_confirmation_email(
 )]
 async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
+    tenant: &Tenant,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
+    consent: bool,
+    status: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
-    sqlx::query!(
+    let now = chrono::Utc::now();
+    // Email addresses are unique per tenant: `None` if this one subscribed to `tenant` in the
+    // meantime - the same address can still subscribe to other tenants.
+    let result = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions
+            (id, email, name, subscribed_at, status, consented_at, tenant_id, locale, source,
+             utm_campaign, time_zone)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        ON CONFLICT (tenant_id, email) DO NOTHING
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        chrono::Utc::now()
+        now,
+        status,
+        consent.then_some(now),
+        tenant.id(),
+        new_subscriber.locale.as_ref().map(AsRef::as_ref),
+        new_subscriber.source.as_ref().map(AsRef::as_ref),
+        new_subscriber.utm_campaign.as_ref().map(AsRef::as_ref),
+        new_subscriber.time_zone.as_ref().map(AsRef::as_ref)
     )
     .execute(transaction)
     // Using the `?` operator to return early if the function failed, returning a sqlx::Error
     .await?;
 
-    Ok(subscriber_id)
+    Ok((result.rows_affected() == 1).then_some(subscriber_id))
+}
+
+struct ExistingSubscriber {
+    id: Uuid,
+    status: String,
+}
+
+/// The row is locked until the end of the transaction: a concurrent confirmation waits for us to be
+/// done, and we see its outcome if it got there first.
+#[tracing::instrument(name = "Look for an existing subscriber", skip_all)]
+async fn get_existing_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    tenant: &Tenant,
+    new_subscriber: &NewSubscriber,
+) -> Result<Option<ExistingSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        ExistingSubscriber,
+        r#"SELECT id, status FROM subscriptions WHERE tenant_id = $1 AND email = $2 FOR UPDATE"#,
+        tenant.id(),
+        new_subscriber.email.as_ref(),
+    )
+    .fetch_optional(transaction)
+    .await
+}
+
+#[tracing::instrument(
+    name = "Move a subscriber back to pending confirmation",
+    skip(transaction)
+)]
+async fn reset_to_pending_confirmation(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'pending_confirmation' WHERE id = $1"#,
+        subscriber_id,
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Count the subscribers with an email domain", skip(transaction))]
+async fn count_subscribers_with_domain(
+    transaction: &mut Transaction<'_, Postgres>,
+    tenant: &Tenant,
+    domain: &str,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM subscriptions
+        WHERE tenant_id = $1
+            AND lower(split_part(email, '@', 2)) = lower($2)
+            AND status <> 'unsubscribed'
+        "#,
+        tenant.id(),
+        domain,
+    )
+    .fetch_one(transaction)
+    .await?;
+    Ok(result.count)
+}
+
+#[tracing::instrument(name = "Get the subscription token of a subscriber", skip_all)]
+async fn get_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT subscription_token FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id,
+    )
+    .fetch_optional(transaction)
+    .await?;
+
+    Ok(result.map(|r| r.subscription_token))
 }
 
 /// Generate a random 25-characters-long case-sensitive subscription token. This token should be α
@@ -0,0 +1,147 @@
+use crate::startup::SoftBounceThreshold;
+use crate::subscription_events::{record_subscription_event, SubscriptionEventType};
+use crate::tenant::Tenant;
+use crate::utils::e500;
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The subset of a Postmark webhook payload we care about - all events carry a `RecordType`
+/// (e.g. `Bounce`, `Delivery`, `SpamComplaint`). Bounces also carry the recipient's `Email` and
+/// their `Type` (e.g. `HardBounce`, `SoftBounce`).
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PostmarkEvent {
+    record_type: String,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default, rename = "Type")]
+    bounce_type: Option<String>,
+}
+
+#[tracing::instrument(
+    name = "Receive a Postmark webhook",
+    skip(event, pool, soft_bounce_threshold),
+    fields(record_type = %event.record_type)
+)]
+pub async fn postmark_webhook(
+    tenant: Tenant,
+    event: web::Json<PostmarkEvent>,
+    pool: web::Data<PgPool>,
+    soft_bounce_threshold: web::Data<SoftBounceThreshold>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let ("Bounce", Some(email)) = (event.record_type.as_str(), &event.email) {
+        record_bounce(
+            &pool,
+            &tenant,
+            email,
+            event.bounce_type.as_deref(),
+            soft_bounce_threshold.0,
+        )
+        .await
+        .context("Failed to record a bounce.")
+        .map_err(e500)?;
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Bounces for addresses we do not know about are ignored. Postmark calls the webhook of each
+/// tenant on its own host: a bounce only applies to the subscription of that tenant.
+///
+/// # Suppression
+/// A hard bounce (e.g. the mailbox does not exist) suppresses the subscriber right away - their
+/// status becomes `bounced`, hence they get no more newsletters. A soft bounce (e.g. the mailbox is
+/// full) may be transient: we only suppress the subscriber once they got `soft_bounce_threshold` of
+/// them. Other bounce types are recorded, nothing more. Subscribers who unsubscribed already are
+/// left as they are.
+///
+/// The counter, the status and the event are updated in a single transaction: concurrent bounces
+/// for the same address cannot lose a soft bounce, nor suppress on a stale count.
+#[tracing::instrument(skip(pool))]
+async fn record_bounce(
+    pool: &PgPool,
+    tenant: &Tenant,
+    email: &str,
+    bounce_type: Option<&str>,
+    soft_bounce_threshold: i32,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let subscriber_ids: Vec<Uuid> = match bounce_type {
+        Some("SoftBounce") => sqlx::query!(
+            r#"
+            UPDATE subscriptions
+            SET
+                soft_bounce_count = soft_bounce_count + 1,
+                status = CASE
+                    WHEN soft_bounce_count + 1 >= $3 AND status <> 'unsubscribed' THEN 'bounced'
+                    ELSE status
+                END
+            WHERE tenant_id = $1 AND email = $2
+            RETURNING id
+            "#,
+            tenant.id(),
+            email,
+            soft_bounce_threshold
+        )
+        .fetch_all(&mut transaction)
+        .await?
+        .into_iter()
+        .map(|r| r.id)
+        .collect(),
+        Some("HardBounce") => sqlx::query!(
+            r#"
+            UPDATE subscriptions
+            SET status = CASE WHEN status <> 'unsubscribed' THEN 'bounced' ELSE status END
+            WHERE tenant_id = $1 AND email = $2
+            RETURNING id
+            "#,
+            tenant.id(),
+            email
+        )
+        .fetch_all(&mut transaction)
+        .await?
+        .into_iter()
+        .map(|r| r.id)
+        .collect(),
+        _ => sqlx::query!(
+            "SELECT id FROM subscriptions WHERE tenant_id = $1 AND email = $2",
+            tenant.id(),
+            email
+        )
+        .fetch_all(&mut transaction)
+        .await?
+        .into_iter()
+        .map(|r| r.id)
+        .collect(),
+    };
+    for subscriber_id in subscriber_ids {
+        record_subscription_event(
+            &mut transaction,
+            subscriber_id,
+            SubscriptionEventType::Bounced,
+            "postmark_webhook",
+        )
+        .await?;
+    }
+    transaction.commit().await
+}
+
+/// Webhook payloads must be JSON and no bigger than `max_body_bytes`.
+///
+/// `actix-web` already answers with a `413` when the payload exceeds the limit, but it uses a
+/// generic `400` when the `Content-Type` is wrong - we want a `415 Unsupported Media Type` instead.
+pub fn webhook_json_config(max_body_bytes: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(max_body_bytes)
+        .content_type_required(true)
+        .error_handler(|e, _req| {
+            let status_code = match e {
+                JsonPayloadError::ContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                _ => e.status_code(),
+            };
+            InternalError::new(e, status_code).into()
+        })
+}
@@ -0,0 +1,13 @@
+use crate::startup::HoneypotField;
+use actix_web::{web, HttpResponse};
+
+/// Third-party pages embed our subscription form with this script.
+pub async fn widget(honeypot_field: web::Data<HoneypotField>) -> HttpResponse {
+    let script = include_str!("widget.js").replace(
+        "{{honeypot_field}}",
+        honeypot_field.0.as_deref().unwrap_or_default(),
+    );
+    HttpResponse::Ok()
+        .content_type("application/javascript; charset=utf-8")
+        .body(script)
+}
@@ -0,0 +1,22 @@
+// Embeddable subscription form. Add it to any page with:
+//
+//   <div data-zero2prod-subscribe></div>
+//   <script src="https://<our domain>/widget.js"></script>
+(function () {
+    var origin = new URL(document.currentScript.src).origin;
+    // Empty if the form has no honeypot field.
+    var honeypot = "{{honeypot_field}}";
+    document.querySelectorAll("[data-zero2prod-subscribe]").forEach(function (container) {
+        var form = document.createElement("form");
+        form.action = origin + "/subscriptions";
+        form.method = "post";
+        form.innerHTML =
+            '<input type="text" name="name" placeholder="Your name" required>' +
+            '<input type="email" name="email" placeholder="Your email" required>' +
+            (honeypot
+                ? '<input type="text" name="' + honeypot + '" style="display:none" tabindex="-1" autocomplete="off">'
+                : '') +
+            '<button type="submit">Subscribe</button>';
+        container.appendChild(form);
+    });
+})();
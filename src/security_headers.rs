@@ -0,0 +1,57 @@
+use crate::configuration::SecurityHeadersSettings;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{
+    HeaderValue, CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use actix_web::web;
+use actix_web_lab::middleware::Next;
+
+/// The headers set on the login and admin pages, parsed once when the application starts.
+#[derive(Debug)]
+pub struct SecurityHeaders {
+    content_security_policy: HeaderValue,
+    referrer_policy: HeaderValue,
+}
+
+impl SecurityHeaders {
+    pub fn new(settings: &SecurityHeadersSettings) -> Result<Self, String> {
+        let parse = |name: &str, value: &str| {
+            HeaderValue::from_str(value).map_err(|_| format!("{value} is not a valid {name}."))
+        };
+        Ok(Self {
+            content_security_policy: parse(
+                "Content-Security-Policy",
+                &settings.content_security_policy,
+            )?,
+            referrer_policy: parse("Referrer-Policy", &settings.referrer_policy)?,
+        })
+    }
+}
+
+/// # Security headers
+/// The login and admin pages must not be framed by other sites (clickjacking), nor load anything
+/// the `Content-Security-Policy` does not allow. Browsers must not second-guess their content type,
+/// and only send as much of their URL to other sites as the `Referrer-Policy` allows.
+pub async fn set_security_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let path = req.path();
+    let is_protected = path == "/login" || path == "/admin" || path.starts_with("/admin/");
+
+    let mut response = next.call(req).await?;
+    let headers = match response.request().app_data::<web::Data<SecurityHeaders>>() {
+        Some(headers) if is_protected => headers.clone(),
+        _ => return Ok(response),
+    };
+    let response_headers = response.headers_mut();
+    response_headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    response_headers.insert(
+        CONTENT_SECURITY_POLICY,
+        headers.content_security_policy.clone(),
+    );
+    response_headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    response_headers.insert(REFERRER_POLICY, headers.referrer_policy.clone());
+    Ok(response)
+}
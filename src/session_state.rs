@@ -1,13 +1,22 @@
+use actix_session::storage::{LoadError, SaveError, UpdateError};
 use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
-use actix_web::dev::Payload;
-use actix_web::{FromRequest, HttpRequest};
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::{ContentType, RETRY_AFTER};
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use actix_web_lab::middleware::Next;
 use std::future::{ready, Ready};
+use tera::{Context, Tera};
 use uuid::Uuid;
 
 pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+    const LAST_SEEN_KEY: &'static str = "last_seen";
+    const LOGIN_REDIRECT_KEY: &'static str = "login_redirect";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -21,6 +30,35 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    /// Timestamps are stored as seconds since the Unix epoch.
+    pub fn insert_logged_in_at(&self, timestamp: i64) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LOGGED_IN_AT_KEY, timestamp)
+    }
+
+    pub fn get_logged_in_at(&self) -> Result<Option<i64>, SessionGetError> {
+        self.0.get(Self::LOGGED_IN_AT_KEY)
+    }
+
+    pub fn insert_last_seen(&self, timestamp: i64) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LAST_SEEN_KEY, timestamp)
+    }
+
+    pub fn get_last_seen(&self) -> Result<Option<i64>, SessionGetError> {
+        self.0.get(Self::LAST_SEEN_KEY)
+    }
+
+    /// The page an anonymous user was trying to reach before being asked to log in.
+    pub fn insert_login_redirect(&self, target: &str) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LOGIN_REDIRECT_KEY, target)
+    }
+
+    /// The redirect target is consumed: it is only honoured once.
+    pub fn take_login_redirect(&self) -> Option<String> {
+        self.0
+            .remove_as(Self::LOGIN_REDIRECT_KEY)
+            .and_then(Result::ok)
+    }
+
     pub fn log_out(self) {
         self.0.purge()
     }
@@ -42,3 +80,43 @@ impl FromRequest for TypedSession {
         ready(Ok(TypedSession(req.get_session())))
     }
 }
+
+/// How long clients should wait before retrying when the session store is unavailable, in seconds.
+const SESSION_STORE_RETRY_AFTER: u32 = 30;
+
+/// # Session Store Outages
+/// `SessionMiddleware` fails the request with an opaque 500 if it cannot load or persist the session
+/// state - e.g. because Redis is down. This middleware must wrap `SessionMiddleware`: it turns those
+/// failures into a 503 with a `Retry-After` header, so that an outage is neither reported as a bug
+/// nor mistaken for the user not being logged in.
+pub async fn handle_session_store_outages(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // Not the request itself: routing needs the only reference to it.
+    let templates = req.app_data::<web::Data<Tera>>().cloned();
+    let e = match next.call(req).await {
+        Ok(response) => return Ok(response),
+        Err(e) => e,
+    };
+    let is_session_store_error = e.as_error::<InternalError<LoadError>>().is_some()
+        || e.as_error::<InternalError<SaveError>>().is_some()
+        || e.as_error::<InternalError<UpdateError>>().is_some();
+    if !is_session_store_error {
+        return Err(e);
+    }
+
+    tracing::warn!(error.message = %e, "The session store is unavailable.");
+    let html_body = templates
+        .and_then(|templates| {
+            templates
+                .render("service_unavailable.html", &Context::new())
+                .ok()
+        })
+        .unwrap_or_else(|| "Service temporarily unavailable.".into());
+    let response = HttpResponse::ServiceUnavailable()
+        .insert_header((RETRY_AFTER, SESSION_STORE_RETRY_AFTER))
+        .content_type(ContentType::html())
+        .body(html_body);
+    Err(InternalError::from_response(e, response).into())
+}
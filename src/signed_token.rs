@@ -0,0 +1,196 @@
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Tokens in "manage your data" links.
+pub const MANAGE_DATA_SCOPE: &str = "manage_data";
+/// How long a "manage your data" link keeps working after we have emailed it.
+const MANAGE_DATA_LINK_VALIDITY_DAYS: i64 = 90;
+/// Tokens in the unsubscribe links of newsletter issues.
+pub const UNSUBSCRIBE_SCOPE: &str = "unsubscribe";
+/// Unsubscribing must keep working long after an issue has gone out.
+const UNSUBSCRIBE_LINK_VALIDITY_DAYS: i64 = 365;
+
+/// # Signed Tokens
+/// Some links we email to subscribers identify them without us storing anything: the token is the
+/// subscriber id and an expiry (a unix timestamp) followed by an HMAC-SHA256 tag - keyed with our
+/// `hmac_secret` - over the id, the expiry and a `scope`. A token is only valid for the scope it
+/// was signed for, e.g. a "manage your data" token cannot be used anywhere else, and only until
+/// `expires_at`: emails get forwarded and archived, so a leaked link must not work forever.
+pub fn sign_subscriber_token(
+    hmac_secret: &Secret<String>,
+    scope: &str,
+    subscriber_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> String {
+    let expires_at = expires_at.timestamp();
+    let tag = mac(hmac_secret, scope, subscriber_id, expires_at)
+        .finalize()
+        .into_bytes();
+    format!(
+        "{subscriber_id}.{expires_at}.{}",
+        base64::encode_config(tag, base64::URL_SAFE_NO_PAD)
+    )
+}
+
+/// The subscriber id carried by `token`, if it has been signed by us for `scope` and has not
+/// expired yet.
+pub fn verify_subscriber_token(
+    hmac_secret: &Secret<String>,
+    scope: &str,
+    token: &str,
+) -> Option<Uuid> {
+    let mut parts = token.splitn(3, '.');
+    let subscriber_id = Uuid::parse_str(parts.next()?).ok()?;
+    let expires_at: i64 = parts.next()?.parse().ok()?;
+    let tag = base64::decode_config(parts.next()?, base64::URL_SAFE_NO_PAD).ok()?;
+    // `verify_slice` compares in constant time.
+    mac(hmac_secret, scope, subscriber_id, expires_at)
+        .verify_slice(&tag)
+        .ok()?;
+    (Utc::now().timestamp() < expires_at).then_some(subscriber_id)
+}
+
+fn mac(
+    hmac_secret: &Secret<String>,
+    scope: &str,
+    subscriber_id: Uuid,
+    expires_at: i64,
+) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(scope.as_bytes());
+    mac.update(b":");
+    mac.update(subscriber_id.as_bytes());
+    mac.update(b":");
+    mac.update(&expires_at.to_be_bytes());
+    mac
+}
+
+/// The link to the page where subscribers see the data we hold on them, on `base_url` - the base
+/// URL of the tenant they subscribed to. It expires after `MANAGE_DATA_LINK_VALIDITY_DAYS`.
+pub fn manage_data_link(
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+    subscriber_id: Uuid,
+) -> Result<String, String> {
+    signed_link(
+        base_url,
+        "/subscriptions/preferences",
+        "token",
+        &sign_subscriber_token(
+            hmac_secret,
+            MANAGE_DATA_SCOPE,
+            subscriber_id,
+            Utc::now() + Duration::days(MANAGE_DATA_LINK_VALIDITY_DAYS),
+        ),
+    )
+}
+
+/// The link unsubscribing the recipient of a newsletter issue, on `base_url` - the base URL of the
+/// tenant they subscribed to. Following it shows a confirmation form, while mail clients `POST` to
+/// it for a one-click unsubscribe (RFC 8058). It expires after `UNSUBSCRIBE_LINK_VALIDITY_DAYS`.
+pub fn unsubscribe_link(
+    base_url: &str,
+    hmac_secret: &Secret<String>,
+    subscriber_id: Uuid,
+) -> Result<String, String> {
+    signed_link(
+        base_url,
+        "/subscriptions/unsubscribe",
+        "subscription_token",
+        &sign_subscriber_token(
+            hmac_secret,
+            UNSUBSCRIBE_SCOPE,
+            subscriber_id,
+            Utc::now() + Duration::days(UNSUBSCRIBE_LINK_VALIDITY_DAYS),
+        ),
+    )
+}
+
+fn signed_link(base_url: &str, path: &str, parameter: &str, token: &str) -> Result<String, String> {
+    let mut url =
+        Url::parse(base_url).map_err(|e| format!("{base_url} is not a valid base URL: {e}"))?;
+    let path = format!("{}{path}", url.path().trim_end_matches('/'));
+    url.set_path(&path);
+    url.query_pairs_mut().clear().append_pair(parameter, token);
+    Ok(url.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret() -> Secret<String> {
+        Secret::new("super-secret".into())
+    }
+
+    fn tomorrow() -> DateTime<Utc> {
+        Utc::now() + Duration::days(1)
+    }
+
+    #[test]
+    fn a_token_is_valid_for_the_scope_it_was_signed_for() {
+        let subscriber_id = Uuid::new_v4();
+        let token = sign_subscriber_token(&secret(), MANAGE_DATA_SCOPE, subscriber_id, tomorrow());
+
+        assert_eq!(
+            verify_subscriber_token(&secret(), MANAGE_DATA_SCOPE, &token),
+            Some(subscriber_id)
+        );
+        assert_eq!(verify_subscriber_token(&secret(), "other", &token), None);
+    }
+
+    #[test]
+    fn a_tampered_token_is_rejected() {
+        let token = sign_subscriber_token(&secret(), MANAGE_DATA_SCOPE, Uuid::new_v4(), tomorrow());
+        let (_, expiry_and_tag) = token.split_once('.').unwrap();
+        let tampered = format!("{}.{expiry_and_tag}", Uuid::new_v4());
+
+        assert_eq!(
+            verify_subscriber_token(&secret(), MANAGE_DATA_SCOPE, &tampered),
+            None
+        );
+        let other_secret = Secret::new("another-secret".into());
+        assert_eq!(
+            verify_subscriber_token(&other_secret, MANAGE_DATA_SCOPE, &token),
+            None
+        );
+    }
+
+    #[test]
+    fn an_expired_token_is_rejected() {
+        let token = sign_subscriber_token(
+            &secret(),
+            MANAGE_DATA_SCOPE,
+            Uuid::new_v4(),
+            Utc::now() - Duration::seconds(1),
+        );
+
+        assert_eq!(
+            verify_subscriber_token(&secret(), MANAGE_DATA_SCOPE, &token),
+            None
+        );
+    }
+
+    #[test]
+    fn extending_the_expiry_of_a_token_invalidates_it() {
+        let subscriber_id = Uuid::new_v4();
+        let token = sign_subscriber_token(
+            &secret(),
+            MANAGE_DATA_SCOPE,
+            subscriber_id,
+            Utc::now() - Duration::seconds(1),
+        );
+        let tag = token.rsplit_once('.').unwrap().1;
+        let extended = format!("{subscriber_id}.{}.{tag}", tomorrow().timestamp());
+
+        assert_eq!(
+            verify_subscriber_token(&secret(), MANAGE_DATA_SCOPE, &extended),
+            None
+        );
+    }
+}
@@ -1,23 +1,84 @@
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::authentication::{
+    reject_anonymous_users, reject_invalid_api_keys, reject_unauthenticated_webhooks,
+    reject_unauthorized_auditors, AuditApiKeys, PartnerApiKeys, WebhookCredentials,
+};
+use crate::cache_control::set_cache_control;
+use crate::canonical_url::{redirect_to_canonical_url, CanonicalUrl};
+use crate::client_ip::TrustedProxies;
+use crate::compression::{
+    skip_compressing_small_responses, strip_identity_encoding, CompressionThreshold,
+};
+use crate::configuration::{
+    DatabaseSettings, EmailClientSettings, SenderVerification, Settings, WelcomeStep,
+};
+use crate::domain::{ConfirmationLink, SubscriberEmailPolicy};
+use crate::duplicate_submissions::DuplicateSubmissions;
+use crate::feature_flags::FeatureFlags;
+use crate::ip_allowlist::{reject_disallowed_networks, AdminAllowedNetworks};
+use crate::load_shedding::{shed_load, InFlightRequestLimit};
+use crate::security_headers::{set_security_headers, SecurityHeaders};
+use crate::session_state::handle_session_store_outages;
+use crate::subscribe_rate_limit::SubscribeRateLimit;
+use crate::subscriber_repository::{PostgresSubscriberRepository, SubscriberRepository};
+use crate::tenant::TenantHosts;
+use crate::welcome_series::validate_welcome_series;
+use crate::worker_pause::WorkerPause;
 use crate::{email_client::EmailClient, routes};
+use actix_session::config::PersistentSession;
 use actix_session::{storage::RedisSessionStore, SessionMiddleware};
-use actix_web::{cookie::Key, dev::Server, web, web::Data, App, HttpServer};
+use actix_web::dev::{Server, ServerHandle};
+use actix_web::middleware::{Compress, Condition};
+use actix_web::{cookie::Key, web, web::Data, App, HttpServer};
 use actix_web_flash_messages::{storage::CookieMessageStore, FlashMessagesFramework};
 use actix_web_lab::middleware::from_fn;
-use once_cell::sync::Lazy;
+use anyhow::Context;
 use secrecy::{ExposeSecret, Secret};
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{Connection, PgConnection, PgPool};
 use std::net::TcpListener;
+use std::sync::Arc;
 use tera::Tera;
 use tracing_actix_web::TracingLogger;
 
 pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
     PgPoolOptions::new()
         .acquire_timeout(std::time::Duration::from_secs(2))
+        .min_connections(configuration.min_connections)
         .connect_lazy_with(configuration.with_db())
 }
 
+/// Opens `connections` connections at once, then hands them back to the pool: the first requests
+/// do not pay for connecting, and we find out right away if Postgres cannot be reached.
+pub async fn warm_up_pool(pool: &PgPool, connections: u32) -> Result<(), sqlx::Error> {
+    let mut acquired = Vec::new();
+    for _ in 0..connections.max(1) {
+        acquired.push(pool.acquire().await?);
+    }
+    Ok(())
+}
+
+/// Connects to Postgres, retrying with an exponential backoff as configured in `configuration`.
+pub async fn connect_with_retry(
+    configuration: &DatabaseSettings,
+    options: &PgConnectOptions,
+) -> Result<PgConnection, sqlx::Error> {
+    let mut backoff = configuration.connect_backoff();
+    let mut attempt = 0;
+    loop {
+        match PgConnection::connect_with(options).await {
+            Ok(connection) => return Ok(connection),
+            Err(e) if attempt < configuration.connect_retries => {
+                tracing::warn!(error.cause_chain = ?e, error.message = %e, attempt,
+                    "Failed to connect to Postgres. Retrying in {backoff:?}.");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 pub struct Application {
     port: u16,
     server: Server,
@@ -32,10 +93,88 @@ pub struct HmacSecret(pub Secret<String>);
 #[derive(Debug)]
 pub struct ApplicationBaseUrl(pub String);
 
+/// Where browsers are sent after subscribing - they get a success page if `None`.
+#[derive(Debug)]
+pub struct SubscribeSuccessRedirect(pub Option<String>);
+
+/// Where admins are redirected to after logging out.
+#[derive(Debug)]
+pub struct LogoutRedirect(pub String);
+
+/// Where confirmation links point to, relative to `ApplicationBaseUrl`.
+#[derive(Debug)]
+pub struct ConfirmationPath(pub String);
+
+/// Template for the mobile app deep-link included in confirmation emails, if any.
+#[derive(Debug)]
+pub struct AppLinkTemplate(pub Option<String>);
+
+/// Whether subscribers must explicitly consent to receive our newsletter.
+#[derive(Debug)]
+pub struct RequireConsent(pub bool);
+
+/// How many soft bounces a subscriber can get before being suppressed.
+#[derive(Debug)]
+pub struct SoftBounceThreshold(pub i32);
+
+/// What subscribers get once they have confirmed: a welcome email, if `send_welcome_email` is set,
+/// and the emails of the welcome series - see `welcome_series`.
+#[derive(Debug)]
+pub struct WelcomeEmails {
+    pub send_welcome_email: bool,
+    pub series: Vec<WelcomeStep>,
+}
+
+/// How many subscribers an email domain can have, if capped.
+#[derive(Debug)]
+pub struct MaxSubscribersPerDomain(pub Option<i64>);
+
+/// The name of the hidden subscription form field that only bots fill in, if any.
+#[derive(Debug)]
+pub struct HoneypotField(pub Option<String>);
+
+/// # Subscribe State
+/// Everything the subscription handlers need besides the request, the connection pool, the email
+/// client and the templates, bundled in a single extractor: one extractor per setting would take
+/// `subscribe` well past the number of extractors actix-web accepts.
+pub struct SubscribeState {
+    pub repository: Arc<dyn SubscriberRepository>,
+    pub hmac_secret: HmacSecret,
+    // How long to wait before retrying a confirmation email that failed to send, in seconds -
+    // `None` if they are not retried.
+    pub confirmation_retry_backoff: Option<u64>,
+    pub duplicate_submissions: DuplicateSubmissions,
+    pub rate_limit: SubscribeRateLimit,
+    pub feature_flags: Data<FeatureFlags>,
+    pub base_url: ApplicationBaseUrl,
+    pub success_redirect: SubscribeSuccessRedirect,
+    pub confirmation_path: ConfirmationPath,
+    pub app_link_template: AppLinkTemplate,
+    pub require_consent: RequireConsent,
+    pub email_policy: SubscriberEmailPolicy,
+    pub max_per_domain: MaxSubscribersPerDomain,
+    pub honeypot_field: HoneypotField,
+}
+
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
+        // The pool connects lazily: we wait for Postgres to be reachable, but we start anyway if it
+        // is not - requests that need the database will fail until it comes back. Unless the pool
+        // is to be warmed up, in which case we do not start at all.
+        if let Err(e) =
+            connect_with_retry(&configuration.database, &configuration.database.with_db()).await
+        {
+            tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                "Postgres is not reachable. Starting anyway.");
+        }
         let connection_pool = get_connection_pool(&configuration.database);
-        let email_client = configuration.email_client.client();
+        if configuration.database.warm_up_pool {
+            warm_up_pool(&connection_pool, configuration.database.min_connections)
+                .await
+                .context("Failed to warm up the connection pool.")?;
+        }
+        let email_client = configuration.email_client.clone().client();
+        verify_sender(&configuration.email_client, &email_client).await?;
 
         let address = format!(
             "{}:{}",
@@ -45,15 +184,7 @@ impl Application {
         let listener = TcpListener::bind(&address)?;
         //Retrieve the port assigned to us by the OS
         let port = listener.local_addr().unwrap().port();
-        let server = run(
-            listener,
-            connection_pool,
-            email_client,
-            configuration.application.base_url,
-            HmacSecret(configuration.application.hmac_secret),
-            configuration.redis_uri,
-        )
-        .await?;
+        let server = run(listener, connection_pool, email_client, configuration).await?;
 
         // We "save" the bound port in one of `Application`'s fields.
         Ok(Self { port, server })
@@ -63,6 +194,11 @@ impl Application {
         self.port
     }
 
+    /// To stop the server from outside of `run_until_stopped`, e.g. in tests.
+    pub fn handle(&self) -> ServerHandle {
+        self.server.handle()
+    }
+
     /// A more expressive name that makes it clear that this function only returns when the application
     /// is stopped.
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
@@ -70,6 +206,45 @@ impl Application {
     }
 }
 
+/// Misconfigured sender addresses cause silent deliverability failures: Postmark rejects every email
+/// sent from an address that is not a confirmed sender signature.
+async fn verify_sender(
+    settings: &EmailClientSettings,
+    email_client: &EmailClient,
+) -> Result<(), anyhow::Error> {
+    if settings.verify_sender_on_startup == SenderVerification::Disabled {
+        return Ok(());
+    }
+
+    let outcome = match &settings.account_token {
+        Some(account_token) => email_client
+            .is_sender_verified(account_token)
+            .await
+            .context("Failed to retrieve the sender signatures from Postmark."),
+        None => Err(anyhow::anyhow!(
+            "Sender verification requires `email_client.account_token` to be set."
+        )),
+    };
+
+    let e = match outcome {
+        Ok(true) => return Ok(()),
+        Ok(false) => anyhow::anyhow!(
+            "{} is not a confirmed sender signature on Postmark.",
+            email_client.sender()
+        ),
+        Err(e) => e,
+    };
+
+    match settings.verify_sender_on_startup {
+        SenderVerification::Fail => Err(e),
+        _ => {
+            tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                "Failed to verify the sender address. Emails might not be delivered.");
+            Ok(())
+        }
+    }
+}
+
 /// # Observability
 ///
 /// The only thing we can rely on to understand and debug an unknown unknown is **telemetry data**:
@@ -89,7 +264,7 @@ impl Application {
 /// In a nutshell, to build an observable system we need:
 /// * to instrument our application to collect high-quality telemetry data;
 /// * access to tools and systems to efficiently slice, dice and manipulate the data to find answers
-/// to our questions.
+///   to our questions.
 ///
 /// # Logging
 /// Logs are the most common type of telemetry data. The go-to crate for logging in Rust is `log`.
@@ -106,79 +281,326 @@ impl Application {
 /// a *local* decision: it is enough to look at the function to decide what deserves to be captured
 /// in a log record. This enables libraries to be instrumented effectively, extending the reach of our
 /// telemetry outside the boundaries of the code we have written first-hand.
+///
+/// `run` only picks the values it needs out of `configuration` - the expensive resources (the
+/// listener, the connection pool and the email client) are built by the caller.
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    hmac_secret: HmacSecret,
-    redis_uri: Secret<String>,
+    configuration: Settings,
 ) -> Result<Server, anyhow::Error> {
+    let feature_flags = Data::new(FeatureFlags::new(configuration.feature_flag_defaults()));
+    let hmac_secret = HmacSecret(configuration.application.hmac_secret.clone());
+    let redis_uri = configuration.redis_uri;
+    let webhook_max_body_bytes = configuration.webhooks.max_body_bytes;
+    let soft_bounce_threshold = Data::new(SoftBounceThreshold(
+        configuration.webhooks.soft_bounce_threshold,
+    ));
+    let webhook_credentials = Data::new(WebhookCredentials::new(
+        configuration.webhooks.basic_auth.as_ref(),
+    ));
+    let session_settings = configuration.session;
+    let shutdown_timeout = configuration.application.shutdown_timeout_seconds;
+    let compression_threshold = Data::new(CompressionThreshold(
+        configuration.application.compression_min_bytes,
+    ));
+    let newsletter_settings = configuration.newsletter;
+    let idempotency_settings = Data::new(configuration.idempotency);
+    let cache_control_settings = configuration.cache_control;
+    let partner_api_keys = Data::new(PartnerApiKeys::new(&configuration.api.partner_api_keys));
+    let audit_api_keys = Data::new(AuditApiKeys::new(&configuration.admin.audit_api_keys));
+    let api_settings = configuration.api;
+    let deliverability_settings = Data::new(configuration.deliverability);
+    let list_cleaning_settings = Data::new(configuration.list_cleaning);
+    let subscribe_redirect = configuration
+        .subscriptions
+        .success_redirect()
+        .map_err(anyhow::Error::msg)?;
+    let logout_redirect = Data::new(LogoutRedirect(
+        session_settings
+            .logout_redirect()
+            .map_err(anyhow::Error::msg)?,
+    ));
+    let trusted_proxies = configuration
+        .application
+        .trusted_proxies()
+        .map_err(anyhow::Error::msg)?;
+    let admin_allowed_networks = configuration
+        .admin
+        .allowed_networks()
+        .map_err(anyhow::Error::msg)?;
+    let tenant_hosts =
+        Data::new(TenantHosts::new(&configuration.tenants).map_err(anyhow::Error::msg)?);
+    let security_headers = Data::new(
+        SecurityHeaders::new(&configuration.security_headers).map_err(anyhow::Error::msg)?,
+    );
+
+    // Handlers only see the repository's trait: they can be tested against other implementations.
+    let subscriber_repository: Arc<dyn SubscriberRepository> =
+        Arc::new(PostgresSubscriberRepository::new(db_pool.clone()));
     // Wrap the connection in a smart pointer
     let db_pool = web::Data::new(db_pool);
     let email_client = web::Data::new(email_client);
-    let base_url = Data::new(ApplicationBaseUrl(base_url));
-    let templates = Data::new(Lazy::force(&TEMPLATES));
+    // Fail fast on a base URL or a confirmation path we cannot build links with.
+    let tenant_base_urls = configuration.tenants.iter().map(|t| &t.base_url);
+    for base_url in std::iter::once(&configuration.application.base_url).chain(tenant_base_urls) {
+        ConfirmationLink::new(base_url, &configuration.subscriptions.confirmation_path, "")
+            .map_err(anyhow::Error::msg)?;
+    }
+    let canonical_url = Data::new(
+        CanonicalUrl::new(&configuration.application.base_url).map_err(anyhow::Error::msg)?,
+    );
+    let enforce_canonical_url = configuration.application.enforce_canonical_url;
+    let subscribe_redirect = subscribe_redirect.map(String::from);
+    // `validate` and the widget need them too.
+    let email_policy = Data::new(configuration.subscriptions.email_policy());
+    let honeypot_field = Data::new(HoneypotField(
+        configuration.subscriptions.honeypot_field.clone(),
+    ));
+    let subscribe_state = Data::new(SubscribeState {
+        repository: subscriber_repository.clone(),
+        hmac_secret: hmac_secret.clone(),
+        confirmation_retry_backoff: (configuration.subscriptions.confirmation_retries > 0)
+            .then_some(
+                configuration
+                    .subscriptions
+                    .confirmation_retry_backoff_seconds,
+            ),
+        duplicate_submissions: DuplicateSubmissions::new(
+            &redis_uri,
+            configuration.subscriptions.duplicate_key_prefix,
+            configuration.subscriptions.duplicate_window_seconds,
+        )?,
+        rate_limit: SubscribeRateLimit::new(
+            &redis_uri,
+            configuration.subscriptions.rate_limit_key_prefix,
+            configuration.subscriptions.rate_limit_max_requests,
+            configuration.subscriptions.rate_limit_window_seconds,
+        )?,
+        feature_flags: feature_flags.clone(),
+        base_url: ApplicationBaseUrl(configuration.application.base_url),
+        success_redirect: SubscribeSuccessRedirect(subscribe_redirect),
+        confirmation_path: ConfirmationPath(configuration.subscriptions.confirmation_path),
+        app_link_template: AppLinkTemplate(configuration.subscriptions.app_link_template),
+        require_consent: RequireConsent(configuration.subscriptions.require_consent),
+        email_policy: *email_policy.get_ref(),
+        max_per_domain: MaxSubscribersPerDomain(configuration.subscriptions.max_per_domain),
+        honeypot_field: HoneypotField(configuration.subscriptions.honeypot_field),
+    });
+    let subscriber_repository: Data<dyn SubscriberRepository> = Data::from(subscriber_repository);
+    let welcome_emails = Data::new(WelcomeEmails {
+        send_welcome_email: configuration.subscriptions.send_welcome_email,
+        series: configuration.subscriptions.welcome_series,
+    });
+    let in_flight_request_limit = Data::new(InFlightRequestLimit::new(
+        configuration.application.max_in_flight_requests,
+    ));
+    let trusted_proxies = Data::new(TrustedProxies(trusted_proxies));
+    let admin_allowed_networks = Data::new(AdminAllowedNetworks(admin_allowed_networks));
+    let templates = Data::new(load_templates(&configuration.application.templates_dir)?);
+    validate_welcome_series(&templates, &welcome_emails.series)?;
     let message_store =
         CookieMessageStore::builder(Key::from(hmac_secret.0.expose_secret().as_bytes())).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
     let secret_key = Key::from(hmac_secret.0.expose_secret().as_bytes());
     let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let worker_pause = Data::new(WorkerPause::new(
+        &redis_uri,
+        newsletter_settings.worker_pause_key.clone(),
+    )?);
 
     let server = HttpServer::new(move || {
         App::new()
             // Middlewares are added using the `wrap` method on `App`
+            // Innermost: it needs the session, which `SessionMiddleware` loads.
+            .wrap(from_fn(set_cache_control))
+            .wrap(from_fn(set_security_headers))
+            // `Compress` must be wrapped by both of these - see `compression`.
+            .wrap(from_fn(skip_compressing_small_responses))
+            .wrap(Compress::default())
+            .wrap(from_fn(strip_identity_encoding))
             .wrap(message_framework.clone())
             // Instead of `Logger::default`
             .wrap(TracingLogger::default())
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
+            .wrap(
+                SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                    .session_lifecycle(
+                        PersistentSession::default().session_ttl(session_settings.ttl()),
+                    )
+                    .build(),
+            )
+            // Must be registered after `SessionMiddleware`, to wrap it.
+            .wrap(from_fn(handle_session_store_outages))
+            // Redirected requests do not need a session.
+            .wrap(Condition::new(
+                enforce_canonical_url,
+                from_fn(redirect_to_canonical_url),
             ))
+            // Outermost: requests we shed should not cost us anything else.
+            .wrap(from_fn(shed_load))
             .route("/", web::get().to(routes::home))
             .route("/login", web::get().to(routes::login_form))
             .route("/login", web::post().to(routes::login))
             .route("/health_check", web::get().to(routes::health_check))
+            .route("/widget.js", web::get().to(routes::widget))
             .route("/newsletters", web::post().to(routes::publish_newsletter))
             .route("/subscriptions", web::post().to(routes::subscribe))
+            .route(
+                "/subscriptions/validate",
+                web::post().to(routes::validate_subscription),
+            )
+            .route(
+                "/subscriptions/household",
+                web::post().to(routes::subscribe_household),
+            )
             .route("/subscriptions/confirm", web::get().to(routes::confirm))
+            .route(
+                "/subscriptions/preferences",
+                web::get().to(routes::subscription_preferences),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(routes::unsubscribe_form),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::post().to(routes::unsubscribe),
+            )
+            .service(
+                web::scope("/webhooks")
+                    .wrap(from_fn(reject_unauthenticated_webhooks))
+                    .app_data(routes::webhook_json_config(webhook_max_body_bytes))
+                    .app_data(soft_bounce_threshold.clone())
+                    .app_data(webhook_credentials.clone())
+                    .route("/postmark", web::post().to(routes::postmark_webhook)),
+            )
+            .service(
+                web::scope("/api")
+                    .wrap(from_fn(reject_invalid_api_keys))
+                    .route(
+                        "/subscribers",
+                        web::get().to(routes::list_confirmed_subscribers),
+                    ),
+            )
+            .configure(test_routes)
+            // Registered before `/admin`, to be matched first: auditors do not have to log in.
+            .service(
+                web::scope("/admin/audit")
+                    .wrap(from_fn(reject_unauthorized_auditors))
+                    .wrap(from_fn(reject_disallowed_networks))
+                    .route("/export.jsonl", web::get().to(routes::export_audit_log)),
+            )
             .service(
                 web::scope("/admin")
                     .wrap(from_fn(reject_anonymous_users))
+                    // Outermost: requests from disallowed networks do not get to the login check.
+                    .wrap(from_fn(reject_disallowed_networks))
                     .route("/dashboard", web::get().to(routes::admin_dashboard))
                     .route(
                         "/newsletters",
                         web::get().to(routes::publish_newsletter_form),
                     )
                     .route("/newsletters", web::post().to(routes::publish_newsletter))
+                    .route(
+                        "/newsletters.json",
+                        web::get().to(routes::list_newsletter_issues),
+                    )
+                    .route(
+                        "/newsletters/scheduled.json",
+                        web::get().to(routes::list_scheduled_newsletter_issues),
+                    )
+                    .route(
+                        "/newsletters/{issue_id}/clone",
+                        web::post().to(routes::clone_newsletter_issue),
+                    )
                     .route("/password", web::get().to(routes::change_password_form))
                     .route("/password", web::post().to(routes::change_password))
-                    .route("/logout", web::post().to(routes::log_out)),
+                    .route("/logout", web::post().to(routes::log_out))
+                    .route("/migrations", web::get().to(routes::list_migrations))
+                    .route(
+                        "/deliverability",
+                        web::get().to(routes::deliverability_report),
+                    )
+                    .route("/idempotency", web::get().to(routes::list_idempotency_keys))
+                    .route("/queue", web::get().to(routes::queue_backlog))
+                    .route("/stats", web::get().to(routes::subscriber_stats))
+                    .route("/worker/pause", web::post().to(routes::pause_worker))
+                    .route("/worker/resume", web::post().to(routes::resume_worker))
+                    .route(
+                        "/subscribers/search",
+                        web::get().to(routes::search_subscribers),
+                    )
+                    .route(
+                        "/subscribers/export",
+                        web::get().to(routes::export_subscribers),
+                    )
+                    .route(
+                        "/subscribers/clean",
+                        web::post().to(routes::clean_subscribers),
+                    )
+                    // Must be registered after `/subscribers/search` and `/subscribers/export`,
+                    // which it would shadow.
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(routes::subscriber_details),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/export.json",
+                        web::get().to(routes::export_subscriber_data),
+                    ),
             )
             // Register the connection as part of the application state
             .app_data(db_pool.clone())
+            .app_data(subscriber_repository.clone())
+            .app_data(feature_flags.clone())
             .app_data(email_client.clone())
-            .app_data(base_url.clone())
+            .app_data(logout_redirect.clone())
+            .app_data(tenant_hosts.clone())
+            .app_data(canonical_url.clone())
+            .app_data(email_policy.clone())
+            .app_data(welcome_emails.clone())
+            .app_data(honeypot_field.clone())
+            .app_data(subscribe_state.clone())
+            .app_data(in_flight_request_limit.clone())
+            .app_data(trusted_proxies.clone())
+            .app_data(admin_allowed_networks.clone())
             .app_data(templates.clone())
+            .app_data(worker_pause.clone())
             .app_data(Data::new(hmac_secret.clone()))
+            .app_data(Data::new(session_settings.clone()))
+            .app_data(Data::new(newsletter_settings.clone()))
+            .app_data(idempotency_settings.clone())
+            .app_data(Data::new(cache_control_settings.clone()))
+            .app_data(security_headers.clone())
+            .app_data(compression_threshold.clone())
+            .app_data(partner_api_keys.clone())
+            .app_data(audit_api_keys.clone())
+            .app_data(Data::new(api_settings.clone()))
+            .app_data(deliverability_settings.clone())
+            .app_data(list_cleaning_settings.clone())
     })
+    .shutdown_timeout(shutdown_timeout)
     .listen(listener)?
     .run();
 
     Ok(server)
 }
 
-static TEMPLATES: Lazy<Tera> = Lazy::new(|| {
-    let mut tera = match Tera::new("templates/**/*") {
-        Ok(t) => t,
-        Err(e) => {
-            println!("Parsing error(s): {e}");
-            ::std::process::exit(1); //should we exit the process?
-        }
-    };
+/// Routes our integration tests rely on. They are not available in production builds.
+fn test_routes(_cfg: &mut web::ServiceConfig) {
+    #[cfg(feature = "test-support")]
+    _cfg.route("/test/sleep", web::get().to(crate::test_support::sleep));
+}
+
+/// Loads every template below `directory`. Relative paths are resolved from the working directory.
+pub fn load_templates(directory: &str) -> Result<Tera, anyhow::Error> {
+    let glob = format!("{}/**/*", directory.trim_end_matches('/'));
+    let mut tera = Tera::new(&glob)
+        .with_context(|| format!("Failed to parse the templates in {directory}"))?;
     //Disable auto-escaping for now.
     tera.autoescape_on(vec![]);
     let template_names: Vec<&str> = tera.get_template_names().collect();
-    println!("Registered templates: {template_names:?}");
-    tera
-});
+    tracing::info!(?template_names, "Registered templates");
+    Ok(tera)
+}
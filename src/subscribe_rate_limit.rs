@@ -0,0 +1,76 @@
+use redis::aio::ConnectionManager;
+use redis::RedisError;
+use secrecy::{ExposeSecret, Secret};
+use std::net::IpAddr;
+use tokio::sync::OnceCell;
+
+/// # Rate Limiting Subscriptions
+/// Each client IP can submit the subscription form up to `max_requests` times per window of
+/// `window_seconds`. The submissions are counted in a Redis key that expires with the window: the
+/// key's remaining TTL is exactly how long a client over the limit has to wait before trying again.
+pub struct SubscribeRateLimit {
+    client: redis::Client,
+    // Connected on first use: the application starts even if Redis is not reachable.
+    connection: OnceCell<ConnectionManager>,
+    key_prefix: String,
+    // 0 for no limit.
+    max_requests: u64,
+    window_seconds: u64,
+}
+
+impl SubscribeRateLimit {
+    pub fn new(
+        redis_uri: &Secret<String>,
+        key_prefix: String,
+        max_requests: u64,
+        window_seconds: u64,
+    ) -> Result<Self, RedisError> {
+        Ok(Self {
+            client: redis::Client::open(redis_uri.expose_secret().as_str())?,
+            connection: OnceCell::new(),
+            key_prefix,
+            max_requests,
+            window_seconds,
+        })
+    }
+
+    /// Counts a submission from `client_ip`. Returns how many seconds to wait before the next one,
+    /// if it is over the limit.
+    #[tracing::instrument(name = "Check the subscription rate limit", skip(self))]
+    pub async fn check(&self, client_ip: Option<IpAddr>) -> Result<Option<u64>, RedisError> {
+        if self.max_requests == 0 || self.window_seconds == 0 {
+            return Ok(None);
+        }
+        let client_ip = client_ip.map_or_else(|| "unknown".into(), |ip| ip.to_string());
+        let key = format!("{}{client_ip}", self.key_prefix);
+        // `SET NX` starts the window if there is none: `INCR` keeps the key's TTL.
+        let (n_requests, ttl): (u64, i64) = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(&key)
+            .arg(0)
+            .arg("NX")
+            .arg("EX")
+            .arg(self.window_seconds)
+            .ignore()
+            .cmd("INCR")
+            .arg(&key)
+            .cmd("TTL")
+            .arg(&key)
+            .query_async(&mut self.connection().await?)
+            .await?;
+        if n_requests <= self.max_requests {
+            return Ok(None);
+        }
+        // A key about to expire has a TTL of 0: clients should still wait before retrying.
+        Ok(Some(ttl.clamp(1, self.window_seconds as i64) as u64))
+    }
+
+    /// `ConnectionManager` reconnects on its own: it is cheap to clone and safe to keep around.
+    async fn connection(&self) -> Result<ConnectionManager, RedisError> {
+        self.connection
+            .get_or_try_init(|| self.client.get_tokio_connection_manager())
+            .await
+            .cloned()
+    }
+}
@@ -0,0 +1,233 @@
+use crate::domain::{NewSubscriber, SubscriberEmail};
+use crate::routes::{get_subscriber_id_from_token, store_subscription, SubscribeError};
+use crate::subscription_events::{record_subscription_event, SubscriptionEventType};
+use crate::tenant::Tenant;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A subscriber, as stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriberRecord {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+}
+
+/// The statuses a subscriber can be moved to once they have subscribed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriberStatus {
+    Confirmed,
+    Unsubscribed,
+    Invalid,
+}
+
+impl SubscriberStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Confirmed => "confirmed",
+            Self::Unsubscribed => "unsubscribed",
+            Self::Invalid => "invalid",
+        }
+    }
+
+    /// The only status a subscriber can be moved to this status from, if any. Confirming is
+    /// for pending subscribers alone: an unsubscribed or bounced subscriber cannot be brought back
+    /// by an old confirmation link.
+    pub fn required_previous_status(&self) -> Option<&'static str> {
+        match self {
+            Self::Confirmed => Some("pending_confirmation"),
+            Self::Unsubscribed | Self::Invalid => None,
+        }
+    }
+
+    /// The event recorded when a subscriber is moved to this status.
+    fn event_type(&self) -> SubscriptionEventType {
+        match self {
+            Self::Confirmed => SubscriptionEventType::Confirmed,
+            Self::Unsubscribed => SubscriptionEventType::Unsubscribed,
+            Self::Invalid => SubscriptionEventType::Invalidated,
+        }
+    }
+}
+
+pub struct StoredSubscription {
+    pub subscriber_id: Uuid,
+    // Whether the subscriber was stored just now, rather than subscribed already.
+    pub is_new: bool,
+    // The token to send a confirmation email with - `None` if there is nothing left to confirm.
+    pub subscription_token: Option<String>,
+}
+
+/// # Subscriber Repository
+/// The storage `subscribe` and `confirm` rely on. Handlers get it as `web::Data<dyn
+/// SubscriberRepository>`: the logic built on top of it can be unit-tested against an in-memory
+/// fake, without a database.
+#[async_trait::async_trait]
+pub trait SubscriberRepository: Send + Sync {
+    /// Stores `new_subscriber`, unless they subscribed already to `tenant` - see
+    /// `store_subscription`.
+    async fn insert(
+        &self,
+        tenant: &Tenant,
+        new_subscriber: &NewSubscriber,
+        consent: bool,
+        max_per_domain: Option<i64>,
+        double_opt_in: bool,
+        source: &str,
+    ) -> Result<StoredSubscription, SubscribeError>;
+
+    async fn find_by_email(
+        &self,
+        tenant: &Tenant,
+        email: &SubscriberEmail,
+    ) -> Result<Option<SubscriberRecord>, anyhow::Error>;
+
+    /// The subscriber a confirmation token was issued to. Tokens issued to the subscribers of
+    /// another tenant are unknown to `tenant`, if any.
+    async fn find_by_token(
+        &self,
+        tenant: Option<&Tenant>,
+        subscription_token: &str,
+    ) -> Result<Option<Uuid>, anyhow::Error>;
+
+    /// Moves the subscriber to `status`, recording why (`source`) in their subscription events.
+    /// Returns `None` if they already had that status, cannot be moved to it from their current
+    /// one - see `SubscriberStatus::required_previous_status` - or do not exist.
+    async fn set_status(
+        &self,
+        subscriber_id: Uuid,
+        status: SubscriberStatus,
+        source: &str,
+    ) -> Result<Option<SubscriberRecord>, anyhow::Error>;
+
+    /// The subscribers of `tenant`, oldest first.
+    async fn list(&self, tenant: &Tenant) -> Result<Vec<SubscriberRecord>, anyhow::Error>;
+}
+
+pub struct PostgresSubscriberRepository {
+    pool: PgPool,
+}
+
+impl PostgresSubscriberRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl SubscriberRepository for PostgresSubscriberRepository {
+    #[tracing::instrument(name = "Store a subscription", skip_all)]
+    async fn insert(
+        &self,
+        tenant: &Tenant,
+        new_subscriber: &NewSubscriber,
+        consent: bool,
+        max_per_domain: Option<i64>,
+        double_opt_in: bool,
+        source: &str,
+    ) -> Result<StoredSubscription, SubscribeError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?;
+        let stored = store_subscription(
+            &mut transaction,
+            tenant,
+            new_subscriber,
+            consent,
+            max_per_domain,
+            double_opt_in,
+            source,
+        )
+        .await?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to store a new subscriber.")?;
+        Ok(stored)
+    }
+
+    #[tracing::instrument(name = "Find a subscriber by email", skip_all)]
+    async fn find_by_email(
+        &self,
+        tenant: &Tenant,
+        email: &SubscriberEmail,
+    ) -> Result<Option<SubscriberRecord>, anyhow::Error> {
+        let subscriber = sqlx::query_as!(
+            SubscriberRecord,
+            r#"
+            SELECT id, email, name, status
+            FROM subscriptions
+            WHERE tenant_id = $1 AND email = $2
+            "#,
+            tenant.id(),
+            email.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(subscriber)
+    }
+
+    async fn find_by_token(
+        &self,
+        tenant: Option<&Tenant>,
+        subscription_token: &str,
+    ) -> Result<Option<Uuid>, anyhow::Error> {
+        Ok(get_subscriber_id_from_token(&self.pool, tenant, subscription_token).await?)
+    }
+
+    /// The update takes a row lock, hence concurrent requests cannot both move the subscriber.
+    #[tracing::instrument(name = "Update a subscriber status", skip(self, source))]
+    async fn set_status(
+        &self,
+        subscriber_id: Uuid,
+        status: SubscriberStatus,
+        source: &str,
+    ) -> Result<Option<SubscriberRecord>, anyhow::Error> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?;
+        let subscriber = sqlx::query_as!(
+            SubscriberRecord,
+            r#"
+            UPDATE subscriptions SET status = $2
+            WHERE id = $1 AND status <> $2 AND ($3::text IS NULL OR status = $3)
+            RETURNING id, email, name, status
+            "#,
+            subscriber_id,
+            status.as_str(),
+            status.required_previous_status(),
+        )
+        .fetch_optional(&mut transaction)
+        .await?;
+        if subscriber.is_some() {
+            record_subscription_event(&mut transaction, subscriber_id, status.event_type(), source)
+                .await
+                .context("Failed to record the subscription event.")?;
+        }
+        transaction.commit().await?;
+        Ok(subscriber)
+    }
+
+    #[tracing::instrument(name = "List subscribers", skip_all)]
+    async fn list(&self, tenant: &Tenant) -> Result<Vec<SubscriberRecord>, anyhow::Error> {
+        let subscribers = sqlx::query_as!(
+            SubscriberRecord,
+            r#"
+            SELECT id, email, name, status
+            FROM subscriptions
+            WHERE tenant_id = $1
+            ORDER BY subscribed_at
+            "#,
+            tenant.id(),
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(subscribers)
+    }
+}
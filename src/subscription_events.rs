@@ -0,0 +1,85 @@
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+/// # Subscription Events
+/// Every change to the state of a subscription is appended to `subscription_events`, alongside
+/// what caused it (`source`): replaying the events of a subscriber gives us their full history,
+/// which `subscriptions.status` alone cannot tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionEventType {
+    Subscribed,
+    Confirmed,
+    Unsubscribed,
+    Bounced,
+    Invalidated,
+}
+
+impl SubscriptionEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Subscribed => "subscribed",
+            Self::Confirmed => "confirmed",
+            Self::Unsubscribed => "unsubscribed",
+            Self::Bounced => "bounced",
+            Self::Invalidated => "invalidated",
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct SubscriptionEvent {
+    pub event_type: String,
+    pub source: String,
+    pub occurred_at: String,
+}
+
+/// Events should be recorded in the same transaction as the state change they describe.
+#[tracing::instrument(skip(executor))]
+pub async fn record_subscription_event(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+    event_type: SubscriptionEventType,
+    source: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_events (subscriber_id, event_type, source)
+        VALUES ($1, $2, $3)
+        "#,
+        subscriber_id,
+        event_type.as_str(),
+        source
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// The subscriber's events, oldest first.
+#[tracing::instrument(skip(pool))]
+pub async fn get_subscription_events(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<SubscriptionEvent>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT event_type, source, occurred_at
+        FROM subscription_events
+        WHERE subscriber_id = $1
+        ORDER BY event_id
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| SubscriptionEvent {
+            event_type: r.event_type,
+            source: r.source,
+            occurred_at: r.occurred_at.to_rfc3339(),
+        })
+        .collect())
+}
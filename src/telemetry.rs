@@ -1,8 +1,14 @@
+use crate::conversion_funnel;
+use rand::Rng;
+use std::io::Write;
 use tokio::task::JoinHandle;
-use tracing::{subscriber::set_global_default, Subscriber};
+use tracing::{subscriber::set_global_default, Level, Metadata, Subscriber};
+use tracing_appender::non_blocking::{NonBlocking, NonBlockingBuilder, WorkerGuard};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{
+    filter::filter_fn, fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Layer, Registry,
+};
 
 /// Compose multiple layers into a `tracing`'s subscriber.
 ///
@@ -12,9 +18,17 @@ use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Regis
 /// returned subscriber, which is indeed quite complex.
 /// We need to explicitly call out that the returned subscriber is `Send` and `Sync` to make it possible
 /// to pass it to `init_subscriber` later on.
+///
+/// # Sampling
+///
+/// Logging every span and event of a busy instance gets expensive. Only a `sample_ratio` fraction
+/// (between 0.0 and 1.0) of the spans and events below `WARN`, picked at random, is formatted and
+/// written to `sink`: warnings and errors are never dropped, and neither are the conversion funnel
+/// events - they are counted downstream.
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
+    sample_ratio: f64,
     sink: Sink,
 ) -> impl Subscriber + Send + Sync
 where
@@ -28,7 +42,12 @@ where
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
 
-    let formatting_layer = BunyanFormattingLayer::new(name, sink);
+    // A per-layer filter: `JsonStorageLayer` still sees every span.
+    let sample_ratio = sample_ratio.clamp(0.0, 1.0);
+    let formatting_layer =
+        BunyanFormattingLayer::new(name, sink).with_filter(filter_fn(move |metadata| {
+            is_sampled(metadata, sample_ratio)
+        }));
 
     // The `with` method is provided by `SubscriberExt`, an extension trait for `Subscriber` exposed
     // by `tracing_subscriber`
@@ -38,6 +57,13 @@ where
         .with(formatting_layer)
 }
 
+fn is_sampled(metadata: &Metadata<'_>, sample_ratio: f64) -> bool {
+    // Levels compare by verbosity: `ERROR` is the least verbose.
+    *metadata.level() <= Level::WARN
+        || metadata.target() == conversion_funnel::TARGET
+        || rand::thread_rng().gen_bool(sample_ratio)
+}
+
 /// Register a subscriber as global default to process span data.
 ///
 /// It should only be called once!
@@ -50,6 +76,37 @@ pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
 
+/// Flushes the logs still buffered by a `non_blocking_sink` once passed to `shutdown`.
+#[must_use = "Buffered logs are flushed when the guard is dropped."]
+pub struct TelemetryGuard {
+    // Only kept for its `Drop`, which does the flushing.
+    _guard: WorkerGuard,
+}
+
+/// # Background Flush
+/// Writing logs synchronously ties requests to the speed of `writer`, e.g. a slow stdout. The sink
+/// returned here hands them to a background thread instead, buffering up to `buffered_lines` lines:
+/// above that, logging blocks rather than dropping lines.
+///
+/// Lines still in the buffer when the process exits are lost, unless the returned guard is handed
+/// to `shutdown` first.
+pub fn non_blocking_sink<W>(writer: W, buffered_lines: usize) -> (NonBlocking, TelemetryGuard)
+where
+    W: Write + Send + 'static,
+{
+    let (sink, guard) = NonBlockingBuilder::default()
+        .buffered_lines_limit(buffered_lines)
+        .lossy(false)
+        .finish(writer);
+    (sink, TelemetryGuard { _guard: guard })
+}
+
+/// Flushes the buffered logs to their writer, waiting for the background thread to be done with
+/// them. Nothing can be logged through the sink afterwards.
+pub fn shutdown(guard: TelemetryGuard) {
+    drop(guard);
+}
+
 pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
 where
     F: FnOnce() -> R + Send + 'static,
@@ -61,3 +118,77 @@ where
     // within its scope.
     tokio::task::spawn_blocking(move || current_span.in_scope(f))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{get_subscriber, non_blocking_sink, shutdown};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    /// A sink keeping hold of everything that has been logged.
+    #[derive(Clone, Default)]
+    struct CapturingWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for CapturingWriter {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn errors_are_never_dropped_while_info_events_are_sampled() {
+        let writer = CapturingWriter::default();
+        let subscriber = get_subscriber("test".into(), "info".into(), 0.1, writer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..1000 {
+                tracing::info!("A successful request");
+            }
+            for _ in 0..100 {
+                tracing::error!("A failed request");
+            }
+        });
+
+        let logs = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        let count = |message: &str| logs.lines().filter(|l| l.contains(message)).count();
+        assert_eq!(count("A failed request"), 100);
+        // 100 events are expected: the bounds are over 5 standard deviations away.
+        let sampled = count("A successful request");
+        assert!(
+            (50..=150).contains(&sampled),
+            "{sampled} info events logged"
+        );
+    }
+
+    #[test]
+    fn events_logged_right_before_shutdown_are_flushed() {
+        let writer = CapturingWriter::default();
+        let (sink, guard) = non_blocking_sink(writer.clone(), 16);
+        let subscriber = get_subscriber("test".into(), "info".into(), 1.0, sink);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..1000 {
+                tracing::info!("Event number {i}");
+            }
+        });
+        shutdown(guard);
+
+        let logs = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(logs.lines().count(), 1000);
+        assert!(logs.contains("Event number 999"));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::configuration::TenantSettings;
+use actix_web::dev::Payload;
+use actix_web::http::header::HOST;
+use actix_web::http::uri::Authority;
+use actix_web::{web, FromRequest, HttpRequest};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+
+/// Serves every host without a tenant of its own. Rows created before tenants were introduced
+/// belong to it - it must match the default of the `tenant_id` columns.
+pub const DEFAULT_TENANT: &str = "default";
+
+/// # Tenants
+/// Several independent newsletters can be served from one deployment: every request is served by
+/// a tenant, picked by its `Host` header, and must only ever see that tenant's data.
+///
+/// Handlers get the tenant of the current request as an extractor, and scope their queries with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tenant {
+    id: String,
+    // Where links sent to the tenant's subscribers point to. `None` for the default tenant, which
+    // is served from `ApplicationBaseUrl`.
+    base_url: Option<String>,
+}
+
+impl Tenant {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn base_url(&self) -> Option<&str> {
+        self.base_url.as_deref()
+    }
+
+    fn default_tenant() -> Self {
+        Self {
+            id: DEFAULT_TENANT.into(),
+            base_url: None,
+        }
+    }
+}
+
+/// The tenant serving each host.
+#[derive(Debug, Default)]
+pub struct TenantHosts(HashMap<String, Tenant>);
+
+impl TenantHosts {
+    pub fn new(tenants: &[TenantSettings]) -> Result<Self, String> {
+        let mut hosts = HashMap::new();
+        for settings in tenants {
+            let tenant = Tenant {
+                id: settings.id.clone(),
+                base_url: Some(settings.base_url.clone()),
+            };
+            for host in &settings.hosts {
+                if let Some(other) = hosts.insert(host.to_lowercase(), tenant.clone()) {
+                    return Err(format!(
+                        "{host} is listed for both the {} and the {} tenants.",
+                        other.id, tenant.id
+                    ));
+                }
+            }
+        }
+        Ok(Self(hosts))
+    }
+
+    /// `host` is the value of the `Host` header: the port, if any, is ignored.
+    pub fn tenant_for(&self, host: Option<&str>) -> Tenant {
+        host.and_then(|host| host.parse::<Authority>().ok())
+            .and_then(|authority| self.0.get(&authority.host().to_lowercase()))
+            .cloned()
+            .unwrap_or_else(Tenant::default_tenant)
+    }
+}
+
+impl FromRequest for Tenant {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Tenant, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let host = req.headers().get(HOST).and_then(|h| h.to_str().ok());
+        let tenant = match req.app_data::<web::Data<TenantHosts>>() {
+            Some(hosts) => hosts.tenant_for(host),
+            None => Tenant::default_tenant(),
+        };
+        ready(Ok(tenant))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TenantHosts, DEFAULT_TENANT};
+    use crate::configuration::TenantSettings;
+    use claims::assert_err;
+
+    fn tenant(id: &str, hosts: &[&str]) -> TenantSettings {
+        TenantSettings {
+            id: id.into(),
+            base_url: format!("https://{}", hosts[0]),
+            hosts: hosts.iter().map(|h| h.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn hosts_are_matched_regardless_of_port_and_case() {
+        let hosts = TenantHosts::new(&[tenant("acme", &["newsletter.acme.com"])]).unwrap();
+
+        for host in ["newsletter.acme.com", "Newsletter.Acme.com:8000"] {
+            assert_eq!(hosts.tenant_for(Some(host)).id(), "acme");
+        }
+    }
+
+    #[test]
+    fn unknown_or_missing_hosts_are_served_by_the_default_tenant() {
+        let hosts = TenantHosts::new(&[tenant("acme", &["newsletter.acme.com"])]).unwrap();
+
+        for host in [Some("127.0.0.1:8000"), Some("not a host"), None] {
+            assert_eq!(hosts.tenant_for(host).id(), DEFAULT_TENANT);
+        }
+    }
+
+    #[test]
+    fn a_host_cannot_be_served_by_two_tenants() {
+        assert_err!(TenantHosts::new(&[
+            tenant("acme", &["newsletter.example.com"]),
+            tenant("globex", &["newsletter.example.com"]),
+        ]));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::configuration::DatabaseSettings;
+use crate::startup::connect_with_retry;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use once_cell::sync::Lazy;
+use sqlx::{Connection, Executor, PgConnection};
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+// The integration tests drive idempotent requests step by step - the module itself is private.
+pub use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
+
+/// The name of the migrated template database, created once per process.
+static TEMPLATE_DATABASE: Lazy<OnceCell<String>> = Lazy::new(OnceCell::new);
+
+/// # Template Databases
+/// Every integration test gets its own logical database, for isolation. Running all our migrations
+/// against each of them gets slower as the number of tests (and migrations) grows.
+///
+/// Postgres can instead create a database as a copy of another one: we migrate a template database
+/// the first time this function is called and clone it, with `CREATE DATABASE ... TEMPLATE ...`,
+/// to create `config.database_name`.
+pub async fn create_database_from_template(config: &DatabaseSettings) -> Result<(), anyhow::Error> {
+    let template = TEMPLATE_DATABASE
+        .get_or_try_init(|| create_template_database(config))
+        .await?;
+
+    let mut connection = connect_with_retry(config, &config.without_db())
+        .await
+        .context("Failed to connect to Postgres.")?;
+    connection
+        .execute(
+            format!(
+                r#"CREATE DATABASE "{}" TEMPLATE "{}";"#,
+                config.database_name, template
+            )
+            .as_str(),
+        )
+        .await
+        .context("Failed to create the database from the template.")?;
+
+    Ok(())
+}
+
+async fn create_template_database(config: &DatabaseSettings) -> Result<String, anyhow::Error> {
+    let template = format!("template_{}", Uuid::new_v4());
+    let mut connection = connect_with_retry(config, &config.without_db())
+        .await
+        .context("Failed to connect to Postgres.")?;
+    connection
+        .execute(format!(r#"CREATE DATABASE "{template}";"#).as_str())
+        .await
+        .context("Failed to create the template database.")?;
+
+    // Postgres refuses to copy a database while other sessions are connected to it: the connection
+    // must be closed before the template is used.
+    let template_config = DatabaseSettings {
+        database_name: template.clone(),
+        ..config.clone()
+    };
+    let mut connection = PgConnection::connect_with(&template_config.with_db())
+        .await
+        .context("Failed to connect to the template database.")?;
+    sqlx::migrate!("./migrations")
+        .run(&mut connection)
+        .await
+        .context("Failed to migrate the template database.")?;
+    connection.close().await?;
+
+    Ok(template)
+}
+
+#[derive(serde::Deserialize)]
+pub struct SleepParameters {
+    milliseconds: u64,
+}
+
+/// A deliberately slow route, served at `/test/sleep` when the `test-support` feature is enabled.
+pub async fn sleep(parameters: web::Query<SleepParameters>) -> HttpResponse {
+    tokio::time::sleep(std::time::Duration::from_millis(parameters.milliseconds)).await;
+    HttpResponse::Ok().finish()
+}
@@ -17,7 +17,7 @@ pub(crate) fn see_other(location: &str) -> HttpResponse {
 
 // Return a 400 with the user-representation of the validation error as body. The error root cause is
 // preserved for logging purposes
-pub fn e400<T: std::fmt::Debug + std::fmt::Display>(e: T) -> actix_web::Error
+pub fn e400<T>(e: T) -> actix_web::Error
 where
     T: std::fmt::Debug + std::fmt::Display + 'static,
 {
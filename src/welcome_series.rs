@@ -0,0 +1,242 @@
+use crate::configuration::{Settings, WelcomeStep};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::{append_footer, ExecutionOutcome};
+use crate::signed_token::{manage_data_link, unsubscribe_link};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{PgExecutor, PgPool};
+use tera::Tera;
+use uuid::Uuid;
+
+/// How long a step is deferred after a transient failure.
+const RETRY_DELAY_SECONDS: i64 = 60;
+
+/// # Welcome Series
+/// New subscribers get the configured series of emails once they have confirmed, each step
+/// `offset_hours` after the confirmation. Every step is queued upfront, and sent by the delivery
+/// worker once it is due: a step is only marked as sent once, hence it never goes out twice.
+///
+/// Steps are tracked by their position in the series - new steps should be appended to it.
+#[tracing::instrument(skip(executor, series))]
+pub async fn enqueue_welcome_series(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+    series: &[WelcomeStep],
+) -> Result<(), anyhow::Error> {
+    if series.is_empty() {
+        return Ok(());
+    }
+    let steps: Vec<i32> = (1..=series.len() as i32).collect();
+    let offsets: Vec<i32> = series.iter().map(|s| s.offset_hours as i32).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO welcome_series_queue (subscriber_id, step, execute_after)
+        SELECT $1, step, now() + make_interval(hours => offset_hours)
+        FROM UNNEST($2::int4[], $3::int4[]) AS s(step, offset_hours)
+        ON CONFLICT (subscriber_id, step) DO NOTHING
+        "#,
+        subscriber_id,
+        &steps,
+        &offsets
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Fails if any step of `series` is missing one of its templates: better to find out at startup
+/// than once the first subscriber is due for it.
+pub fn validate_welcome_series(
+    templates: &Tera,
+    series: &[WelcomeStep],
+) -> Result<(), anyhow::Error> {
+    for step in series {
+        for extension in ["html", "txt"] {
+            let name = format!("{}.{extension}", step.template);
+            templates
+                .get_template(&name)
+                .with_context(|| format!("The welcome series template {name} does not exist."))?;
+        }
+    }
+    Ok(())
+}
+
+/// Sends the next step of the welcome series that is due, if any. Subscribers who are no longer
+/// confirmed are skipped. Steps end with the same footer as newsletter issues - see
+/// `append_footer`.
+#[tracing::instrument(
+    skip_all,
+    fields(subscriber_id=tracing::field::Empty, step=tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_welcome_step(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &Tera,
+    configuration: &Settings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = sqlx::query!(
+        r#"
+        SELECT q.subscriber_id, q.step, s.tenant_id, s.email, s.name, s.locale
+        FROM welcome_series_queue q
+        JOIN subscriptions s ON s.id = q.subscriber_id
+        WHERE q.sent_at IS NULL AND q.execute_after <= now() AND s.status = 'confirmed'
+        FOR UPDATE OF q
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    let task = match task {
+        Some(task) => task,
+        None => return Ok(ExecutionOutcome::EmptyQueue),
+    };
+    tracing::Span::current()
+        .record("subscriber_id", tracing::field::display(task.subscriber_id))
+        .record("step", task.step);
+
+    let step = configuration
+        .subscriptions
+        .welcome_series
+        .get(task.step as usize - 1);
+    let recipient = SubscriberEmail::parse(task.email);
+    let base_url = configuration.base_url_of_tenant(&task.tenant_id);
+    let hmac_secret = &configuration.application.hmac_secret;
+    let unsubscribe_link =
+        unsubscribe_link(base_url, hmac_secret, task.subscriber_id).map_err(anyhow::Error::msg)?;
+    let manage_data_link =
+        manage_data_link(base_url, hmac_secret, task.subscriber_id).map_err(anyhow::Error::msg)?;
+    match (step, recipient) {
+        (None, _) => {
+            tracing::warn!("The step is no longer part of the welcome series. Skipping.");
+        }
+        (_, Err(e)) => {
+            tracing::error!(error.message = %e,
+                "Skipping a welcome series step. The stored recipient is invalid.");
+        }
+        (Some(step), Ok(recipient)) => match render_step(
+            templates,
+            step,
+            &task.name,
+            &unsubscribe_link,
+            &manage_data_link,
+            &configuration.newsletter.company_address,
+        ) {
+            Ok((html_body, text_body)) => match email_client
+                .send_newsletter(
+                    &recipient,
+                    task.locale.as_deref(),
+                    &step.subject,
+                    &html_body,
+                    &text_body,
+                    Some(&unsubscribe_link),
+                )
+                .await
+            {
+                Ok(_) => {}
+                Err(e) if e.is_permanent() => {
+                    tracing::error!(error.cause_chain = ?e, error.message = %e,
+                        "The welcome series step cannot be sent as is. Skipping.");
+                }
+                Err(e) => {
+                    tracing::warn!(error.cause_chain = ?e, error.message = %e,
+                        "Failed to send a welcome series step. Retrying later.");
+                    sqlx::query!(
+                        r#"
+                        UPDATE welcome_series_queue
+                        SET execute_after = $3
+                        WHERE subscriber_id = $1 AND step = $2
+                        "#,
+                        task.subscriber_id,
+                        task.step,
+                        Utc::now() + chrono::Duration::seconds(RETRY_DELAY_SECONDS)
+                    )
+                    .execute(&mut transaction)
+                    .await?;
+                    transaction.commit().await?;
+                    return Ok(ExecutionOutcome::TaskCompleted);
+                }
+            },
+            Err(e) => {
+                tracing::error!(error.cause_chain = ?e, error.message = %e,
+                    "Failed to render a welcome series step. Skipping.");
+            }
+        },
+    }
+    sqlx::query!(
+        r#"
+        UPDATE welcome_series_queue
+        SET sent_at = now()
+        WHERE subscriber_id = $1 AND step = $2
+        "#,
+        task.subscriber_id,
+        task.step
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// Returns the HTML and plain text bodies of `step`, for a subscriber called `name`, followed by
+/// our footer.
+fn render_step(
+    templates: &Tera,
+    step: &WelcomeStep,
+    name: &str,
+    unsubscribe_link: &str,
+    manage_data_link: &str,
+    company_address: &str,
+) -> Result<(String, String), tera::Error> {
+    let mut context = tera::Context::new();
+    context.insert("name", name);
+    context.insert("unsubscribe_link", unsubscribe_link);
+    let html_body = templates.render(&format!("{}.html", step.template), &context)?;
+    let text_body = templates.render(&format!("{}.txt", step.template), &context)?;
+    append_footer(
+        html_body,
+        text_body,
+        unsubscribe_link,
+        manage_data_link,
+        company_address,
+        templates,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(template: &str) -> WelcomeStep {
+        WelcomeStep {
+            template: template.into(),
+            subject: "Getting started".into(),
+            offset_hours: 24,
+        }
+    }
+
+    #[test]
+    fn a_series_whose_templates_exist_is_valid() {
+        let mut templates = Tera::default();
+        templates
+            .add_raw_templates(vec![("day_one.html", "Hi!"), ("day_one.txt", "Hi!")])
+            .unwrap();
+
+        assert!(validate_welcome_series(&templates, &[step("day_one")]).is_ok());
+    }
+
+    #[test]
+    fn a_step_missing_either_template_is_rejected() {
+        let mut templates = Tera::default();
+        templates
+            .add_raw_templates(vec![("day_one.html", "Hi!"), ("day_two.txt", "Hi!")])
+            .unwrap();
+
+        assert!(validate_welcome_series(&templates, &[step("day_one")]).is_err());
+        assert!(validate_welcome_series(&templates, &[step("day_two")]).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, RedisError};
+use secrecy::{ExposeSecret, Secret};
+use tokio::sync::OnceCell;
+
+/// # Pausing Deliveries
+/// During an incident (e.g. an issue went out with the wrong content, or our sending reputation is
+/// tanking) we want to stop sending emails without stopping the process - or losing the queue.
+///
+/// The pause is a flag in Redis, shared by the API (to set it) and every delivery worker (to check
+/// it before each task). Workers leave the queue untouched while it is set.
+pub struct WorkerPause {
+    client: redis::Client,
+    // Connected on first use: the application starts even if Redis is not reachable.
+    connection: OnceCell<ConnectionManager>,
+    key: String,
+}
+
+impl WorkerPause {
+    pub fn new(redis_uri: &Secret<String>, key: String) -> Result<Self, RedisError> {
+        Ok(Self {
+            client: redis::Client::open(redis_uri.expose_secret().as_str())?,
+            connection: OnceCell::new(),
+            key,
+        })
+    }
+
+    pub async fn is_paused(&self) -> Result<bool, RedisError> {
+        self.connection().await?.exists(&self.key).await
+    }
+
+    #[tracing::instrument(name = "Pause the delivery worker", skip(self))]
+    pub async fn pause(&self) -> Result<(), RedisError> {
+        self.connection().await?.set(&self.key, 1).await
+    }
+
+    #[tracing::instrument(name = "Resume the delivery worker", skip(self))]
+    pub async fn resume(&self) -> Result<(), RedisError> {
+        self.connection().await?.del(&self.key).await
+    }
+
+    /// `ConnectionManager` reconnects on its own: it is cheap to clone and safe to keep around.
+    async fn connection(&self) -> Result<ConnectionManager, RedisError> {
+        self.connection
+            .get_or_try_init(|| self.client.get_tokio_connection_manager())
+            .await
+            .cloned()
+    }
+}
@@ -0,0 +1,71 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app_with};
+
+#[tokio::test]
+async fn requests_from_an_allowed_network_reach_the_admin_panel() {
+    // Arrange - Our test client connects from the loopback interface.
+    let app = spawn_app_with(|c| c.admin.allowed_cidrs = vec!["127.0.0.0/8".into()]).await;
+
+    // Act
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn requests_from_outside_the_allowed_networks_are_forbidden() {
+    // Arrange
+    let app = spawn_app_with(|c| c.admin.allowed_cidrs = vec!["203.0.113.0/24".into()]).await;
+
+    // Act
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn the_client_ip_is_taken_from_x_forwarded_for_behind_a_trusted_proxy() {
+    // Arrange - We play the part of a proxy on the loopback interface.
+    let app = spawn_app_with(|c| {
+        c.application.trusted_proxies = vec!["127.0.0.1".into()];
+        c.admin.allowed_cidrs = vec!["203.0.113.0/24".into()];
+    })
+    .await;
+
+    for (forwarded_for, expected_status) in [("203.0.113.7", 303), ("198.51.100.1", 403)] {
+        // Act
+        let response = app
+            .api_client
+            .get(format!("{}/admin/dashboard", &app.address))
+            .header("X-Forwarded-For", forwarded_for)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            expected_status,
+            "Unexpected status for a client at {forwarded_for}."
+        );
+    }
+}
+
+#[tokio::test]
+async fn x_forwarded_for_is_ignored_from_untrusted_peers() {
+    // Arrange
+    let app = spawn_app_with(|c| c.admin.allowed_cidrs = vec!["203.0.113.0/24".into()]).await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .header("X-Forwarded-For", "203.0.113.7")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
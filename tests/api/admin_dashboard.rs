@@ -1,4 +1,5 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
+use std::time::Duration;
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
@@ -13,6 +14,44 @@ async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
     assert_is_redirect_to(&response, "/login");
 }
 
+#[tokio::test]
+async fn anonymous_json_requests_to_admin_routes_get_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/newsletters.json", &app.address))
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(401, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "You must log in first.");
+}
+
+#[tokio::test]
+async fn anonymous_browser_requests_to_admin_routes_are_redirected_to_login() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/newsletters.json", &app.address))
+        .header("Accept", "text/html,application/xhtml+xml")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
 #[tokio::test]
 async fn logout_clears_session_state() {
     // Arrange
@@ -42,3 +81,63 @@ async fn logout_clears_session_state() {
     let response = app.get_admin_dashboard().await;
     assert_is_redirect_to(&response, "/login");
 }
+
+#[tokio::test]
+async fn an_idle_session_is_logged_out() {
+    // Arrange
+    let app = spawn_app_with(|c| c.session.idle_timeout_seconds = 1).await;
+    app.login().await;
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Let the session go idle for longer than the timeout
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your session has expired - please log in again.</i></p>"));
+
+    // The session is gone for good
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_session_expires_after_its_ttl_even_when_active() {
+    // Arrange
+    let app = spawn_app_with(|c| c.session.ttl_seconds = 2).await;
+    app.login().await;
+
+    // Act - Keep the session active until it is past its TTL
+    for _ in 0..3 {
+        let response = app.get_admin_dashboard().await;
+        assert_eq!(response.status().as_u16(), 200);
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn logout_redirects_to_the_configured_destination() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.session.logout_redirect = "https://example.com/".into();
+        c.session.allowed_logout_redirect_hosts = vec!["example.com".into()];
+    })
+    .await;
+    app.login().await;
+
+    // Act
+    let response = app.post_logout().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "https://example.com/");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,90 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app_with, TestApp};
+use secrecy::Secret;
+
+const AUDIT_API_KEY: &str = "an-audit-api-key";
+
+async fn get_audit_export(
+    app: &TestApp,
+    query: &[(&str, String)],
+    api_key: Option<&str>,
+) -> reqwest::Response {
+    let mut request = app
+        .api_client
+        .get(format!("{}/admin/audit/export.jsonl", &app.address))
+        .query(query);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    request.send().await.expect("Failed to execute request.")
+}
+
+fn parse_jsonl(body: &str) -> Vec<serde_json::Value> {
+    body.lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn audited_actions_are_exported_as_jsonl_within_the_date_filter() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.admin.audit_api_keys = vec![Secret::new(AUDIT_API_KEY.into())]).await;
+    app.login().await;
+    app.post_pause_worker().await;
+    app.post_resume_worker().await;
+    let today = chrono::Utc::now().date_naive();
+    let day = |offset: i64| (today + chrono::Duration::days(offset)).to_string();
+
+    // Act - Part 1 - Within the date filter
+    let response = get_audit_export(
+        &app,
+        &[("from", day(-1)), ("to", day(1))],
+        Some(AUDIT_API_KEY),
+    )
+    .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "application/x-ndjson");
+    let entries = parse_jsonl(&response.text().await.unwrap());
+    let actions: Vec<_> = entries
+        .iter()
+        .map(|e| e["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, vec!["worker_paused", "worker_resumed"]);
+    for entry in &entries {
+        assert_eq!(entry["user_id"], app.test_user.user_id.to_string());
+    }
+
+    // Act - Part 2 - Outside the date filter
+    let response = get_audit_export(&app, &[("from", day(1))], Some(AUDIT_API_KEY)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(parse_jsonl(&response.text().await.unwrap()).is_empty());
+
+    // Act - Part 3 - As the logged-in admin
+    let response = get_audit_export(&app, &[], None).await;
+
+    // Assert
+    assert_eq!(parse_jsonl(&response.text().await.unwrap()).len(), 2);
+}
+
+#[tokio::test]
+async fn exporting_the_audit_log_requires_an_audit_api_key_or_an_admin_session() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.admin.audit_api_keys = vec![Secret::new(AUDIT_API_KEY.into())]).await;
+
+    // Act - Part 1 - Anonymous
+    let response = get_audit_export(&app, &[], None).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Invalid API key
+    let response = get_audit_export(&app, &[], Some("not-an-audit-api-key")).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
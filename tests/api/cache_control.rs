@@ -0,0 +1,81 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+fn cache_control(response: &reqwest::Response) -> &str {
+    response
+        .headers()
+        .get("Cache-Control")
+        .expect("The response has no Cache-Control header.")
+        .to_str()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn admin_responses_are_never_stored() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Part 1 - Anonymous
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    assert_eq!(cache_control(&response), "no-store");
+
+    // Act - Part 2 - Logged in
+    app.login().await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(cache_control(&response), "no-store");
+}
+
+#[tokio::test]
+async fn public_pages_are_not_stored_for_logged_in_users() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(&app.address)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(cache_control(&response), "no-store");
+}
+
+#[tokio::test]
+async fn the_widget_is_cached_for_a_long_time() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/widget.js", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(cache_control(&response), "public, max-age=86400");
+}
+
+#[tokio::test]
+async fn the_home_page_is_cached_for_a_short_time() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(&app.address)
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(cache_control(&response), "public, max-age=60");
+}
@@ -0,0 +1,69 @@
+use crate::helpers::{spawn_app_with, TestApp};
+
+const CANONICAL_HOST: &str = "newsletter.example.com";
+
+async fn spawn_app_with_canonical_url() -> TestApp {
+    spawn_app_with(|c| {
+        c.application.base_url = format!("https://{CANONICAL_HOST}");
+        c.application.enforce_canonical_url = true;
+    })
+    .await
+}
+
+async fn get(app: &TestApp, path: &str, host: &str, scheme: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}{}", &app.address, path))
+        .header("Host", host)
+        .header("X-Forwarded-Proto", scheme)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn requests_for_another_host_or_scheme_are_redirected_to_the_canonical_url() {
+    // Arrange
+    let app = spawn_app_with_canonical_url().await;
+
+    for (host, scheme) in [
+        ("www.newsletter.example.com", "https"),
+        (CANONICAL_HOST, "http"),
+    ] {
+        // Act
+        let response = get(&app, "/login?next=%2Fadmin", host, scheme).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 301);
+        assert_eq!(
+            response.headers().get("Location").unwrap(),
+            "https://newsletter.example.com/login?next=%2Fadmin"
+        );
+    }
+}
+
+#[tokio::test]
+async fn requests_for_the_canonical_url_and_health_checks_are_not_redirected() {
+    // Arrange
+    let app = spawn_app_with_canonical_url().await;
+
+    // Act
+    let canonical = get(&app, "/login", CANONICAL_HOST, "https").await;
+    let health_check = get(&app, "/health_check", "www.newsletter.example.com", "http").await;
+
+    // Assert
+    assert_eq!(canonical.status().as_u16(), 200);
+    assert_eq!(health_check.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn requests_are_not_redirected_unless_enabled() {
+    // Arrange
+    let app =
+        spawn_app_with(|c| c.application.base_url = format!("https://{CANONICAL_HOST}")).await;
+
+    // Act
+    let response = get(&app, "/login", "www.newsletter.example.com", "http").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
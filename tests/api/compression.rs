@@ -0,0 +1,30 @@
+use crate::helpers::{spawn_app_with, TestApp};
+
+async fn get_with_gzip(app: &TestApp, path: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}{}", &app.address, path))
+        .header("Accept-Encoding", "gzip")
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn only_responses_above_the_threshold_are_compressed() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.compression_min_bytes = 100).await;
+
+    // Act - Part 1 - A tiny response
+    let response = get_with_gzip(&app, "/health_check").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers().get("Content-Encoding").is_none());
+
+    // Act - Part 2 - A large response
+    let response = get_with_gzip(&app, "/login").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Encoding"], "gzip");
+}
@@ -0,0 +1,134 @@
+use crate::helpers::{spawn_app, TestApp};
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+use zero2prod::daily_stats::{
+    aggregate_daily_stats, catch_up_daily_stats, get_recent_daily_stats, DailyStats,
+};
+
+async fn store_delivery(app: &TestApp, issue_id: Uuid, status: &str, at: DateTime<Utc>) {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_deliveries
+            (newsletter_issue_id, subscriber_email, status, delivered_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        issue_id,
+        format!("{}@example.com", Uuid::new_v4()),
+        status,
+        at
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to store delivery.");
+}
+
+async fn store_bounce(app: &TestApp, at: DateTime<Utc>) {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'le guin', now(), 'confirmed')
+        "#,
+        subscriber_id,
+        format!("{subscriber_id}@example.com"),
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to store subscriber.");
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_events (subscriber_id, event_type, source, occurred_at)
+        VALUES ($1, 'bounced', 'postmark_webhook', $2)
+        "#,
+        subscriber_id,
+        at
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to store bounce.");
+}
+
+async fn store_issue(app: &TestApp) -> Uuid {
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues
+            (newsletter_issue_id, title, text_content, html_content, published_at)
+        VALUES ($1, 'Title', 'Text', '<p>HTML</p>', now())
+        "#,
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to store newsletter issue.");
+    issue_id
+}
+
+#[tokio::test]
+async fn daily_stats_match_the_activity_of_the_day() {
+    // Arrange
+    let app = spawn_app().await;
+    let day = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+    let during = |hour| DateTime::from_utc(day.and_hms_opt(hour, 0, 0).unwrap(), Utc);
+    let next_day = during(0) + chrono::Duration::days(1);
+    let issue_id = store_issue(&app).await;
+    for hour in [0, 12, 23] {
+        store_delivery(&app, issue_id, "delivered", during(hour)).await;
+    }
+    store_delivery(&app, issue_id, "skipped_frequency_cap", during(12)).await;
+    store_delivery(&app, issue_id, "delivered", next_day).await;
+    store_bounce(&app, during(6)).await;
+    store_bounce(&app, during(18)).await;
+    store_bounce(&app, next_day).await;
+
+    // Act - Aggregating twice must not count anything twice
+    aggregate_daily_stats(&app.db_pool, day).await.unwrap();
+    aggregate_daily_stats(&app.db_pool, day).await.unwrap();
+
+    // Assert
+    let stats = get_recent_daily_stats(&app.db_pool, 7).await.unwrap();
+    assert_eq!(
+        stats,
+        vec![DailyStats {
+            day: "2026-10-01".into(),
+            sent: 3,
+            opened: 0,
+            clicked: 0,
+            bounced: 2,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn catching_up_backfills_every_day_since_the_last_aggregated_one() {
+    // Arrange
+    let app = spawn_app().await;
+    let last_aggregated_day = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+    let today = NaiveDate::from_ymd_opt(2026, 10, 4).unwrap();
+    let midday = |day: NaiveDate| DateTime::from_utc(day.and_hms_opt(12, 0, 0).unwrap(), Utc);
+    let issue_id = store_issue(&app).await;
+    aggregate_daily_stats(&app.db_pool, last_aggregated_day)
+        .await
+        .unwrap();
+    // The aggregator then stopped running, for a few days.
+    for day in last_aggregated_day.iter_days().take(4) {
+        store_delivery(&app, issue_id, "delivered", midday(day)).await;
+    }
+
+    // Act
+    catch_up_daily_stats(&app.db_pool, today).await.unwrap();
+
+    // Assert
+    let stats = get_recent_daily_stats(&app.db_pool, 7).await.unwrap();
+    let expected: Vec<_> = ["2026-10-04", "2026-10-03", "2026-10-02", "2026-10-01"]
+        .into_iter()
+        .map(|day| DailyStats {
+            day: day.into(),
+            sent: 1,
+            opened: 0,
+            clicked: 0,
+            bounced: 0,
+        })
+        .collect();
+    assert_eq!(stats, expected);
+}
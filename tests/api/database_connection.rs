@@ -0,0 +1,88 @@
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use zero2prod::configuration::{get_configuration, DatabaseSettings};
+use zero2prod::startup::{connect_with_retry, Application};
+
+/// A port nothing is listening on - yet.
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Stands in for a Postgres instance that is still starting up: after `delay`, it starts listening
+/// on `port` and forwards every connection to the actual database.
+fn postgres_available_after(configuration: &DatabaseSettings, port: u16, delay: Duration) {
+    let postgres_address = format!("{}:{}", configuration.host, configuration.port);
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+        loop {
+            let (mut inbound, _) = listener.accept().await.unwrap();
+            let mut outbound = TcpStream::connect(&postgres_address).await.unwrap();
+            tokio::spawn(async move {
+                let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+            });
+        }
+    });
+}
+
+fn database_settings(port: u16, connect_retries: u32) -> DatabaseSettings {
+    let mut configuration = get_configuration()
+        .expect("Failed to read configuration.")
+        .database;
+    configuration.host = "127.0.0.1".into();
+    configuration.port = port;
+    configuration.connect_retries = connect_retries;
+    configuration.connect_backoff_milliseconds = 100;
+    configuration
+}
+
+#[tokio::test]
+async fn connecting_succeeds_once_postgres_becomes_reachable_within_the_retry_budget() {
+    // Arrange
+    let port = free_port();
+    let configuration = database_settings(port, 5);
+    let postgres = get_configuration()
+        .expect("Failed to read configuration.")
+        .database;
+    postgres_available_after(&postgres, port, Duration::from_millis(500));
+
+    // Act - Retries wait 100ms, 200ms, 400ms, 800ms and 1.6s
+    let outcome = connect_with_retry(&configuration, &configuration.without_db()).await;
+
+    // Assert
+    assert!(outcome.is_ok());
+}
+
+#[tokio::test]
+async fn connecting_fails_once_the_retry_budget_is_exhausted() {
+    // Arrange
+    let port = free_port();
+    let configuration = database_settings(port, 2);
+
+    // Act
+    let outcome = connect_with_retry(&configuration, &configuration.without_db()).await;
+
+    // Assert
+    assert!(outcome.is_err());
+}
+
+#[tokio::test]
+async fn startup_fails_fast_if_the_pool_cannot_be_warmed_up() {
+    // Arrange
+    let mut configuration = get_configuration().expect("Failed to read configuration.");
+    configuration.application.port = 0;
+    configuration.database = database_settings(free_port(), 0);
+    configuration.database.warm_up_pool = true;
+
+    // Act
+    let outcome = tokio::time::timeout(Duration::from_secs(10), Application::build(configuration))
+        .await
+        .expect("Startup did not fail fast.");
+
+    // Assert
+    assert!(outcome.is_err());
+}
@@ -0,0 +1,38 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn the_deliverability_page_shows_the_records_expected_on_the_sending_domain() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.email_client.sender_email = "newsletter@example.com".into();
+        c.deliverability.spf_include = "spf.mtasv.net".into();
+        c.deliverability.dkim_selector = Some("20261016pm".into());
+    })
+    .await;
+    app.login().await;
+
+    // Act
+    let html_page = app.get_deliverability_html().await;
+
+    // Assert
+    assert!(html_page.contains("<strong>example.com</strong>"));
+    assert!(html_page.contains("v=spf1 a mx include:spf.mtasv.net ~all"));
+    assert!(html_page.contains("20261016pm._domainkey.example.com"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_deliverability_page() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/deliverability", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,31 @@
+use crate::helpers::spawn_app_with;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn in_flight_requests_complete_on_shutdown_while_new_connections_are_refused() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.shutdown_timeout_seconds = 5).await;
+    let url = format!("{}/test/sleep?milliseconds=1000", app.address);
+    let slow_request = tokio::spawn(async move { reqwest::get(url).await.map(|r| r.status()) });
+    // Give the slow request the time to reach the application
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // Act
+    let started_at = Instant::now();
+    let shutdown = tokio::spawn(app.server_handle.stop(true));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Assert
+    let new_request = reqwest::Client::new()
+        .get(format!("{}/health_check", app.address))
+        .send()
+        .await;
+    assert!(new_request.is_err(), "A new connection has been accepted.");
+    let status = slow_request
+        .await
+        .unwrap()
+        .expect("The slow request was dropped.");
+    assert_eq!(status.as_u16(), 200);
+    shutdown.await.unwrap();
+    assert!(started_at.elapsed() < Duration::from_secs(5));
+}
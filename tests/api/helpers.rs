@@ -1,11 +1,23 @@
+use actix_web::dev::ServerHandle;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
 use once_cell::sync::Lazy;
-use sqlx::{Connection, Executor, PgConnection, PgPool};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tera::Tera;
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
 use uuid::Uuid;
 use wiremock::MockServer;
-use zero2prod::configuration::{get_configuration, DatabaseSettings};
+use zero2prod::configuration::{get_configuration, DatabaseSettings, Settings};
+use zero2prod::confirmation_retries::try_execute_confirmation_retry;
+use zero2prod::conversion_funnel;
 use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
+use zero2prod::retry_budget::RetryBudget;
+use zero2prod::test_support::create_database_from_template;
+use zero2prod::welcome_series::try_execute_welcome_step;
 use zero2prod::{email_client::EmailClient, startup, startup::Application, telemetry};
 
 pub(crate) struct TestApp {
@@ -16,6 +28,11 @@ pub(crate) struct TestApp {
     pub(crate) test_user: TestUser,
     pub(crate) api_client: reqwest::Client,
     pub(crate) email_client: EmailClient,
+    pub(crate) retry_budget: RetryBudget,
+    pub(crate) templates: Tera,
+    // To run a delivery worker against this application.
+    pub(crate) configuration: Settings,
+    pub(crate) server_handle: ServerHandle,
 }
 
 /// Confirmation links embedded in the request to the email API.
@@ -25,9 +42,19 @@ pub(crate) struct ConfirmationLinks {
 }
 
 impl TestApp {
+    pub async fn post_subscriptions_validate(&self, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/validate", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -35,6 +62,30 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn post_subscriptions_household(&self, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/household", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_postmark_webhook(
+        &self,
+        body: String,
+        content_type: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/webhooks/postmark", &self.address))
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     /// Extract the confirmation links embedded in the request to the email API.
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
@@ -44,6 +95,8 @@ impl TestApp {
             let links: Vec<_> = linkify::LinkFinder::new()
                 .links(s)
                 .filter(|l| *l.kind() == linkify::LinkKind::Url)
+                // Confirmation emails also carry a "manage your data" link.
+                .filter(|l| !l.as_str().contains("/subscriptions/preferences"))
                 .collect();
 
             assert_eq!(links.len(), 1);
@@ -56,8 +109,31 @@ impl TestApp {
             confirmation_link
         };
 
-        let html = get_link(&body["HtmlBody"].as_str().unwrap());
-        let plain_text = get_link(&body["TextBody"].as_str().unwrap());
+        let html = get_link(body["HtmlBody"].as_str().unwrap());
+        let plain_text = get_link(body["TextBody"].as_str().unwrap());
+
+        ConfirmationLinks { html, plain_text }
+    }
+
+    /// The "manage your data" links of an email, HTML and plain text.
+    pub fn get_manage_data_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
+        let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+
+        let get_link = |s: &str| {
+            let links: Vec<_> = linkify::LinkFinder::new()
+                .links(s)
+                .filter(|l| l.as_str().contains("/subscriptions/preferences"))
+                .collect();
+            assert_eq!(links.len(), 1);
+
+            let mut link = reqwest::Url::parse(links[0].as_str()).unwrap();
+            assert_eq!(link.host_str().unwrap(), "127.0.0.1");
+            link.set_port(Some(self.port)).unwrap();
+            link
+        };
+
+        let html = get_link(body["HtmlBody"].as_str().unwrap());
+        let plain_text = get_link(body["TextBody"].as_str().unwrap());
 
         ConfirmationLinks { html, plain_text }
     }
@@ -67,7 +143,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             // This `reqwest` method makes sure that the body is URL-encoded and the `Content-Type`
             // header is set accordingly.
             .form(body)
@@ -76,10 +152,30 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_unsubscribe(&self, subscription_token: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/unsubscribe", &self.address))
+            .query(&[("subscription_token", subscription_token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_home_html(&self) -> String {
+        self.api_client
+            .get(&self.address)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
     // Our tests will only look at the HTML page, therefore we do not expose the underlying reqwest::Response
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -90,7 +186,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -102,7 +198,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -117,7 +213,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
@@ -126,7 +222,31 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_clean_subscribers(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/subscribers/clean", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_pause_worker(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/worker/pause", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resume_worker(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/worker/resume", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -142,7 +262,7 @@ impl TestApp {
 
     pub async fn get_publish_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -157,19 +277,195 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_publish_newsletter_with_idempotency_key_header<Body>(
+        &self,
+        body: &Body,
+        idempotency_key: &str,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters", &self.address))
+            .header("Idempotency-Key", idempotency_key)
             .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_subscribers_search(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers/search", &self.address))
+            .query(&[("q", query)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers_export(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers/export", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_details_html(&self, subscriber_id: uuid::Uuid) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_subscriber_data_export(&self, subscriber_id: uuid::Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}/export.json",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_migrations(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/migrations", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_migrations_html(&self) -> String {
+        self.get_migrations().await.text().await.unwrap()
+    }
+
+    pub async fn get_deliverability_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/deliverability", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_queue_backlog(&self) -> serde_json::Value {
+        self.api_client
+            .get(format!("{}/admin/queue", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .json()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_subscriber_stats(&self) -> serde_json::Value {
+        self.api_client
+            .get(format!("{}/admin/stats", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .json()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_newsletter_issues(&self) -> serde_json::Value {
+        self.api_client
+            .get(format!("{}/admin/newsletters.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .json()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_scheduled_newsletter_issues(&self) -> serde_json::Value {
+        self.api_client
+            .get(format!(
+                "{}/admin/newsletters/scheduled.json",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .json()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_idempotency_keys_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/idempotency", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
     pub async fn dispatch_all_pending_emails(&self) {
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.retry_budget,
+                &self.configuration,
+                &self.templates,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn dispatch_all_welcome_steps(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_welcome_step(
+                &self.db_pool,
+                &self.email_client,
+                &self.templates,
+                &self.configuration,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn dispatch_all_confirmation_retries(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_confirmation_retry(
+                &self.db_pool,
+                &self.email_client,
+                &self.configuration.subscriptions,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
@@ -187,18 +483,74 @@ static TRACING: Lazy<()> = Lazy::new(|| {
     // We could work around it, but this is the most straight-forward way of moving forward.
     if std::env::var("TEST_LOG").is_ok() {
         let subscriber =
-            telemetry::get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
+            telemetry::get_subscriber(subscriber_name, default_filter_level, 1.0, std::io::stdout)
+                .with(FunnelEventsLayer);
         telemetry::init_subscriber(subscriber);
     } else {
         let subscriber =
-            telemetry::get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+            telemetry::get_subscriber(subscriber_name, default_filter_level, 1.0, std::io::sink)
+                .with(FunnelEventsLayer);
         telemetry::init_subscriber(subscriber);
     }
 });
 
+/// The conversion funnel events of every application spawned by the tests, as `(subscriber_id,
+/// step)` pairs - they share the global `tracing` subscriber.
+static FUNNEL_EVENTS: Lazy<Mutex<Vec<(String, String)>>> = Lazy::new(Default::default);
+
+struct FunnelEventsLayer;
+
+impl<S: tracing::Subscriber> Layer<S> for FunnelEventsLayer {
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        if event.metadata().target() != conversion_funnel::TARGET {
+            return;
+        }
+        let mut fields = FieldValues::default();
+        event.record(&mut fields);
+        let subscriber_id = fields.0.remove("subscriber_id").unwrap_or_default();
+        let step = fields.0.remove("step").unwrap_or_default();
+        FUNNEL_EVENTS.lock().unwrap().push((subscriber_id, step));
+    }
+}
+
+#[derive(Default)]
+struct FieldValues(HashMap<&'static str, String>);
+
+impl Visit for FieldValues {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name(), format!("{value:?}"));
+    }
+}
+
+/// The conversion funnel steps `subscriber_id` went through, in order.
+pub fn funnel_steps(subscriber_id: Uuid) -> Vec<String> {
+    let subscriber_id = subscriber_id.to_string();
+    FUNNEL_EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(id, _)| *id == subscriber_id)
+        .map(|(_, step)| step.clone())
+        .collect()
+}
+
 /// We are running tests, so it is not worth it to propagate errors: if we fail to perform the required
 /// setup we can just panic and crash all the things.
 pub(crate) async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Same as `spawn_app`, but `customise` gets a chance to tweak the configuration before the
+/// application is built.
+pub(crate) async fn spawn_app_with(customise: impl FnOnce(&mut Settings)) -> TestApp {
     // The first time `initialize` is invoked the code in `TRACING` is executed. All other invocations
     // will instead skip execution.
     Lazy::force(&TRACING);
@@ -213,6 +565,10 @@ pub(crate) async fn spawn_app() -> TestApp {
         // Use a random OS port
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // Tests share a Redis instance: pausing one worker must not pause everybody else's.
+        c.newsletter.worker_pause_key = format!("worker_paused:{}", Uuid::new_v4());
+        c.subscriptions.duplicate_key_prefix = format!("recent_subscriptions:{}:", Uuid::new_v4());
+        customise(&mut c);
         c
     };
 
@@ -224,11 +580,13 @@ pub(crate) async fn spawn_app() -> TestApp {
         .expect("Failed to build application");
 
     let port = application.port();
+    let server_handle = application.handle();
     let address = format!("http://127.0.0.1:{}", &port);
 
     // launch the server as a background task
     // tokio::spawn returns a handle to the spawned future, but we have no use for it here, hence the
     // non-binding let
+    #[allow(clippy::let_underscore_future)]
     let _ = tokio::spawn(application.run_until_stopped());
 
     let client = reqwest::Client::builder()
@@ -244,7 +602,11 @@ pub(crate) async fn spawn_app() -> TestApp {
         port,
         test_user: TestUser::generate(),
         api_client: client,
-        email_client: configuration.email_client.client(),
+        email_client: configuration.email_client.clone().client(),
+        retry_budget: RetryBudget::new(configuration.newsletter.retry_budget_per_minute),
+        templates: startup::load_templates(&configuration.application.templates_dir).unwrap(),
+        configuration,
+        server_handle,
     };
 
     test_app.test_user.store(&test_app.db_pool).await;
@@ -275,27 +637,12 @@ pub(crate) async fn spawn_app() -> TestApp {
 /// * run database migrations on it.
 ///
 /// The best place to do this is in spawn_app, before launching our actix-web test application.
-async fn configure_database(config: &DatabaseSettings) -> PgPool {
-    let mut connection = PgConnection::connect_with(&config.without_db())
+/// Migrations only run once per test binary: each database is a copy of an already migrated
+/// template.
+async fn configure_database(config: &DatabaseSettings) {
+    create_database_from_template(config)
         .await
-        .expect("Failed to connect to Postgres");
-
-    connection
-        .execute(format!(r#"CREATE DATABASE "{}"; "#, config.database_name).as_str())
-        .await
-        .expect("Failed to create database.");
-
-    //Migrate database
-    let connection_pool = PgPool::connect_with(config.with_db())
-        .await
-        .expect("Failed to connect to Postgres.");
-
-    sqlx::migrate!("./migrations")
-        .run(&connection_pool)
-        .await
-        .expect("Failed to migrate the database");
-
-    connection_pool
+        .expect("Failed to create the database.");
 }
 
 pub(crate) struct TestUser {
@@ -342,3 +689,19 @@ pub(crate) fn assert_is_redirect_to(response: &reqwest::Response, location: &str
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
 }
+
+/// Stores a confirmed subscriber straight into the database, skipping the subscription flow.
+pub(crate) async fn store_subscriber(app: &TestApp, email: &str, name: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, $3, now(), 'confirmed')
+        "#,
+        Uuid::new_v4(),
+        email,
+        name
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to store subscriber.");
+}
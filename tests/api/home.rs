@@ -0,0 +1,21 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_home_page_links_to_the_admin_dashboard_for_logged_in_admins_only() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Part 1 - Anonymous visitor
+    let html_page = app.get_home_html().await;
+
+    // Assert
+    assert!(html_page.contains("Welcome to our newsletter!"));
+    assert!(!html_page.contains(r#"<a href="/admin/dashboard">Admin dashboard</a>"#));
+
+    // Act - Part 2 - Logged-in admin
+    app.login().await;
+    let html_page = app.get_home_html().await;
+
+    // Assert
+    assert!(html_page.contains(r#"<a href="/admin/dashboard">Admin dashboard</a>"#));
+}
@@ -0,0 +1,159 @@
+use crate::helpers::{spawn_app, TestApp};
+use actix_web::body::to_bytes;
+use actix_web::HttpResponse;
+use sqlx::{Postgres, Transaction};
+use zero2prod::test_support::{save_response, try_processing, IdempotencyKey, NextAction};
+
+const MAX_STORED_BODY_BYTES: usize = 512;
+
+async fn start_processing(app: &TestApp, key: &IdempotencyKey) -> Transaction<'static, Postgres> {
+    match try_processing(
+        &app.db_pool,
+        key,
+        app.test_user.user_id,
+        &app.configuration.idempotency,
+    )
+    .await
+    .unwrap()
+    {
+        NextAction::StartProcessing(transaction) => transaction,
+        NextAction::ReturnSavedResponse(_) => {
+            panic!("The request was not expected to be replayed.")
+        }
+    }
+}
+
+#[tokio::test]
+async fn small_responses_are_stored_and_replayed() {
+    // Arrange
+    let app = spawn_app().await;
+    let key: IdempotencyKey = uuid::Uuid::new_v4().to_string().try_into().unwrap();
+    let transaction = start_processing(&app, &key).await;
+    let response = HttpResponse::Ok().body("a".repeat(MAX_STORED_BODY_BYTES));
+    save_response(
+        transaction,
+        &key,
+        app.test_user.user_id,
+        response,
+        MAX_STORED_BODY_BYTES,
+    )
+    .await
+    .unwrap();
+
+    // Act
+    let next_action = try_processing(
+        &app.db_pool,
+        &key,
+        app.test_user.user_id,
+        &app.configuration.idempotency,
+    )
+    .await
+    .unwrap();
+
+    // Assert
+    match next_action {
+        NextAction::ReturnSavedResponse(response) => {
+            let body = to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(body.len(), MAX_STORED_BODY_BYTES);
+        }
+        NextAction::StartProcessing(_) => panic!("The saved response was expected to be replayed."),
+    }
+}
+
+#[tokio::test]
+async fn large_responses_are_not_stored_and_retries_are_processed_again() {
+    // Arrange
+    let app = spawn_app().await;
+    let key: IdempotencyKey = uuid::Uuid::new_v4().to_string().try_into().unwrap();
+    let transaction = start_processing(&app, &key).await;
+    let body = "a".repeat(MAX_STORED_BODY_BYTES + 1);
+
+    // Act - Part 1 - Process the request
+    let response = save_response(
+        transaction,
+        &key,
+        app.test_user.user_id,
+        HttpResponse::Ok().body(body.clone()),
+        MAX_STORED_BODY_BYTES,
+    )
+    .await
+    .unwrap();
+
+    // Assert - The caller gets the whole response, but it has not been stored
+    assert_eq!(to_bytes(response.into_body()).await.unwrap(), body);
+    let saved =
+        sqlx::query!("SELECT response_status_code, response_body, replayable FROM idempotency")
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to fetch the idempotency record.");
+    assert_eq!(saved.response_status_code, Some(200));
+    assert_eq!(saved.response_body, None);
+    assert!(!saved.replayable);
+
+    // Act - Part 2 - Retry
+    let next_action = try_processing(
+        &app.db_pool,
+        &key,
+        app.test_user.user_id,
+        &app.configuration.idempotency,
+    )
+    .await
+    .unwrap();
+
+    // Assert
+    assert!(matches!(next_action, NextAction::StartProcessing(_)));
+}
+
+#[tokio::test]
+async fn retries_wait_for_a_concurrent_request_to_save_its_response() {
+    // Arrange - The request has committed its idempotency record, but not its response yet
+    let app = spawn_app().await;
+    let key: IdempotencyKey = uuid::Uuid::new_v4().to_string().try_into().unwrap();
+    sqlx::query!(
+        "INSERT INTO idempotency (user_id, idempotency_key, created_at) VALUES ($1, $2, now())",
+        app.test_user.user_id,
+        key.as_ref()
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to insert the idempotency record.");
+    let body = "a".repeat(MAX_STORED_BODY_BYTES);
+
+    // Act - The request saves its response while it is being retried
+    let save = async {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let transaction = app.db_pool.begin().await.unwrap();
+        save_response(
+            transaction,
+            &key,
+            app.test_user.user_id,
+            HttpResponse::Ok().body(body.clone()),
+            MAX_STORED_BODY_BYTES,
+        )
+        .await
+    };
+    let retry = try_processing(
+        &app.db_pool,
+        &key,
+        app.test_user.user_id,
+        &app.configuration.idempotency,
+    );
+    // Bounded: a retry holding on to the row lock would have both of them wait on each other.
+    let (response, next_action) = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        tokio::join!(save, retry)
+    })
+    .await
+    .expect("The retry and the request it retries are waiting on each other.");
+
+    // Assert - Both get the same, fully-formed, response
+    let response = response.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(to_bytes(response.into_body()).await.unwrap(), body);
+    match next_action.unwrap() {
+        NextAction::ReturnSavedResponse(saved_response) => {
+            assert_eq!(saved_response.status().as_u16(), 200);
+            assert_eq!(to_bytes(saved_response.into_body()).await.unwrap(), body);
+        }
+        NextAction::StartProcessing(_) => panic!("The saved response was expected to be replayed."),
+    }
+}
@@ -0,0 +1,38 @@
+use crate::helpers::spawn_app_with;
+use std::time::Duration;
+
+#[tokio::test]
+async fn requests_above_the_in_flight_limit_get_a_503() {
+    // Arrange
+    let app = spawn_app_with(|c| c.application.max_in_flight_requests = 2).await;
+    let slow_requests: Vec<_> = (0..2)
+        .map(|_| {
+            let url = format!("{}/test/sleep?milliseconds=1000", app.address);
+            tokio::spawn(async move { reqwest::get(url).await.unwrap().status().as_u16() })
+        })
+        .collect();
+    // Give the slow requests the time to reach the application
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // Act
+    let response = reqwest::get(format!("{}/login", app.address))
+        .await
+        .unwrap();
+    let health_check = reqwest::get(format!("{}/health_check", app.address))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 503);
+    assert!(response.headers().get("Retry-After").is_some());
+    assert_eq!(health_check.status().as_u16(), 200);
+    for slow_request in slow_requests {
+        assert_eq!(slow_request.await.unwrap(), 200);
+    }
+
+    // The limit only applies to requests in flight
+    let response = reqwest::get(format!("{}/login", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
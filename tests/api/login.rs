@@ -64,3 +64,87 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+#[tokio::test]
+async fn login_redirects_to_the_page_requested_before_logging_in() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Part 1 - Try to reach an admin page while anonymous
+    let response = app.get_publish_newsletter().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Login
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    });
+    let response = app.post_login(&login_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+#[tokio::test]
+async fn the_requested_page_is_only_used_for_the_next_login() {
+    // Arrange
+    let app = spawn_app().await;
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    });
+    app.get_publish_newsletter().await;
+    let response = app.post_login(&login_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.post_logout().await;
+
+    // Act
+    let response = app.post_login(&login_body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+/// Coarse by design: we only want to catch an unknown username short-circuiting the (slow) password
+/// hash verification.
+#[tokio::test]
+async fn unknown_usernames_and_wrong_passwords_are_indistinguishable() {
+    // Arrange
+    let app = spawn_app().await;
+    let unknown_username = serde_json::json!({
+        "username": uuid::Uuid::new_v4().to_string(),
+        "password": "random-password"
+    });
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "random-password"
+    });
+
+    let mut median_durations = Vec::new();
+    for login_body in [&unknown_username, &wrong_password] {
+        // Act
+        let mut durations = Vec::new();
+        for _ in 0..5 {
+            let start = std::time::Instant::now();
+            let response = app.post_login(login_body).await;
+            durations.push(start.elapsed());
+
+            // Assert - Same response...
+            assert_is_redirect_to(&response, "/login");
+            let html_page = app.get_login_html().await;
+            assert!(html_page.contains(r#"<p><i>Authentication failed</i></p>"#));
+        }
+        durations.sort();
+        median_durations.push(durations[durations.len() / 2]);
+    }
+
+    // ...in a comparable amount of time
+    let (fastest, slowest) = (
+        median_durations.iter().min().unwrap(),
+        median_durations.iter().max().unwrap(),
+    );
+    assert!(
+        slowest.as_secs_f64() < fastest.as_secs_f64() * 3.0,
+        "Median login durations are too far apart: {median_durations:?}"
+    );
+}
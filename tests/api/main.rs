@@ -1,11 +1,38 @@
+mod admin_allowlist;
 mod admin_dashboard;
+mod audit_log;
+mod cache_control;
+mod canonical_url;
 mod change_password;
+mod compression;
+mod daily_stats;
+mod database_connection;
+mod deliverability;
+mod graceful_shutdown;
 mod health_check;
 mod helpers;
+mod home;
+mod idempotency;
+mod load_shedding;
 mod login;
+mod migrations;
 mod newsletter;
+mod partner_api;
+mod security_headers;
+mod sender_verification;
+mod session_store;
+mod subscriber_data_export;
+mod subscribers_clean;
+mod subscribers_export;
+mod subscribers_search;
+mod subscription_preferences;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_unsubscribe;
+mod templates;
+mod tenants;
+mod test_support;
+mod webhooks;
 
 /// Each file in tests/ folder gets compiled as its own crate. `cargo` compiles each test executable
 /// in isolation and warns us if, for a specific tet file, one or more public functions in `helpers`
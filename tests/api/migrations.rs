@@ -0,0 +1,49 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+/// The versions of the migrations shipped in the `migrations` folder, in the order they are applied.
+fn known_migration_versions() -> Vec<String> {
+    let mut versions: Vec<String> = std::fs::read_dir("migrations")
+        .expect("Failed to read the migrations folder.")
+        .map(|entry| {
+            let file_name = entry.unwrap().file_name().into_string().unwrap();
+            file_name.split('_').next().unwrap().to_string()
+        })
+        .collect();
+    versions.sort();
+    versions
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_migrations() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_migrations().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn migrations_page_lists_the_applied_migrations_in_order() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let html_page = app.get_migrations_html().await;
+
+    // Assert
+    let positions: Vec<usize> = known_migration_versions()
+        .iter()
+        .map(|version| {
+            html_page
+                .find(&format!("<td>{version}</td>"))
+                .unwrap_or_else(|| panic!("Migration {version} is not listed."))
+        })
+        .collect();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    assert!(html_page.contains("All migrations applied cleanly."));
+    assert!(!html_page.contains("<td>dirty</td>"));
+}
@@ -1,10 +1,17 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
+use crate::helpers::{
+    assert_is_redirect_to, spawn_app, spawn_app_with, ConfirmationLinks, TestApp,
+};
 use fake::faker::internet::en::SafeEmail;
 use fake::faker::name::en::Name;
 use fake::Fake;
+use std::collections::HashMap;
 use std::time::Duration;
-use wiremock::matchers::{any, method, path};
-use wiremock::{Mock, ResponseTemplate};
+use wiremock::matchers::{any, body_partial_json, method, path};
+use wiremock::{Mock, Respond, ResponseTemplate};
+use zero2prod::configuration::{QuietHoursSettings, WarmUpSettings};
+use zero2prod::issue_delivery_worker::{
+    quiet_hours_end, requeue_stale_claims, run_worker_until_stopped,
+};
 
 #[tokio::test]
 async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
@@ -62,88 +69,1460 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
 /// the scoped mock are verified. This creates a useful feedback loop to keep our test helpers clean
 /// and up-to-date.
 async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    create_unconfirmed_subscriber_with_locale(app, "").await
+}
+
+async fn create_unconfirmed_subscriber_with_locale(
+    app: &TestApp,
+    locale: &str,
+) -> ConfirmationLinks {
     // We are working with multiple subscribers now, their details must be randomized to avoid conflicts!
     let name: String = Name().fake();
     let email: String = SafeEmail().fake();
     //let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
-    let body = serde_urlencoded::to_string(&serde_json::json!({
+    let body = serde_urlencoded::to_string(serde_json::json!({
         "name": name,
-        "email": email
+        "email": email,
+        "locale": locale
     }))
     .unwrap();
 
-    let _mock_guard = Mock::given(path("/email"))
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // We now inspect the requests received by the mock Postmark server to retrieve the confirmation
+    // link and return it.
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    app.get_confirmation_links(email_request)
+}
+
+async fn create_confirmed_subscriber(app: &TestApp) {
+    // We can then reuse the same helper and just add an extra step to actually call the confirmation
+    // link!
+    create_confirmed_subscriber_with_locale(app, "").await
+}
+
+async fn create_confirmed_subscriber_with_locale(app: &TestApp, locale: &str) {
+    let confirmation_link = create_unconfirmed_subscriber_with_locale(app, locale).await;
+
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[ignore]
+#[tokio::test]
+async fn newsletters_are_delivered_to_confirmed_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    dbg!(&app.email_server.uri());
+    // Act - Part 1 - Submit newsletter form
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(
+        "<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>"
+    ));
+    app.dispatch_all_pending_emails().await;
+
+    // Mock verifies on Drop that we have sent the newsletter email
+}
+
+#[tokio::test]
+async fn an_issue_enqueued_twice_for_the_same_subscriber_is_delivered_once() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Act - Enqueue the same (issue, subscriber) pair again, bypassing the form
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT newsletter_issue_id, subscriber_email
+        FROM newsletter_deliveries
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to enqueue the issue again.");
+    app.dispatch_all_pending_emails().await;
+
+    // Mock verifies on Drop that we have sent the newsletter email exactly once
+}
+
+#[tokio::test]
+async fn newsletters_are_personalized_for_each_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Hi {{ name }}!",
+        "html_content" : "<p>Hi {{ name }}!</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let subscribers = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch the subscribers.");
+    let newsletters: Vec<serde_json::Value> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .filter(|body: &serde_json::Value| body["Subject"] == "Newsletter title")
+        .collect();
+    assert_eq!(newsletters.len(), 2);
+    for newsletter in newsletters {
+        let subscriber = subscribers
+            .iter()
+            .find(|s| newsletter["To"] == s.email.as_str())
+            .unwrap();
+        let text_body = newsletter["TextBody"].as_str().unwrap();
+        assert!(text_body.starts_with(&format!("Hi {}!", subscriber.name)));
+    }
+}
+
+#[tokio::test]
+async fn newsletters_carry_the_footer() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.newsletter.company_address = "Zero2Prod Ltd, 42 Test Road".into();
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    for content in [&body["HtmlBody"], &body["TextBody"]] {
+        let content = content.as_str().unwrap();
+        assert!(content.contains("Zero2Prod Ltd, 42 Test Road"));
+        assert!(content.contains("/subscriptions/unsubscribe?subscription_token="));
+        assert!(content.contains("/subscriptions/preferences?token="));
+    }
+    // The signature delimiter is on a line of its own, after the content
+    let text_body = body["TextBody"].as_str().unwrap();
+    assert!(text_body.contains("Newsletter body as plain text\n--\n"));
+}
+
+#[tokio::test]
+async fn the_unsubscribe_link_is_on_its_own_line_in_the_text_body() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let text_body = body["TextBody"].as_str().unwrap();
+    let unsubscribe_link = format!(
+        "{}/subscriptions/unsubscribe?subscription_token=",
+        app.configuration.application.base_url
+    );
+    assert!(text_body
+        .lines()
+        .any(|line| line.starts_with(&unsubscribe_link)));
+}
+
+#[tokio::test]
+async fn the_list_unsubscribe_link_unsubscribes_its_recipient_in_one_click() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let list_unsubscribe = body["Headers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|h| h["Name"] == "List-Unsubscribe")
+        .unwrap()["Value"]
+        .as_str()
+        .unwrap();
+    let mut unsubscribe_link = reqwest::Url::parse(
+        list_unsubscribe
+            .trim_start_matches('<')
+            .trim_end_matches('>'),
+    )
+    .unwrap();
+    assert_eq!(unsubscribe_link.host_str().unwrap(), "127.0.0.1");
+    unsubscribe_link.set_port(Some(app.port)).unwrap();
+
+    // Act - As mail clients do, see RFC 8058
+    let response = app
+        .api_client
+        .post(unsubscribe_link)
+        .form(&[("List-Unsubscribe", "One-Click")])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn the_message_id_of_each_newsletter_is_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let message_id = body["Headers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|h| h["Name"] == "Message-ID")
+        .unwrap()["Value"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    // `<uuid@sending-domain>`, the sender in our test configuration being `test@gmail.com`
+    let (id, domain) = message_id
+        .strip_prefix('<')
+        .and_then(|m| m.strip_suffix('>'))
+        .and_then(|m| m.split_once('@'))
+        .unwrap();
+    assert!(uuid::Uuid::parse_str(id).is_ok());
+    assert_eq!(domain, "gmail.com");
+
+    let delivery = sqlx::query!("SELECT message_id FROM newsletter_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the delivery.");
+    assert_eq!(delivery.message_id, Some(message_id));
+}
+
+#[tokio::test]
+async fn issues_whose_content_is_not_a_valid_template_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Hi {{ name",
+        "html_content" : "<p>Hi {{ name }}!</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 422);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("The newsletter issue content is not a valid template"));
+    assert_eq!(count_newsletter_issues(&app).await, 0);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn an_incomplete_issue_is_shown_again_with_the_submitted_values() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    // Act - Part 1 - Submit an issue without content
+    let newsletter_request_body = serde_json::json!({
+        "title": "A <title> worth keeping",
+        "text_content": "",
+        "html_content": "",
+        "idempotency_key": idempotency_key
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 422);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"value="A &lt;title&gt; worth keeping""#));
+    assert!(html_page.contains("The newsletter issue needs both a plain text and an HTML content."));
+    // The form carries a fresh idempotency key for the next attempt.
+    assert!(!html_page.contains(&idempotency_key));
+    assert_eq!(count_newsletter_issues(&app).await, 0);
+
+    // Act - Part 2 - Submit the completed issue with the rejected idempotency key
+    let newsletter_request_body = serde_json::json!({
+        "title": "A <title> worth keeping",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": idempotency_key
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+
+    // Assert - The key had not been used up
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(count_newsletter_issues(&app).await, 1);
+}
+
+#[tokio::test]
+async fn nothing_is_delivered_while_the_worker_is_paused() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Pause the worker
+    let response = app.post_pause_worker().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("Delivery worker: paused"));
+
+    // Act - Part 2 - Publish an issue
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert - Nothing goes out while paused
+    let worker = tokio::spawn(run_worker_until_stopped(app.configuration.clone()));
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert_eq!(count_newsletter_deliveries(&app).await, 0);
+
+    // Act - Part 3 - Resume the worker
+    let response = app.post_resume_worker().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Assert - The issue is delivered
+    let mut deliveries = 0;
+    for _ in 0..50 {
+        deliveries = count_newsletter_deliveries(&app).await;
+        if deliveries > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    worker.abort();
+    assert_eq!(deliveries, 1);
+}
+
+/// Responds with `response`, telling the test each time an email is sent.
+struct NotifyOnSend {
+    sent: tokio::sync::mpsc::UnboundedSender<()>,
+    response: ResponseTemplate,
+}
+
+impl Respond for NotifyOnSend {
+    fn respond(&self, _request: &wiremock::Request) -> ResponseTemplate {
+        let _ = self.sent.send(());
+        self.response.clone()
+    }
+}
+
+#[tokio::test]
+async fn confirmation_emails_do_not_wait_for_newsletter_deliveries() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.confirmation_retries = 3;
+        c.subscriptions.confirmation_retry_backoff_seconds = 0;
+        // Newsletter emails never go through: they must not time out either.
+        c.email_client.request_timeout_milliseconds = 3_600_000;
+    })
+    .await;
+    for _ in 0..10 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.login().await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let (newsletter_sent, mut newsletter_emails) = tokio::sync::mpsc::unbounded_channel();
+    Mock::given(path("/email"))
+        .and(body_partial_json(
+            serde_json::json!({"Subject": "Newsletter title"}),
+        ))
+        .respond_with(NotifyOnSend {
+            sent: newsletter_sent,
+            response: ResponseTemplate::new(200).set_delay(Duration::from_secs(3600)),
+        })
+        .mount(&app.email_server)
+        .await;
+    // The confirmation email fails the first time, to be retried by the worker.
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .mount(&app.email_server)
+        .await;
+    let (confirmation_sent, mut confirmation_emails) = tokio::sync::mpsc::unbounded_channel();
+    Mock::given(path("/email"))
+        .respond_with(NotifyOnSend {
+            sent: confirmation_sent,
+            response: ResponseTemplate::new(200),
+        })
+        .mount(&app.email_server)
+        .await;
+    let worker = tokio::spawn(run_worker_until_stopped(app.configuration.clone()));
+    // The newsletter issue is going out - and stuck.
+    tokio::time::timeout(Duration::from_secs(10), newsletter_emails.recv())
+        .await
+        .expect("The newsletter issue did not start going out.");
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Assert - The retry goes out while the newsletter emails are still in flight
+    let retried = tokio::time::timeout(Duration::from_secs(10), confirmation_emails.recv()).await;
+    let deliveries = count_newsletter_deliveries(&app).await;
+    worker.abort();
+    assert!(
+        retried.is_ok(),
+        "The confirmation email waited for the newsletter."
+    );
+    assert_eq!(deliveries, 0);
+}
+
+async fn count_newsletter_deliveries(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_deliveries"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count newsletter deliveries.")
+        .count
+}
+
+#[tokio::test]
+async fn the_queue_backlog_reports_the_pending_delivery_tasks() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.queue_backlog_threshold = 2).await;
+    for _ in 0..3 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.login().await;
+
+    let backlog = app.get_queue_backlog().await;
+    assert_eq!(backlog["depth"], 0);
+    assert_eq!(
+        backlog["oldest_pending_age_seconds"],
+        serde_json::Value::Null
+    );
+    assert_eq!(backlog["status"], "ok");
+
+    // Act - Enqueue one delivery task per confirmed subscriber, without dispatching them
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert
+    let backlog = app.get_queue_backlog().await;
+    assert_eq!(backlog["depth"], 3);
+    assert!(backlog["oldest_pending_age_seconds"].as_i64().unwrap() >= 0);
+    assert_eq!(backlog["status"], "degraded");
+}
+
+#[tokio::test]
+async fn the_newsletter_listing_reports_the_delivery_progress_of_each_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish an issue
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let listing = app.get_newsletter_issues().await;
+    let issue = &listing["issues"][0];
+    assert_eq!(issue["title"], "Newsletter title");
+    assert_eq!(issue["status"], "in_progress");
+    assert_eq!(issue["pending"], 1);
+    assert_eq!(issue["delivered"], 0);
+
+    // Act - Part 2 - Run the worker
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let listing = app.get_newsletter_issues().await;
+    assert_eq!(listing["issues"].as_array().unwrap().len(), 1);
+    let issue = &listing["issues"][0];
+    assert_eq!(issue["status"], "completed");
+    assert_eq!(issue["pending"], 0);
+    assert_eq!(issue["delivered"], 1);
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_listed_until_they_are_due() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    // Postgres timestamps have a lower precision than `chrono`'s.
+    let from_now = |delay: chrono::Duration| {
+        (chrono::Utc::now() + delay).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    };
+    let in_a_day = from_now(chrono::Duration::days(1));
+    let in_a_week = from_now(chrono::Duration::weeks(1));
+
+    // Act - Schedule two issues, and publish a third one right away
+    for (title, scheduled_for) in [
+        ("Next week", in_a_week.clone()),
+        ("Tomorrow", in_a_day.clone()),
+        ("Right now", String::new()),
+    ] {
+        let newsletter_request_body = serde_json::json!({
+            "title": title,
+            "text_content" : format!("{title} as plain text"),
+            "html_content" : format!("<p>{title} as HTML</p>"),
+            "scheduled_for": scheduled_for,
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        });
+        let response = app.post_publish_newsletter(&newsletter_request_body).await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+    }
+
+    // Assert
+    let at = |date_time: &str| chrono::DateTime::parse_from_rfc3339(date_time).unwrap();
+    let scheduled = app.get_scheduled_newsletter_issues().await;
+    let scheduled: Vec<_> = scheduled["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|issue| {
+            (
+                issue["title"].as_str().unwrap().to_owned(),
+                at(issue["scheduled_for"].as_str().unwrap()),
+            )
+        })
+        .collect();
+    assert_eq!(
+        scheduled,
+        vec![
+            ("Tomorrow".to_owned(), at(&in_a_day)),
+            ("Next week".to_owned(), at(&in_a_week)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn issues_cannot_be_scheduled_in_the_past() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "scheduled_for": (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339(),
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(count_newsletter_issues(&app).await, 0);
+}
+
+#[tokio::test]
+async fn tasks_with_a_stale_claim_are_requeued() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Simulate a worker that claimed the task an hour ago and crashed before completing it
+    sqlx::query!("UPDATE issue_delivery_queue SET claimed_at = now() - interval '1 hour'")
+        .execute(&app.db_pool)
+        .await
+        .expect("Failed to backdate the claim.");
+    // The confirmation email of the subscriber has been received already.
+    let n_received = app.email_server.received_requests().await.unwrap().len();
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        n_received
+    );
+
+    // Act
+    let n_requeued = requeue_stale_claims(&app.db_pool, Duration::from_secs(600))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(n_requeued, 1);
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that the requeued task has been delivered
+}
+
+#[tokio::test]
+async fn emails_above_the_warm_up_daily_limit_are_deferred_to_the_next_day() {
+    // Arrange
+    let today = chrono::Utc::now().date_naive();
+    let app = spawn_app_with(|c| {
+        c.warm_up = Some(WarmUpSettings {
+            started_on: today.format("%Y-%m-%d").to_string(),
+            daily_limits: vec![1, 100],
+        })
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let deferred = sqlx::query!("SELECT execute_after FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch the queued tasks.");
+    assert_eq!(deferred.len(), 1);
+    let next_day = today.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap();
+    assert_eq!(deferred[0].execute_after.naive_utc(), next_day);
+    // Mock verifies on Drop that only one email went out today
+}
+
+#[tokio::test]
+async fn deliveries_during_quiet_hours_are_deferred_to_their_end() {
+    // Arrange
+    let now = chrono::Utc::now();
+    let app = spawn_app_with(|c| {
+        c.quiet_hours = Some(QuietHoursSettings {
+            start: (now - chrono::Duration::hours(2))
+                .format("%H:%M")
+                .to_string(),
+            end: (now + chrono::Duration::hours(1))
+                .format("%H:%M")
+                .to_string(),
+            time_zone: "UTC".into(),
+        })
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let deferred = sqlx::query!("SELECT execute_after FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch the queued tasks.");
+    assert_eq!(deferred.len(), 1);
+    // The window ends at the start of the minute an hour from now.
+    let execute_after = deferred[0].execute_after;
+    assert!(execute_after > now + chrono::Duration::minutes(59));
+    assert!(execute_after <= now + chrono::Duration::hours(1));
+    // Mock verifies on Drop that nothing has been sent during quiet hours
+}
+
+#[tokio::test]
+async fn quiet_hours_are_in_the_time_zone_of_the_subscriber_if_known() {
+    // Arrange
+    let now = chrono::Utc::now();
+    // UTC+5 - it is quiet hours there, not in UTC.
+    let subscriber_time_zone = "Etc/GMT-5";
+    let app = spawn_app_with(|c| {
+        c.quiet_hours = Some(QuietHoursSettings {
+            start: (now + chrono::Duration::hours(3))
+                .format("%H:%M")
+                .to_string(),
+            end: (now + chrono::Duration::hours(6))
+                .format("%H:%M")
+                .to_string(),
+            time_zone: "UTC".into(),
+        })
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET time_zone = $1
+        WHERE id = (SELECT id FROM subscriptions ORDER BY subscribed_at LIMIT 1)
+        "#,
+        subscriber_time_zone
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to set the time zone of the subscriber.");
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let deferred = sqlx::query!(
+        r#"
+        SELECT q.execute_after, s.time_zone
+        FROM issue_delivery_queue q
+        JOIN subscriptions s ON s.email = q.subscriber_email
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch the queued tasks.");
+    assert_eq!(deferred.len(), 1);
+    assert_eq!(deferred[0].time_zone.as_deref(), Some(subscriber_time_zone));
+    // The window ends at the start of the minute an hour from now.
+    let execute_after = deferred[0].execute_after;
+    assert!(execute_after > now + chrono::Duration::minutes(59));
+    assert!(execute_after <= now + chrono::Duration::hours(1));
+    // Mock verifies on Drop that the other subscriber got the issue right away
+}
+
+#[tokio::test]
+async fn quiet_hours_follow_daylight_saving_time() {
+    // Arrange
+    let app = spawn_app().await;
+    let quiet_hours = QuietHoursSettings {
+        start: "22:00".into(),
+        end: "07:00".into(),
+        time_zone: "Europe/Paris".into(),
+    };
+    let mut connection = app.db_pool.acquire().await.unwrap();
+    let utc = |datetime| {
+        chrono::DateTime::parse_from_rfc3339(datetime)
+            .unwrap()
+            .with_timezone(&chrono::Utc)
+    };
+    let test_cases = [
+        // 23:00 in Paris, in summer (UTC+2) and in winter (UTC+1).
+        ("2026-07-01T21:00:00Z", "2026-07-02T05:00:00Z"),
+        ("2026-01-15T22:00:00Z", "2026-01-16T06:00:00Z"),
+        // Clocks go back an hour on the night of 2026-10-25.
+        ("2026-10-24T21:00:00Z", "2026-10-25T06:00:00Z"),
+    ];
+
+    for (now, end) in test_cases {
+        // Act
+        let outcome = quiet_hours_end(&mut connection, &quiet_hours, "Europe/Paris", utc(now))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(
+            outcome,
+            Some(utc(end)),
+            "Unexpected end of quiet hours at {now}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn failed_deliveries_beyond_the_retry_budget_are_retried_much_later() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.retry_budget_per_minute = 2).await;
+    for _ in 0..5 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.login().await;
+
+    // Our email provider is down.
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(5)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - Every delivery is queued again: as many as the budget allows for a minute from now,
+    // the others for much later
+    let retried = sqlx::query!("SELECT execute_after, n_retries FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch the queued tasks.");
+    assert_eq!(retried.len(), 5);
+    assert!(retried.iter().all(|task| task.n_retries == 1));
+    let retried_soon = retried
+        .iter()
+        .filter(|task| task.execute_after < chrono::Utc::now() + chrono::Duration::minutes(2))
+        .count();
+    assert_eq!(retried_soon, 2);
+    assert!(retried
+        .iter()
+        .all(|task| task.execute_after > chrono::Utc::now()));
+    // Mock verifies on Drop that every delivery has been attempted once
+}
+
+#[tokio::test]
+async fn deliveries_are_given_up_on_after_too_many_retries() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.max_retries_per_delivery = 1).await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    // Our email provider is down.
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Act - The retry is due
+    sqlx::query!("UPDATE issue_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let queued = sqlx::query!(r#"SELECT COUNT(*) AS "queued!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.queued, 0);
+    // Mock verifies on Drop that the delivery has been attempted twice
+}
+
+#[tokio::test]
+async fn subscribers_who_got_an_issue_recently_are_skipped_by_the_next_one() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.frequency_cap_seconds = 86400).await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Publish two issues back-to-back
+    for title in ["First issue", "Second issue"] {
+        let newsletter_request_body = serde_json::json!({
+            "title": title,
+            "text_content" : format!("{title} as plain text"),
+            "html_content" : format!("<p>{title} as HTML</p>"),
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        });
+        let response = app.post_publish_newsletter(&newsletter_request_body).await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+        app.dispatch_all_pending_emails().await;
+    }
+
+    // Assert - The skip is recorded
+    let deliveries: Vec<(String, String)> = sqlx::query!(
+        r#"
+        SELECT i.title, d.status
+        FROM newsletter_deliveries d
+        JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+        ORDER BY i.title
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| (r.title, r.status))
+    .collect();
+    assert_eq!(
+        deliveries,
+        vec![
+            ("First issue".into(), "delivered".into()),
+            ("Second issue".into(), "skipped_frequency_cap".into()),
+        ]
+    );
+    // Mock verifies on Drop that we have sent a single newsletter email
+}
+
+#[tokio::test]
+async fn subscribers_who_just_confirmed_get_the_issue_after_the_configured_delay() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.new_subscriber_delay_seconds = 86400).await;
+    create_confirmed_subscriber(&app).await;
+    // They confirmed two days ago.
+    sqlx::query!(
+        "UPDATE subscription_events SET occurred_at = now() - interval '2 days' \
+        WHERE event_type = 'confirmed'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let older_subscriber = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(method("POST"))
+        .and(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&email_requests.last().unwrap().body).unwrap();
+    assert_eq!(body["To"], older_subscriber);
+    let deferred = sqlx::query!("SELECT subscriber_email, execute_after FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch the queued tasks.");
+    assert_eq!(deferred.len(), 1);
+    assert_ne!(deferred[0].subscriber_email, older_subscriber);
+    assert!(deferred[0].execute_after > chrono::Utc::now() + chrono::Duration::hours(23));
+    // Mock verifies on Drop that only the older subscriber got the issue straight away
+}
+
+#[tokio::test]
+async fn publishing_an_identical_issue_again_requires_force() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let mut newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 1 - Publish the same content under a new idempotency key
+    newsletter_request_body["idempotency_key"] = uuid::Uuid::new_v4().to_string().into();
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert - Part 1
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("An identical newsletter issue has been published recently."));
+    assert_eq!(count_newsletter_issues(&app).await, 1);
+
+    // Act - Part 2 - Force it through
+    newsletter_request_body["force"] = "true".into();
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert - Part 2
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(
+        "<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>"
+    ));
+    assert_eq!(count_newsletter_issues(&app).await, 2);
+}
+
+#[tokio::test]
+async fn publishing_again_within_the_cooldown_requires_force() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.publish_cooldown_seconds = 3600).await;
+    app.login().await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 1 - Publish another issue straight away
+    let mut newsletter_request_body = serde_json::json!({
+        "title": "Another newsletter title",
+        "text_content": "Another newsletter body as plain text",
+        "html_content": "<p>Another newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert - Part 1
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("You published a newsletter issue recently."));
+    assert_eq!(count_newsletter_issues(&app).await, 1);
+
+    // Act - Part 2 - Force it through
+    newsletter_request_body["force"] = "true".into();
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert - Part 2
+    assert_eq!(count_newsletter_issues(&app).await, 2);
+}
+
+#[tokio::test]
+async fn publishing_to_fewer_confirmed_subscribers_than_the_minimum_requires_force() {
+    // Arrange
+    let app = spawn_app_with(|c| c.newsletter.min_confirmed_to_publish = Some(2)).await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
+        .mount(&app.email_server)
         .await;
 
-    app.post_subscriptions(body.into())
-        .await
-        .error_for_status()
-        .unwrap();
+    // Act - Part 1 - Publish to the one confirmed subscriber
+    let mut newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
 
-    // We now inspect the requests received by the mock Postmark server to retrieve the confirmation
-    // link and return it.
-    let email_request = &app
-        .email_server
-        .received_requests()
+    // Assert - Part 1
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("would only reach 1 confirmed subscriber(s)"));
+    assert_eq!(count_newsletter_issues(&app).await, 0);
+
+    // Act - Part 2 - Force it through
+    newsletter_request_body["force"] = "true".into();
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert - Part 2
+    assert_eq!(count_newsletter_issues(&app).await, 1);
+}
+
+async fn count_newsletter_issues(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
         .await
-        .unwrap()
-        .pop()
-        .unwrap();
+        .expect("Failed to count newsletter issues.")
+        .count
+}
+
+#[tokio::test]
+async fn idempotency_keys_used_to_publish_are_listed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": &idempotency_key
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    // Replay the request
+    app.post_publish_newsletter(&newsletter_request_body).await;
+
+    // Act
+    let html_page = app.get_idempotency_keys_html().await;
 
-    app.get_confirmation_links(&email_request)
+    // Assert
+    assert!(html_page.contains(&format!("<td>{idempotency_key}</td>")));
+    assert!(html_page.contains("<td>303</td>"));
+    assert!(html_page.contains("<td>1</td>"));
+    assert!(html_page.contains("[redacted]"));
 }
 
-async fn create_confirmed_subscriber(app: &TestApp) {
-    // We can then reuse the same helper and just add an extra step to actually call the confirmation
-    // link!
-    let confirmation_link = create_unconfirmed_subscriber(app).await;
+#[tokio::test]
+async fn the_idempotency_key_can_be_sent_as_a_header() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+    });
 
-    reqwest::get(confirmation_link.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
+    // Act
+    for _ in 0..2 {
+        let response = app
+            .post_publish_newsletter_with_idempotency_key_header(
+                &newsletter_request_body,
+                &idempotency_key,
+            )
+            .await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+    }
+
+    // Assert - The second request was a replay
+    assert_eq!(count_newsletter_issues(&app).await, 1);
 }
 
-#[ignore]
 #[tokio::test]
-async fn newsletters_are_delivered_to_confirmed_subscribers() {
+async fn an_invalid_idempotency_key_header_is_rejected_with_a_400() {
     // Arrange
     let app = spawn_app().await;
-    create_confirmed_subscriber(&app).await;
     app.login().await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+    });
 
-    Mock::given(method("POST"))
-        .and(path("/email"))
-        .respond_with(ResponseTemplate::new(200))
-        .expect(1)
-        .mount(&app.email_server)
+    // Act
+    let response = app
+        .post_publish_newsletter_with_idempotency_key_header(
+            &newsletter_request_body,
+            &"a".repeat(100),
+        )
         .await;
 
-    dbg!(&app.email_server.uri());
-    // Act - Part 1 - Submit newsletter form
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "Invalid Idempotency-Key header: The idempotency key must be shorter than 50 characters."
+    );
+    assert_eq!(count_newsletter_issues(&app).await, 0);
+}
+
+#[tokio::test]
+async fn the_idempotency_key_can_be_sent_in_the_body() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
     let newsletter_request_body = serde_json::json!({
         "title": "Newsletter title",
-        "text_content" : "Newsletter body as plain text",
-        "html_content" : "<p>Newsletter body as HTML</p>",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
         "idempotency_key": uuid::Uuid::new_v4().to_string()
     });
-    let response = app.post_publish_newsletter(&newsletter_request_body).await;
-    assert_is_redirect_to(&response, "/admin/newsletters");
 
-    // Act - Part 2 - Follow the redirect
-    let html_page = app.get_publish_newsletter_html().await;
-    assert!(html_page.contains(
-        "<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>"
-    ));
-    app.dispatch_all_pending_emails().await;
+    // Act
+    for _ in 0..2 {
+        let response = app.post_publish_newsletter(&newsletter_request_body).await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+    }
 
-    // Mock verifies on Drop that we have sent the newsletter email
+    // Assert - The second request was a replay
+    assert_eq!(count_newsletter_issues(&app).await, 1);
+}
+
+#[tokio::test]
+async fn the_idempotency_key_in_the_body_takes_precedence_over_the_header() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let body_key = uuid::Uuid::new_v4().to_string();
+    let header_key = uuid::Uuid::new_v4().to_string();
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": &body_key
+    });
+
+    // Act
+    let response = app
+        .post_publish_newsletter_with_idempotency_key_header(&newsletter_request_body, &header_key)
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert - Only the body key has been used
+    let html_page = app.get_idempotency_keys_html().await;
+    assert!(html_page.contains(&format!("<td>{body_key}</td>")));
+    assert!(!html_page.contains(&header_key));
 }
 
 /// # Basic Authentication
@@ -272,3 +1651,220 @@ async fn concurrent_form_submission_is_handled_gracefully() {
 
     // Mock verifies on Drop that we have sent the newsletter email **once**
 }
+
+#[tokio::test]
+async fn issues_are_sent_under_the_sender_name_of_the_subscriber_locale() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.email_client.sender_email = "newsletter@example.com".into();
+        c.email_client.sender_names = HashMap::from([
+            ("en".into(), "Our newsletter".into()),
+            ("fr".into(), "Notre bulletin".into()),
+        ]);
+    })
+    .await;
+    create_confirmed_subscriber_with_locale(&app, "fr").await;
+    create_confirmed_subscriber_with_locale(&app, "en").await;
+    app.login().await;
+    let received_before = app.email_server.received_requests().await.unwrap().len();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content" : "Newsletter body as plain text",
+        "html_content" : "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let locales: HashMap<String, String> = sqlx::query!("SELECT email, locale FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.email, r.locale.unwrap()))
+        .collect();
+    let requests = app.email_server.received_requests().await.unwrap();
+    let mut from_by_locale: Vec<(String, String)> = requests[received_before..]
+        .iter()
+        .map(|request| {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            let locale = locales[body["To"].as_str().unwrap()].clone();
+            (locale, body["From"].as_str().unwrap().to_owned())
+        })
+        .collect();
+    from_by_locale.sort();
+    assert_eq!(
+        from_by_locale,
+        vec![
+            (
+                "en".into(),
+                r#""Our newsletter" <newsletter@example.com>"#.into()
+            ),
+            (
+                "fr".into(),
+                r#""Notre bulletin" <newsletter@example.com>"#.into()
+            ),
+        ]
+    );
+}
+
+async fn publish_and_get_stored_html(app: &TestApp, html_content: &str) -> String {
+    app.login().await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": html_content,
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    sqlx::query!("SELECT html_content FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the stored issue.")
+        .html_content
+}
+
+#[tokio::test]
+async fn the_html_of_issues_is_stripped_of_the_tags_that_are_not_allowed() {
+    // Arrange
+    let html_content =
+        r#"<p>Hello<img src="https://example.com/logo.png"><script>alert(1)</script></p>"#;
+    let restrictive_app = spawn_app_with(|c| {
+        c.newsletter.allowed_html_tags = ["p".to_string()].into();
+    })
+    .await;
+    let permissive_app = spawn_app_with(|c| {
+        c.newsletter.allowed_html_tags = ["p".to_string(), "img".to_string()].into();
+        c.newsletter.allowed_html_attributes =
+            [("img".to_string(), ["src".to_string()].into())].into();
+    })
+    .await;
+
+    // Act
+    let restrictive_html = publish_and_get_stored_html(&restrictive_app, html_content).await;
+    let permissive_html = publish_and_get_stored_html(&permissive_app, html_content).await;
+
+    // Assert
+    assert_eq!("<p>Hello</p>", restrictive_html);
+    assert_eq!(
+        r#"<p>Hello<img src="https://example.com/logo.png"></p>"#,
+        permissive_html
+    );
+}
+
+#[tokio::test]
+async fn the_html_of_issues_is_sanitized_again_once_rendered() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.newsletter.allowed_html_tags = ["p".to_string()].into();
+    })
+    .await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Hello</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    // e.g. the issue was published before `<img>` was disallowed.
+    sqlx::query!(
+        r#"UPDATE newsletter_issues SET html_content = '<p>Hello {{ name }}<img src="https://example.com/logo.png"></p>'"#
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to update the stored issue.");
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // The first request is the confirmation email of the subscriber.
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&email_requests.last().unwrap().body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(html_body.starts_with("<p>Hello "));
+    assert!(!html_body.contains("<img"));
+}
+
+#[tokio::test]
+async fn cloning_a_past_issue_prefills_the_newsletter_form_without_publishing() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "The past issue",
+        "text_content": "The past issue as plain text",
+        "html_content": "<p>The past issue as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/newsletters/{}/clone",
+            &app.address, issue_id
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"value="The past issue""#));
+    assert!(html_page.contains("The past issue as plain text"));
+    assert!(html_page.contains("&lt;p&gt;The past issue as HTML&lt;&#x2F;p&gt;"));
+    assert!(!html_page.contains(newsletter_request_body["idempotency_key"].as_str().unwrap()));
+    assert_eq!(1, count_newsletter_issues(&app).await);
+}
+
+#[tokio::test]
+async fn cloning_an_unknown_issue_returns_a_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/newsletters/{}/clone",
+            &app.address,
+            uuid::Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(404, response.status().as_u16());
+}
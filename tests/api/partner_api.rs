@@ -0,0 +1,80 @@
+use crate::helpers::{spawn_app_with, store_subscriber, TestApp};
+use secrecy::Secret;
+
+const API_KEY: &str = "a-partner-api-key";
+
+async fn spawn_app_with_partner_key() -> TestApp {
+    spawn_app_with(|c| {
+        c.api.partner_api_keys = vec![Secret::new(API_KEY.into())];
+        c.api.page_size = 2;
+    })
+    .await
+}
+
+async fn get_subscribers_page(
+    app: &TestApp,
+    page: u32,
+    api_key: Option<&str>,
+) -> reqwest::Response {
+    let mut request = app
+        .api_client
+        .get(format!("{}/api/subscribers", &app.address))
+        .query(&[("page", page)]);
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    request.send().await.expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_api_key_are_rejected() {
+    // Arrange
+    let app = spawn_app_with_partner_key().await;
+
+    for api_key in [None, Some("not-a-partner-api-key")] {
+        // Act
+        let response = get_subscribers_page(&app, 1, api_key).await;
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+    }
+}
+
+#[tokio::test]
+async fn confirmed_subscribers_are_listed_a_page_at_a_time() {
+    // Arrange
+    let app = spawn_app_with_partner_key().await;
+    store_subscriber(&app, "ursula_le_guin@gmail.com", "Ursula").await;
+    store_subscriber(&app, "terry_pratchett@gmail.com", "Terry").await;
+    store_subscriber(&app, "octavia_butler@gmail.com", "Octavia").await;
+    store_subscriber(&app, "gone@gmail.com", "Gone").await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed' WHERE email = 'gone@gmail.com'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act - Part 1 - First page
+    let response = get_subscribers_page(&app, 1, Some(API_KEY)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["next_page"], 2);
+    let subscribers = body["subscribers"].as_array().unwrap();
+    assert_eq!(subscribers.len(), 2);
+    assert_eq!(subscribers[0]["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(subscribers[0]["name"], "Ursula");
+    assert!(subscribers[0]["confirmed_at"].is_null());
+    assert_eq!(subscribers[1]["email"], "terry_pratchett@gmail.com");
+
+    // Act - Part 2 - Last page
+    let response = get_subscribers_page(&app, 2, Some(API_KEY)).await;
+
+    // Assert
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["next_page"].is_null());
+    let subscribers = body["subscribers"].as_array().unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0]["email"], "octavia_butler@gmail.com");
+}
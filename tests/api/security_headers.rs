@@ -0,0 +1,67 @@
+use crate::helpers::{spawn_app, spawn_app_with};
+
+fn header<'a>(response: &'a reqwest::Response, name: &str) -> Option<&'a str> {
+    response
+        .headers()
+        .get(name)
+        .map(|value| value.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn the_login_page_carries_the_security_headers() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.security_headers.content_security_policy = "default-src 'self'; style-src 'self'".into();
+    })
+    .await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(header(&response, "X-Frame-Options"), Some("DENY"));
+    assert_eq!(
+        header(&response, "Content-Security-Policy"),
+        Some("default-src 'self'; style-src 'self'")
+    );
+    assert_eq!(header(&response, "X-Content-Type-Options"), Some("nosniff"));
+    assert_eq!(header(&response, "Referrer-Policy"), Some("same-origin"));
+}
+
+#[tokio::test]
+async fn admin_pages_carry_the_security_headers() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(header(&response, "X-Frame-Options"), Some("DENY"));
+    assert!(header(&response, "Content-Security-Policy").is_some());
+}
+
+#[tokio::test]
+async fn public_pages_do_not_carry_the_security_headers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/widget.js", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(header(&response, "X-Frame-Options"), None);
+}
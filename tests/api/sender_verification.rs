@@ -0,0 +1,76 @@
+use secrecy::Secret;
+use wiremock::matchers::{header_exists, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use zero2prod::configuration::{get_configuration, SenderVerification};
+use zero2prod::startup::Application;
+
+async fn build_application(
+    email_server: &MockServer,
+    verify_sender_on_startup: SenderVerification,
+) -> Result<Application, anyhow::Error> {
+    let mut c = get_configuration().expect("Failed to read configuration.");
+    c.application.port = 0;
+    c.email_client.base_url = email_server.uri();
+    c.email_client.verify_sender_on_startup = verify_sender_on_startup;
+    c.email_client.account_token = Some(Secret::new("my-account-token".into()));
+    Application::build(c).await
+}
+
+async fn mount_unverified_sender(email_server: &MockServer) {
+    let signatures = serde_json::json!({
+        "TotalCount": 1,
+        "SenderSignatures": [
+            {"EmailAddress": "someone-else@gmail.com", "Confirmed": true}
+        ]
+    });
+    Mock::given(header_exists("X-Postmark-Account-Token"))
+        .and(path("/senders"))
+        .and(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(signatures))
+        .expect(1)
+        .mount(email_server)
+        .await;
+}
+
+#[tokio::test]
+async fn startup_fails_for_an_unverified_sender_when_configured_to_fail() {
+    // Arrange
+    let email_server = MockServer::start().await;
+    mount_unverified_sender(&email_server).await;
+
+    // Act
+    let outcome = build_application(&email_server, SenderVerification::Fail).await;
+
+    // Assert
+    assert!(outcome.is_err());
+}
+
+#[tokio::test]
+async fn startup_only_warns_for_an_unverified_sender_when_configured_to_warn() {
+    // Arrange
+    let email_server = MockServer::start().await;
+    mount_unverified_sender(&email_server).await;
+
+    // Act
+    let outcome = build_application(&email_server, SenderVerification::Warn).await;
+
+    // Assert
+    assert!(outcome.is_ok());
+}
+
+#[tokio::test]
+async fn sender_is_not_verified_when_verification_is_disabled() {
+    // Arrange
+    let email_server = MockServer::start().await;
+    Mock::given(path("/senders"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&email_server)
+        .await;
+
+    // Act
+    let outcome = build_application(&email_server, SenderVerification::Disabled).await;
+
+    // Assert
+    assert!(outcome.is_ok());
+}
@@ -0,0 +1,30 @@
+use crate::helpers::spawn_app_with;
+use secrecy::Secret;
+use tokio::net::TcpListener;
+
+/// Stands in for a Redis instance that goes down right after the application has started: it
+/// accepts the connection opened by the session store at startup, then closes it and stops
+/// listening.
+async fn dead_redis_uri() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = listener.accept().await;
+        // Both the connection and the listener are dropped here.
+    });
+    format!("redis://{address}")
+}
+
+#[tokio::test]
+async fn admin_requests_get_a_503_if_the_session_store_is_unavailable() {
+    // Arrange
+    let redis_uri = dead_redis_uri().await;
+    let app = spawn_app_with(|c| c.redis_uri = Secret::new(redis_uri)).await;
+
+    // Act
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 503);
+    assert!(response.headers().get("Retry-After").is_some());
+}
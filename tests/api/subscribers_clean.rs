@@ -0,0 +1,73 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, store_subscriber};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_clean_the_subscriber_list() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_clean_subscribers().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn cleaning_flags_exactly_the_subscribers_with_an_invalid_address() {
+    // Arrange
+    let app = spawn_app().await;
+    store_subscriber(&app, "ursula_le_guin@gmail.com", "Ursula").await;
+    // Stored before our validation got stricter.
+    store_subscriber(&app, "terry_pratchett.gmail.com", "Terry").await;
+    app.login().await;
+
+    // Act
+    let response = app.post_clean_subscribers().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["invalidated"], 1);
+    let statuses: Vec<(String, String)> =
+        sqlx::query!("SELECT email, status FROM subscriptions ORDER BY email")
+            .fetch_all(&app.db_pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.email, r.status))
+            .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ("terry_pratchett.gmail.com".into(), "invalid".into()),
+            ("ursula_le_guin@gmail.com".into(), "confirmed".into()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn cleaning_flags_role_accounts_and_disposable_domains_if_configured() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.list_cleaning.flag_role_accounts = true;
+        c.list_cleaning.disposable_domains = vec!["mailinator.com".into()];
+    })
+    .await;
+    store_subscriber(&app, "ursula_le_guin@gmail.com", "Ursula").await;
+    store_subscriber(&app, "admin@gmail.com", "Admin").await;
+    store_subscriber(&app, "terry@Mailinator.com", "Terry").await;
+    app.login().await;
+
+    // Act
+    let response = app.post_clean_subscribers().await;
+
+    // Assert
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["invalidated"], 2);
+    let confirmed = sqlx::query!("SELECT email FROM subscriptions WHERE status = 'confirmed'")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(confirmed.len(), 1);
+    assert_eq!(confirmed[0].email, "ursula_le_guin@gmail.com");
+}
@@ -0,0 +1,57 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, store_subscriber};
+use sha2::{Digest, Sha256};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_subscribers_export().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_export_is_a_timestamped_csv_attachment_with_a_checksum() {
+    // Arrange
+    let app = spawn_app().await;
+    store_subscriber(&app, "ursula_le_guin@gmail.com", "Le Guin, Ursula").await;
+    store_subscriber(&app, "terry_pratchett@gmail.com", "Terry").await;
+    app.login().await;
+
+    // Act
+    let response = app.get_subscribers_export().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let content_disposition = response
+        .headers()
+        .get("Content-Disposition")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+    let today = chrono::Utc::now().format("%Y%m%d").to_string();
+    assert!(content_disposition.starts_with("attachment; filename=\"subscribers-"));
+    assert!(content_disposition.contains(&today));
+    assert!(content_disposition.ends_with(".csv\""));
+    let checksum = response
+        .headers()
+        .get("X-Checksum-SHA256")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    let body = response.text().await.unwrap();
+    let expected_checksum: String = Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    assert_eq!(checksum, expected_checksum);
+    assert!(body.starts_with("email,name,status,subscribed_at\r\n"));
+    assert!(body.contains("ursula_le_guin@gmail.com,\"Le Guin, Ursula\",confirmed,"));
+    assert!(body.contains("terry_pratchett@gmail.com,Terry,confirmed,"));
+}
@@ -0,0 +1,63 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, store_subscriber};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_search_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_subscribers_search("ursula").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscriber_search_returns_case_insensitive_substring_matches() {
+    // Arrange
+    let app = spawn_app().await;
+    store_subscriber(&app, "ursula_le_guin@gmail.com", "Ursula").await;
+    store_subscriber(&app, "URSULA.k@example.com", "Ursula K").await;
+    store_subscriber(&app, "terry_pratchett@gmail.com", "Terry").await;
+    app.login().await;
+
+    // Act
+    let response = app.get_subscribers_search("ursula").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("ursula_le_guin@gmail.com"));
+    assert!(html_page.contains("URSULA.k@example.com"));
+    assert!(!html_page.contains("terry_pratchett@gmail.com"));
+}
+
+#[tokio::test]
+async fn subscriber_search_treats_wildcards_literally() {
+    // Arrange
+    let app = spawn_app().await;
+    store_subscriber(&app, "ursula_le_guin@gmail.com", "Ursula").await;
+    store_subscriber(&app, "ursulaxle@gmail.com", "Ursula X").await;
+    app.login().await;
+
+    // Act
+    let response = app.get_subscribers_search("ursula_le").await;
+
+    // Assert
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("ursula_le_guin@gmail.com"));
+    assert!(!html_page.contains("ursulaxle@gmail.com"));
+}
+
+#[tokio::test]
+async fn subscriber_search_rejects_a_query_that_is_too_short() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app.get_subscribers_search("ur").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
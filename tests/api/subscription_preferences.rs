@@ -0,0 +1,57 @@
+use crate::helpers::spawn_app;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn the_manage_data_link_of_the_confirmation_email_shows_the_subscriber_data() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    app.post_subscriptions("name=terry&email=terry_pratchett%40gmail.com".into())
+        .await;
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let links = app.get_manage_data_links(&email_requests[0]);
+    assert_eq!(links.html, links.plain_text);
+
+    // Act
+    let response = reqwest::get(links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("ursula_le_guin@gmail.com"));
+    assert!(!html_page.contains("terry_pratchett@gmail.com"));
+}
+
+#[tokio::test]
+async fn a_tampered_manage_data_link_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let mut link = app.get_manage_data_links(email_request).plain_text;
+    let token = link.query_pairs().next().unwrap().1.into_owned();
+    let (_, expiry_and_tag) = token.split_once('.').unwrap();
+    let tampered = format!("{}.{expiry_and_tag}", uuid::Uuid::new_v4());
+    link.query_pairs_mut()
+        .clear()
+        .append_pair("token", &tampered);
+
+    // Act
+    let response = reqwest::get(link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -1,5 +1,5 @@
-use crate::helpers::spawn_app;
-use wiremock::matchers::{method, path};
+use crate::helpers::{spawn_app, spawn_app_with};
+use wiremock::matchers::{any, method, path};
 use wiremock::{Mock, ResponseTemplate};
 
 /// # Errors
@@ -54,10 +54,11 @@ async fn subscribe_returns_a_200_for_valid_form_data() {
 }
 
 #[tokio::test]
-async fn subscribe_persists_the_new_subscriber() {
+async fn subscribe_renders_a_success_page_by_default() {
     // Arrange
     let app = spawn_app().await;
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -65,74 +66,150 @@ async fn subscribe_persists_the_new_subscriber() {
         .await;
 
     // Act
-    app.post_subscriptions(body.into()).await;
+    let response = app.post_subscriptions(body.into()).await;
 
     // Assert
-    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
-        .fetch_one(&app.db_pool)
-        .await
-        .expect("Failed to fetch saved subscription.");
+    assert_eq!(200, response.status().as_u16());
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Check your inbox to confirm your subscription."));
+}
 
-    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
-    assert_eq!(saved.name, "le guin");
-    assert_eq!(saved.status, "pending_confirmation");
+#[tokio::test]
+async fn subscribe_redirects_to_the_configured_success_page() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.success_redirect = Some("https://example.com/thanks".into());
+        c.subscriptions.allowed_redirect_hosts = vec!["example.com".into()];
+    })
+    .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(302, response.status().as_u16());
+    assert_eq!(
+        response.headers().get("Location").unwrap(),
+        "https://example.com/thanks"
+    );
 }
 
 #[tokio::test]
-async fn subscribe_returns_a_400_when_data_is_missing() {
+async fn subscribe_returns_a_400_when_consent_is_required_but_missing() {
     // Arrange
-    let app = spawn_app().await;
+    let app = spawn_app_with(|c| c.subscriptions.require_consent = true).await;
     let test_cases = vec![
-        ("name=le%20guin", "missing the mail"),
-        ("email=ursula_le_%40gmail.com", "missing the name"),
-        ("", "missing both name and email"),
+        (
+            "name=le%20guin&email=ursula_le_guin%40gmail.com",
+            "missing consent",
+        ),
+        (
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&consent=false",
+            "declined consent",
+        ),
     ];
 
-    for (invalid_body, error_message) in test_cases {
+    for (body, description) in test_cases {
         // Act
-        let response = app.post_subscriptions(invalid_body.into()).await;
+        let response = app.post_subscriptions(body.into()).await;
 
         // Assert
         assert_eq!(
             400,
             response.status().as_u16(),
-            // Additional customised error message on test failure
-            "The API did not fail with 400 Bad Request when the payload was {}.",
-            error_message
-        )
+            "The API did not fail with 400 Bad Request when the payload had {}.",
+            description
+        );
     }
 }
 
 #[tokio::test]
-async fn subscribe_returns_a_400_when_fields_are_present_but_invalid() {
+async fn subscribe_stores_the_consent_timestamp_when_consent_is_required() {
     // Arrange
-    let app = spawn_app().await;
+    let app = spawn_app_with(|c| c.subscriptions.require_consent = true).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&consent=true";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT consented_at FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert!(saved.consented_at.is_some());
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_400_for_aliases_and_role_accounts_when_blocked() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.block_plus_addressing = true;
+        c.subscriptions.block_role_accounts = true;
+    })
+    .await;
     let test_cases = vec![
-        ("name=&email=coolkrishna31%40gmail.com", "empty name"),
-        ("name=Ursula&email=", "empty email"),
-        ("name=Ursula&email=definitely-not-an-email", "invalid email"),
+        ("name=le%20guin&email=user%2Btag%40x.com", "a plus address"),
+        ("name=le%20guin&email=admin%40x.com", "a role account"),
     ];
 
     for (body, description) in test_cases {
-        //Act
+        // Act
         let response = app.post_subscriptions(body.into()).await;
 
         // Assert
         assert_eq!(
             400,
             response.status().as_u16(),
-            "The API did not return a 200 OK when the payload was {}.",
+            "The API did not fail with 400 Bad Request when the payload had {}.",
             description
         );
     }
 }
 
 #[tokio::test]
-async fn subscribe_sends_a_confirmation_email_for_valid_data() {
+async fn subscribe_rejects_new_subscribers_once_their_domain_is_full() {
     // Arrange
-    let app = spawn_app().await;
-    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let app = spawn_app_with(|c| c.subscriptions.max_per_domain = Some(2)).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let mut statuses = Vec::new();
+    for local_part in ["first", "second", "third"] {
+        let body = format!("name=le%20guin&email={local_part}%40same.com");
+        statuses.push(app.post_subscriptions(body).await.status().as_u16());
+    }
+    let other_domain = app
+        .post_subscriptions("name=le%20guin&email=first%40other.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(statuses, vec![200, 200, 400]);
+    assert_eq!(other_domain.status().as_u16(), 200);
+}
 
+#[tokio::test]
+async fn submissions_filling_in_the_honeypot_are_ignored() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.honeypot_field = Some("website".into())).await;
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -141,23 +218,107 @@ async fn subscribe_sends_a_confirmation_email_for_valid_data() {
         .await;
 
     // Act
-    app.post_subscriptions(body.into()).await;
+    let bot = app
+        .post_subscriptions("name=bot&email=bot%40spam.com&website=https%3A%2F%2Fspam.com".into())
+        .await;
+    let human = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com&website=".into())
+        .await;
 
     // Assert
-    // Mock asserts on drop
+    assert_eq!(bot.status().as_u16(), 200);
+    assert_eq!(human.status().as_u16(), 200);
+    let emails: Vec<String> = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.")
+        .into_iter()
+        .map(|r| r.email)
+        .collect();
+    assert_eq!(emails, vec!["ursula_le_guin@gmail.com"]);
 }
 
 #[tokio::test]
-async fn subscribe_sends_a_confirmation_email_with_a_link() {
+async fn a_double_submit_is_only_processed_once() {
     // Arrange
     let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
+    // Act
+    let first = app.post_subscriptions(body.into()).await;
+    let second = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert_eq!(saved.len(), 1);
+    // Mock verifies on Drop that we have sent a single confirmation email
+}
+
+#[tokio::test]
+async fn subscribe_accepts_aliases_and_role_accounts_by_default() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for body in [
+        "name=le%20guin&email=user%2Btag%40x.com",
+        "name=le%20guin&email=admin%40x.com",
+    ] {
+        // Act
+        let response = app.post_subscriptions(body.into()).await;
+
+        // Assert
+        assert_eq!(200, response.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn subscribe_accepts_submissions_with_or_without_consent_when_not_required() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "name=terry&email=terry_pratchett%40gmail.com&consent=true",
+    ];
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for body in test_cases {
+        // Act
+        let response = app.post_subscriptions(body.into()).await;
+
+        // Assert
+        assert_eq!(200, response.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn subscribe_persists_the_new_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
-        // We are not setting an expectation here anymore. The test is focused on another aspect of
-        // the app behavior.
         .mount(&app.email_server)
         .await;
 
@@ -165,32 +326,765 @@ async fn subscribe_sends_a_confirmation_email_with_a_link() {
     app.post_subscriptions(body.into()).await;
 
     // Assert
-    let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
 
-    // The two links should be identical
-    assert_eq!(confirmation_links.html, confirmation_links.plain_text);
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "le guin");
+    assert_eq!(saved.status, "pending_confirmation");
 }
 
 #[tokio::test]
-async fn subscribe_fails_if_there_is_a_fatal_database_error() {
+async fn subscribers_must_confirm_their_email_with_double_opt_in() {
     // Arrange
-    let app = spawn_app().await;
-    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
-    // Sabotage the database
-    // Break `subscriptions` instead of `subscription_tokens`
-    sqlx::query!("ALTER TABLE subscriptions DROP COLUMN email;",)
+    let app = spawn_app_with(|c| c.subscriptions.double_opt_in = true).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "pending_confirmation");
+    // Mock asserts on drop that the confirmation email has been sent
+}
+
+#[tokio::test]
+async fn subscribers_are_confirmed_right_away_without_double_opt_in() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.double_opt_in = false).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Your subscription is confirmed."));
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions",)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+    // Mock asserts on drop that no confirmation email has been sent
+}
+
+#[tokio::test]
+async fn double_opt_in_can_be_turned_off_in_the_database() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.double_opt_in = true).await;
+    sqlx::query!("INSERT INTO feature_flags (name, enabled) VALUES ('double_opt_in', false)")
         .execute(&app.db_pool)
         .await
         .unwrap();
-    // sqlx::query!("ALTER TABLE subscription_tokens DROP COLUMN subscription_token;",)
-    //     .execute(&app.db_pool)
-    //     .await
-    //     .unwrap();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
 
     // Act
-    let response = app.post_subscriptions(body.into()).await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
 
     // Assert
-    assert_eq!(response.status().as_u16(), 500);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn double_opt_in_falls_back_to_its_default_if_overrides_cannot_be_checked() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.double_opt_in = true).await;
+    // Sabotage the overrides
+    sqlx::query!("ALTER TABLE feature_flags DROP COLUMN enabled")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_400_when_data_is_missing() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("name=le%20guin", "missing the mail"),
+        ("email=ursula_le_%40gmail.com", "missing the name"),
+        ("", "missing both name and email"),
+    ];
+
+    for (invalid_body, error_message) in test_cases {
+        // Act
+        let response = app.post_subscriptions(invalid_body.into()).await;
+
+        // Assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            // Additional customised error message on test failure
+            "The API did not fail with 400 Bad Request when the payload was {}.",
+            error_message
+        )
+    }
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_400_when_fields_are_present_but_invalid() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("name=&email=coolkrishna31%40gmail.com", "empty name"),
+        ("name=Ursula&email=", "empty email"),
+        ("name=Ursula&email=definitely-not-an-email", "invalid email"),
+    ];
+
+    for (body, description) in test_cases {
+        //Act
+        let response = app.post_subscriptions(body.into()).await;
+
+        // Assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return a 200 OK when the payload was {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn browsers_are_shown_the_error_page_with_the_invalid_fields() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept", "text/html,application/xhtml+xml")
+        .body("name=le%20guin&email=definitely-not-an-email")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("We could not subscribe you"));
+    assert!(html_page.contains("email: definitely-not-an-email is not a valid subscriber email."));
+}
+
+#[tokio::test]
+async fn other_clients_are_not_shown_the_error_page() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=definitely-not-an-email".into())
+        .await;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+    let body = response.text().await.unwrap();
+    assert!(!body.contains("We could not subscribe you"));
+}
+
+#[tokio::test]
+async fn subscribe_sends_a_confirmation_email_for_valid_data() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+
+    // Assert
+    // Mock asserts on drop
+}
+
+#[tokio::test]
+async fn failed_confirmation_emails_are_retried_then_flagged() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.confirmation_retries = 2;
+        c.subscriptions.confirmation_retry_backoff_seconds = 0;
+    })
+    .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    // The first attempt, then two retries.
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+    app.dispatch_all_confirmation_retries().await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT id, confirmation_failed FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert!(saved.confirmation_failed);
+    let queued = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM confirmation_email_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(0, queued.count);
+
+    app.login().await;
+    let html_page = app.get_subscriber_details_html(saved.id).await;
+    assert!(html_page.contains("The confirmation email could not be sent, even after retrying."));
+}
+
+#[tokio::test]
+async fn subscribe_sends_a_confirmation_email_with_a_link() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        // We are not setting an expectation here anymore. The test is focused on another aspect of
+        // the app behavior.
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // The two links should be identical
+    assert_eq!(confirmation_links.html, confirmation_links.plain_text);
+}
+
+#[tokio::test]
+async fn confirmation_email_includes_an_app_link_if_configured() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.app_link_template =
+            Some("zero2prod://confirm?subscription_token={subscription_token}".into());
+    })
+    .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    for content in [&body["HtmlBody"], &body["TextBody"]] {
+        let content = content.as_str().unwrap();
+        assert!(content.contains("/subscriptions/confirm?subscription_token="));
+        assert!(content.contains("zero2prod://confirm?subscription_token="));
+    }
+}
+
+#[tokio::test]
+async fn subscribe_fails_if_there_is_a_fatal_database_error() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    // Sabotage the database
+    // Break `subscriptions` instead of `subscription_tokens`
+    sqlx::query!("ALTER TABLE subscriptions DROP COLUMN email;",)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    // sqlx::query!("ALTER TABLE subscription_tokens DROP COLUMN subscription_token;",)
+    //     .execute(&app.db_pool)
+    //     .await
+    //     .unwrap();
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 500);
+}
+
+#[tokio::test]
+async fn validate_returns_a_200_for_valid_form_data_without_subscribing() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions_validate(body.into()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body, serde_json::json!({ "valid": true }));
+
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn validate_returns_a_422_with_the_errors_of_each_invalid_field() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("name=&email=ursula_le_guin%40gmail.com", vec!["name"]),
+        ("name=Ursula&email=", vec!["email"]),
+        (
+            "name=%3Cscript%3E&email=definitely-not-an-email",
+            vec!["email", "name"],
+        ),
+    ];
+
+    for (body, invalid_fields) in test_cases {
+        // Act
+        let response = app.post_subscriptions_validate(body.into()).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "The API did not return a 422 Unprocessable Entity when the payload was {body}."
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["valid"], false);
+        let errors = body["errors"].as_object().unwrap();
+        assert_eq!(
+            errors.keys().map(String::as_str).collect::<Vec<_>>(),
+            invalid_fields
+        );
+    }
+}
+
+#[tokio::test]
+async fn a_household_signup_subscribes_the_valid_addresses_and_reports_the_invalid_ones() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = serde_urlencoded::to_string([
+        ("name", "le guin"),
+        (
+            "emails",
+            "ursula@example.com, not-an-email, theodora@example.com",
+        ),
+    ])
+    .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions_household(body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let summary: serde_json::Value = response.json().await.unwrap();
+    let results = summary["results"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["email"], "ursula@example.com");
+    assert_eq!(results[0]["status"], "pending_confirmation");
+    assert_eq!(results[1]["email"], "not-an-email");
+    assert_eq!(results[1]["status"], "invalid");
+    assert!(results[1]["error"].is_string());
+    assert_eq!(results[2]["email"], "theodora@example.com");
+    assert_eq!(results[2]["status"], "pending_confirmation");
+
+    let saved = sqlx::query!("SELECT email FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    let emails: Vec<_> = saved.into_iter().map(|r| r.email).collect();
+    assert_eq!(emails, ["theodora@example.com", "ursula@example.com"]);
+}
+
+#[tokio::test]
+async fn household_signups_filling_in_the_honeypot_are_ignored() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.honeypot_field = Some("website".into())).await;
+    let body = serde_urlencoded::to_string([
+        ("name", "bot"),
+        ("emails", "bot@spam.com, another-bot@spam.com"),
+        ("website", "https://spam.com"),
+    ])
+    .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions_household(body).await;
+
+    // Assert - Bots are told they subscribed
+    assert_eq!(response.status().as_u16(), 200);
+    let summary: serde_json::Value = response.json().await.unwrap();
+    let results = summary["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results
+        .iter()
+        .all(|r| r["status"] == "pending_confirmation"));
+    let saved = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count saved subscriptions.");
+    assert_eq!(saved.count, 0);
+}
+
+#[tokio::test]
+async fn a_double_submit_of_a_household_signup_is_only_processed_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = serde_urlencoded::to_string([
+        ("name", "le guin"),
+        ("emails", "ursula@example.com, theodora@example.com"),
+    ])
+    .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions_household(body.clone()).await;
+    let response = app.post_subscriptions_household(body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let summary: serde_json::Value = response.json().await.unwrap();
+    let results = summary["results"].as_array().unwrap();
+    assert!(results.iter().all(|r| r["status"] == "duplicate"));
+    // Mock verifies on Drop that a single confirmation email has been sent to each address
+}
+
+#[tokio::test]
+async fn household_signups_count_towards_the_rate_limit() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.rate_limit_max_requests = 1;
+        c.subscriptions.rate_limit_window_seconds = 60;
+        // Redis is shared by every test: we do not want to count their submissions.
+        c.subscriptions.rate_limit_key_prefix = format!("test:rate:{}:", uuid::Uuid::new_v4());
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act
+    let body =
+        serde_urlencoded::to_string([("name", "le guin"), ("emails", "theodora@example.com")])
+            .unwrap();
+    let response = app.post_subscriptions_household(body).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+}
+
+#[tokio::test]
+async fn subscribe_returns_the_id_of_the_new_subscriber_to_json_clients() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept", "application/json")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "pending_confirmation");
+    let subscriber_id = uuid::Uuid::parse_str(body["id"].as_str().unwrap()).unwrap();
+    let saved = sqlx::query!(
+        "SELECT email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+}
+
+#[tokio::test]
+async fn subscribe_does_not_return_the_id_of_an_existing_subscriber() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.duplicate_window_seconds = 0).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let subscribe = || {
+        app.api_client
+            .post(format!("{}/subscriptions", &app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("Accept", "application/json")
+            .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+            .send()
+    };
+    subscribe().await.expect("Failed to execute request.");
+
+    // Act - Anybody can submit somebody else's email address
+    let response = subscribe().await.expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "pending_confirmation");
+    assert!(body.get("id").is_none());
+}
+
+#[tokio::test]
+async fn subscribe_stores_where_the_subscriber_came_from() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let body =
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&source=twitter&utm_campaign=spring-sale";
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT id, source, utm_campaign FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.source.as_deref(), Some("twitter"));
+    assert_eq!(saved.utm_campaign.as_deref(), Some("spring-sale"));
+
+    app.login().await;
+    let html_page = app.get_subscriber_details_html(saved.id).await;
+    assert!(html_page.contains("Source: twitter"));
+    assert!(html_page.contains("Campaign: spring-sale"));
+
+    // Only confirmed subscribers are counted.
+    let stats = app.get_subscriber_stats().await;
+    assert_eq!(stats["total"], 0);
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let stats = app.get_subscriber_stats().await;
+    assert_eq!(stats["total"], 1);
+    assert_eq!(
+        stats["by_source"],
+        serde_json::json!([
+            {"source": "twitter", "utm_campaign": "spring-sale", "subscribers": 1}
+        ])
+    );
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_400_when_the_source_is_too_long() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = format!(
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&source={}",
+        "a".repeat(101)
+    );
+
+    // Act
+    let response = app.post_subscriptions(body).await;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_stores_the_time_zone_of_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&time_zone=America%2FNew_York";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT time_zone FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.time_zone.as_deref(), Some("America/New_York"));
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_400_for_an_unknown_time_zone() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&time_zone=Mars%2FOlympus_Mons";
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+    let saved = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .expect("Failed to query the subscriptions.");
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn subscribing_above_the_rate_limit_gets_a_429_telling_when_to_retry() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.rate_limit_max_requests = 2;
+        c.subscriptions.rate_limit_window_seconds = 60;
+        // Redis is shared by every test: we do not want to count their submissions.
+        c.subscriptions.rate_limit_key_prefix = format!("test:rate:{}:", uuid::Uuid::new_v4());
+    })
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    for email in ["ursula_le_guin%40gmail.com", "octavia_butler%40gmail.com"] {
+        let response = app
+            .post_subscriptions(format!("name=le%20guin&email={email}"))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=n_k_jemisin%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response
+        .headers()
+        .get("Retry-After")
+        .expect("The Retry-After header is missing.")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after));
 }
@@ -1,6 +1,7 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{funnel_steps, spawn_app, spawn_app_with};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
+use zero2prod::configuration::WelcomeStep;
 
 #[tokio::test]
 async fn confirmations_without_token_are_rejected_with_a_400() {
@@ -31,7 +32,7 @@ async fn the_link_returned_by_subscribe_returns_a_200_if_called() {
     app.post_subscriptions(body.into()).await;
 
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     // Act
     let response = reqwest::get(confirmation_links.html).await.unwrap();
@@ -54,7 +55,7 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
 
     app.post_subscriptions(body.into()).await;
     let email_request = &app.email_server.received_requests().await.unwrap()[0];
-    let confirmation_links = app.get_confirmation_links(&email_request);
+    let confirmation_links = app.get_confirmation_links(email_request);
 
     // Act
     reqwest::get(confirmation_links.html)
@@ -73,3 +74,393 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
     assert_eq!(saved.name, "le guin");
     assert_eq!(saved.status, "confirmed");
 }
+
+#[tokio::test]
+async fn an_old_confirmation_link_does_not_resubscribe_an_unsubscribed_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "unsubscribed");
+}
+
+#[tokio::test]
+async fn an_unsubscribed_subscriber_can_subscribe_and_confirm_again() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.duplicate_window_seconds = 0).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "confirmed");
+}
+
+#[tokio::test]
+async fn resubscribing_before_confirming_resends_the_same_link() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.duplicate_window_seconds = 0).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let first_links = app.get_confirmation_links(&email_requests[0]);
+    let second_links = app.get_confirmation_links(&email_requests[1]);
+    assert_eq!(first_links.html, second_links.html);
+}
+
+#[tokio::test]
+async fn a_resend_racing_with_a_confirmation_leaves_a_consistent_state() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // Act - Resend and confirm with the first link concurrently
+    let resend = app.post_subscriptions(body.into());
+    let confirm = reqwest::get(confirmation_links.html.clone());
+    let (resend_response, confirm_response) = tokio::join!(resend, confirm);
+
+    // Assert
+    assert_eq!(resend_response.status().as_u16(), 200);
+    assert_eq!(confirm_response.unwrap().status().as_u16(), 200);
+
+    let subscribers = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions.");
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0].status, "confirmed");
+
+    let tokens = sqlx::query!("SELECT subscription_token FROM subscription_tokens")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch subscription tokens.");
+    assert_eq!(tokens.len(), 1);
+    // If the resend went out, it carried the same (and only) valid link.
+    for email_request in &app.email_server.received_requests().await.unwrap()[1..] {
+        assert_eq!(
+            app.get_confirmation_links(email_request).html,
+            confirmation_links.html
+        );
+    }
+}
+
+#[tokio::test]
+async fn following_the_confirmation_link_twice_sends_a_single_welcome_email() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.send_welcome_email = true).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // Act - Part 1 - The email client pre-fetches the link
+    let first_response = reqwest::get(confirmation_links.html.clone()).await.unwrap();
+    // Act - Part 2 - The subscriber clicks on it
+    let second_response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(first_response.status().as_u16(), 200);
+    assert!(first_response
+        .text()
+        .await
+        .unwrap()
+        .contains("Your subscription is confirmed"));
+    assert_eq!(second_response.status().as_u16(), 200);
+    assert!(second_response
+        .text()
+        .await
+        .unwrap()
+        .contains("Your subscription was already confirmed"));
+
+    let welcome_emails = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+        .filter(|body| body["Subject"] == "Your subscription is confirmed")
+        .count();
+    assert_eq!(welcome_emails, 1);
+}
+
+#[tokio::test]
+async fn confirming_a_subscriber_queues_each_step_of_the_welcome_series_once() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.subscriptions.welcome_series = vec![
+            WelcomeStep {
+                template: "welcome".into(),
+                subject: "Getting started".into(),
+                offset_hours: 24,
+            },
+            WelcomeStep {
+                template: "welcome".into(),
+                subject: "One week in".into(),
+                offset_hours: 168,
+            },
+        ]
+    })
+    .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    // Act - the link is followed twice, e.g. pre-fetched by the email client
+    let confirmed_at = chrono::Utc::now();
+    reqwest::get(confirmation_links.html.clone())
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let steps = sqlx::query!(
+        r#"SELECT step, execute_after, sent_at FROM welcome_series_queue ORDER BY step"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch the queued welcome series.");
+    assert_eq!(steps.len(), 2);
+    for (step, offset_hours) in steps.iter().zip([24, 168]) {
+        let offset = step.execute_after - confirmed_at;
+        assert!(
+            (offset - chrono::Duration::hours(offset_hours))
+                .num_seconds()
+                .abs()
+                < 60,
+            "Step {} is due {offset} after the confirmation.",
+            step.step
+        );
+        assert!(step.sent_at.is_none());
+    }
+}
+
+#[tokio::test]
+async fn welcome_series_steps_carry_the_newsletter_footer() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.newsletter.company_address = "Zero2Prod Ltd, 42 Test Road".into();
+        c.subscriptions.welcome_series = vec![WelcomeStep {
+            template: "welcome".into(),
+            subject: "Getting started".into(),
+            offset_hours: 0,
+        }]
+    })
+    .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    app.dispatch_all_welcome_steps().await;
+
+    // Assert
+    let step: serde_json::Value = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .find(|body: &serde_json::Value| body["Subject"] == "Getting started")
+        .expect("The welcome series step was not sent.");
+    for content in [&step["HtmlBody"], &step["TextBody"]] {
+        let content = content.as_str().unwrap();
+        assert!(content.contains("Zero2Prod Ltd, 42 Test Road"));
+        assert!(content.contains("/subscriptions/unsubscribe?subscription_token="));
+        assert!(content.contains("/subscriptions/preferences?token="));
+    }
+    let unsubscribe_link = format!(
+        "{}/subscriptions/unsubscribe?subscription_token=",
+        app.configuration.application.base_url
+    );
+    assert!(step["TextBody"]
+        .as_str()
+        .unwrap()
+        .lines()
+        .any(|line| line.starts_with(&unsubscribe_link)));
+}
+
+#[tokio::test]
+async fn each_step_of_the_conversion_funnel_is_logged_for_the_same_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the subscriber.")
+        .id;
+    assert_eq!(
+        funnel_steps(subscriber_id),
+        vec!["subscribed", "confirmation_sent", "confirmed"]
+    );
+}
+
+#[tokio::test]
+async fn subscribing_again_is_not_logged_as_a_new_subscription() {
+    // Arrange
+    let app = spawn_app_with(|c| c.subscriptions.duplicate_window_seconds = 0).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_subscriptions(body.into()).await;
+    app.post_subscriptions(body.into()).await;
+
+    // Assert
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch the subscriber.")
+        .id;
+    // The confirmation email is sent again, though.
+    assert_eq!(
+        funnel_steps(subscriber_id),
+        vec!["subscribed", "confirmation_sent", "confirmation_sent"]
+    );
+}
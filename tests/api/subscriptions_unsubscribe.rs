@@ -0,0 +1,187 @@
+use crate::helpers::spawn_app;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn unsubscribing_without_token_is_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(&format!("{}/subscriptions/unsubscribe", app.address))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn unsubscribing_with_an_unknown_token_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(&format!(
+        "{}/subscriptions/unsubscribe?subscription_token=unknown",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn following_the_unsubscribe_link_asks_for_confirmation_without_unsubscribing() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let mut unsubscribe_link = app.get_confirmation_links(email_request).html;
+    unsubscribe_link.set_path("/subscriptions/unsubscribe");
+
+    // Act - e.g. the link is pre-fetched by the email client
+    let response = reqwest::get(unsubscribe_link).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"<form action="/subscriptions/unsubscribe?subscription_token="#));
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn posting_an_unknown_token_is_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_unsubscribe("unknown").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn subscription_state_changes_are_recorded_in_order() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Subscribe, confirm (twice) and unsubscribe
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_link = app.get_confirmation_links(email_request).html;
+    for _ in 0..2 {
+        reqwest::get(confirmation_link.clone())
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+    let subscription_token = confirmation_link
+        .query_pairs()
+        .find(|(key, _)| key == "subscription_token")
+        .unwrap()
+        .1
+        .into_owned();
+    app.post_unsubscribe(&subscription_token)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let saved = sqlx::query!("SELECT id, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.status, "unsubscribed");
+
+    let events: Vec<(String, String)> = sqlx::query!(
+        "SELECT event_type, source FROM subscription_events WHERE subscriber_id = $1 ORDER BY event_id",
+        saved.id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch subscription events.")
+    .into_iter()
+    .map(|r| (r.event_type, r.source))
+    .collect();
+    assert_eq!(
+        events,
+        vec![
+            ("subscribed".into(), "subscription_form".into()),
+            ("confirmed".into(), "confirmation_link".into()),
+            ("unsubscribed".into(), "unsubscribe_link".into()),
+        ]
+    );
+
+    // The same timeline is shown on the subscriber detail page
+    app.login().await;
+    let html_page = app.get_subscriber_details_html(saved.id).await;
+    let subscribed = html_page.find("<td>subscribed</td>").unwrap();
+    let confirmed = html_page.find("<td>confirmed</td>").unwrap();
+    let unsubscribed = html_page.find("<td>unsubscribed</td>").unwrap();
+    assert!(subscribed < confirmed && confirmed < unsubscribed);
+    assert!(html_page.contains("Status: unsubscribed"));
+}
+
+#[tokio::test]
+async fn bounces_reported_by_postmark_are_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let event = serde_json::json!({
+        "RecordType": "Bounce",
+        "Email": "ursula_le_guin@gmail.com"
+    });
+    app.post_postmark_webhook(event.to_string(), "application/json")
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let last_event = sqlx::query!(
+        "SELECT event_type, source FROM subscription_events ORDER BY event_id DESC LIMIT 1"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch subscription events.");
+    assert_eq!(last_event.event_type, "bounced");
+    assert_eq!(last_event.source, "postmark_webhook");
+}
@@ -0,0 +1,28 @@
+use crate::helpers::spawn_app_with;
+use std::path::Path;
+
+#[tokio::test]
+async fn templates_are_loaded_from_the_configured_directory() {
+    // Arrange - A copy of our templates, with a login page of its own
+    let templates_dir = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&templates_dir).unwrap();
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("templates");
+    for template in std::fs::read_dir(source).unwrap() {
+        let template = template.unwrap();
+        std::fs::copy(template.path(), templates_dir.join(template.file_name())).unwrap();
+    }
+    std::fs::write(
+        templates_dir.join("login.html"),
+        "<p>Welcome to our own login page</p>",
+    )
+    .unwrap();
+    let templates_dir_path = templates_dir.to_str().unwrap().to_owned();
+    let app = spawn_app_with(|c| c.application.templates_dir = templates_dir_path).await;
+
+    // Act
+    let html_page = app.get_login_html().await;
+
+    // Assert
+    assert!(html_page.contains("<p>Welcome to our own login page</p>"));
+    std::fs::remove_dir_all(templates_dir).unwrap();
+}
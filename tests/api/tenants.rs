@@ -0,0 +1,299 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app_with, TestApp};
+use secrecy::Secret;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::configuration::TenantSettings;
+
+const API_KEY: &str = "a-partner-api-key";
+const TENANT_A_HOST: &str = "tenant-a.example.com";
+const TENANT_B_HOST: &str = "tenant-b.example.com";
+
+async fn spawn_app_with_two_tenants() -> TestApp {
+    spawn_app_with(|c| {
+        c.api.partner_api_keys = vec![Secret::new(API_KEY.into())];
+        c.tenants = [("a", TENANT_A_HOST), ("b", TENANT_B_HOST)]
+            .into_iter()
+            .map(|(id, host)| TenantSettings {
+                id: id.into(),
+                hosts: vec![host.into()],
+                base_url: format!("https://{host}"),
+            })
+            .collect();
+    })
+    .await
+}
+
+async fn get_subscriber_emails(app: &TestApp, host: &str) -> Vec<String> {
+    let body: serde_json::Value = app
+        .api_client
+        .get(format!("{}/api/subscribers", &app.address))
+        .header("Host", host)
+        .bearer_auth(API_KEY)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .json()
+        .await
+        .unwrap();
+    body["subscribers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["email"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn a_subscriber_of_one_tenant_is_invisible_to_another_tenant() {
+    // Arrange
+    let app = spawn_app_with_two_tenants().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Subscribe to tenant A
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Host", TENANT_A_HOST)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 2 - Confirm, following the link sent on behalf of tenant A
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let confirmation_link = linkify::LinkFinder::new()
+        .links(body["TextBody"].as_str().unwrap())
+        .map(|l| reqwest::Url::parse(l.as_str()).unwrap())
+        .next()
+        .unwrap();
+    assert_eq!(confirmation_link.host_str().unwrap(), TENANT_A_HOST);
+    let confirm = |host: &str| {
+        app.api_client
+            .get(format!(
+                "{}{}?{}",
+                &app.address,
+                confirmation_link.path(),
+                confirmation_link.query().unwrap()
+            ))
+            .header("Host", host)
+            .send()
+    };
+    // Tenant B does not know about tokens issued by tenant A.
+    assert_eq!(confirm(TENANT_B_HOST).await.unwrap().status().as_u16(), 401);
+    assert_eq!(confirm(TENANT_A_HOST).await.unwrap().status().as_u16(), 200);
+
+    // Assert
+    assert_eq!(
+        get_subscriber_emails(&app, TENANT_A_HOST).await,
+        vec!["ursula_le_guin@gmail.com"]
+    );
+    assert!(get_subscriber_emails(&app, TENANT_B_HOST).await.is_empty());
+    assert!(get_subscriber_emails(&app, "127.0.0.1").await.is_empty());
+}
+
+#[tokio::test]
+async fn the_same_email_can_subscribe_to_two_tenants_but_only_once_to_each() {
+    // Arrange
+    let app = spawn_app_with_two_tenants().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let subscribe = |host: &'static str| {
+        app.api_client
+            .post(format!("{}/subscriptions", &app.address))
+            .header("Host", host)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+            .send()
+    };
+
+    // Act
+    for host in [TENANT_A_HOST, TENANT_B_HOST, TENANT_A_HOST] {
+        assert_eq!(subscribe(host).await.unwrap().status().as_u16(), 200);
+    }
+
+    // Assert
+    let tenants = sqlx::query!(
+        "SELECT tenant_id FROM subscriptions WHERE email = 'ursula_le_guin@gmail.com' ORDER BY tenant_id"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved subscriptions.")
+    .into_iter()
+    .map(|r| r.tenant_id)
+    .collect::<Vec<_>>();
+    assert_eq!(tenants, vec!["a", "b"]);
+}
+
+#[tokio::test]
+async fn an_email_subscribed_concurrently_to_the_same_tenant_gets_a_409() {
+    // Arrange
+    let app = spawn_app_with_two_tenants().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    // Another subscription of the same address is in flight: not committed yet, so the endpoint
+    // does not find it and its insert waits for ours.
+    let mut concurrent = app.db_pool.begin().await.unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, tenant_id)
+        VALUES ($1, 'ursula_le_guin@gmail.com', 'le guin', now(), 'pending_confirmation', 'a')
+        "#,
+        uuid::Uuid::new_v4()
+    )
+    .execute(&mut concurrent)
+    .await
+    .unwrap();
+
+    // Act
+    let request = app
+        .api_client
+        .post(format!("{}/subscriptions", &app.address))
+        .header("Host", TENANT_A_HOST)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .send();
+    let response = tokio::spawn(request);
+    while !sqlx::query!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM pg_stat_activity
+            WHERE datname = current_database() AND wait_event_type = 'Lock'
+        ) AS "waiting!"
+        "#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .waiting
+    {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    concurrent.commit().await.unwrap();
+    let response = response.await.unwrap().unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 409);
+    let subscriptions = sqlx::query!(
+        "SELECT id FROM subscriptions WHERE email = 'ursula_le_guin@gmail.com' AND tenant_id = 'a'"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(subscriptions.len(), 1);
+}
+
+#[tokio::test]
+async fn a_newsletter_issue_is_only_delivered_to_the_subscribers_of_its_tenant() {
+    // Arrange
+    let app = spawn_app_with_two_tenants().await;
+    for (email, tenant_id) in [("a@example.com", "a"), ("b@example.com", "b")] {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, tenant_id)
+            VALUES ($1, $2, 'le guin', now(), 'confirmed', $3)
+            "#,
+            uuid::Uuid::new_v4(),
+            email,
+            tenant_id
+        )
+        .execute(&app.db_pool)
+        .await
+        .expect("Failed to insert a confirmed subscriber.");
+    }
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/newsletters", &app.address))
+        .header("Host", TENANT_A_HOST)
+        .form(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "a@example.com");
+    assert!(body["TextBody"].as_str().unwrap().contains(&format!(
+        "https://{TENANT_A_HOST}/subscriptions/preferences"
+    )));
+}
+
+#[tokio::test]
+async fn a_bounce_only_suppresses_the_subscription_of_its_tenant() {
+    // Arrange
+    let app = spawn_app_with_two_tenants().await;
+    for tenant_id in ["a", "b"] {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, tenant_id)
+            VALUES ($1, 'ursula_le_guin@gmail.com', 'le guin', now(), 'confirmed', $2)
+            "#,
+            uuid::Uuid::new_v4(),
+            tenant_id
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    let body = serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": "HardBounce",
+        "Email": "ursula_le_guin@gmail.com"
+    });
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/webhooks/postmark", &app.address))
+        .header("Host", TENANT_A_HOST)
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let statuses = sqlx::query!("SELECT tenant_id, status FROM subscriptions ORDER BY tenant_id")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.tenant_id, r.status))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        vec![
+            ("a".to_string(), "bounced".to_string()),
+            ("b".to_string(), "confirmed".to_string())
+        ]
+    );
+}
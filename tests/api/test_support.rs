@@ -0,0 +1,35 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use zero2prod::configuration::get_configuration;
+use zero2prod::test_support::create_database_from_template;
+
+#[tokio::test]
+async fn databases_created_from_the_template_are_fully_migrated() {
+    // Arrange
+    let mut configuration = get_configuration()
+        .expect("Failed to read configuration.")
+        .database;
+    let n_migrations = std::fs::read_dir("migrations")
+        .expect("Failed to read the migrations folder.")
+        .count() as i64;
+
+    for _ in 0..2 {
+        // Act
+        configuration.database_name = Uuid::new_v4().to_string();
+        create_database_from_template(&configuration)
+            .await
+            .expect("Failed to create the database from the template.");
+
+        // Assert
+        let pool = PgPool::connect_with(configuration.with_db())
+            .await
+            .expect("Failed to connect to Postgres.");
+        let applied =
+            sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM _sqlx_migrations WHERE success"#)
+                .fetch_one(&pool)
+                .await
+                .expect("Failed to count the applied migrations.")
+                .count;
+        assert_eq!(applied, n_migrations);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::helpers::{spawn_app, spawn_app_with, store_subscriber, TestApp};
+use secrecy::Secret;
+use zero2prod::configuration::WebhookBasicAuthSettings;
+
+async fn post_bounce(app: &TestApp, email: &str, bounce_type: &str) {
+    let body = serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": bounce_type,
+        "Email": email
+    });
+    let response = app
+        .post_postmark_webhook(body.to_string(), "application/json")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn subscriber_status(app: &TestApp, email: &str) -> String {
+    sqlx::query!("SELECT status FROM subscriptions WHERE email = $1", email)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.")
+        .status
+}
+
+#[tokio::test]
+async fn postmark_webhook_accepts_a_json_payload() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "RecordType": "Bounce",
+        "Email": "ursula_le_guin@gmail.com"
+    });
+
+    // Act
+    let response = app
+        .post_postmark_webhook(body.to_string(), "application/json")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn postmark_webhook_rejects_a_non_json_content_type_with_a_415() {
+    // Arrange
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("application/x-www-form-urlencoded", "RecordType=Bounce"),
+        ("text/plain", r#"{"RecordType": "Bounce"}"#),
+    ];
+
+    for (content_type, body) in test_cases {
+        // Act
+        let response = app.post_postmark_webhook(body.into(), content_type).await;
+
+        // Assert
+        assert_eq!(
+            415,
+            response.status().as_u16(),
+            "The API did not fail with 415 Unsupported Media Type when the content type was {}.",
+            content_type
+        );
+    }
+}
+
+#[tokio::test]
+async fn postmark_webhook_rejects_an_oversized_payload_with_a_413() {
+    // Arrange
+    let app = spawn_app().await;
+    // Well above the limit configured in `base.yaml`
+    let body = serde_json::json!({
+        "RecordType": "Bounce",
+        "Details": "a".repeat(1024 * 1024)
+    });
+
+    // Act
+    let response = app
+        .post_postmark_webhook(body.to_string(), "application/json")
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 413);
+}
+
+#[tokio::test]
+async fn subscribers_are_suppressed_once_they_reach_the_soft_bounce_threshold() {
+    // Arrange
+    let app = spawn_app_with(|c| c.webhooks.soft_bounce_threshold = 3).await;
+    let email = "ursula_le_guin@gmail.com";
+    store_subscriber(&app, email, "Ursula").await;
+
+    // Act - Part 1 - Stay below the threshold
+    for _ in 0..2 {
+        post_bounce(&app, email, "SoftBounce").await;
+    }
+    assert_eq!(subscriber_status(&app, email).await, "confirmed");
+
+    // Act - Part 2 - Reach it
+    post_bounce(&app, email, "SoftBounce").await;
+
+    // Assert
+    assert_eq!(subscriber_status(&app, email).await, "bounced");
+}
+
+#[tokio::test]
+async fn subscribers_are_suppressed_after_a_single_hard_bounce() {
+    // Arrange
+    let app = spawn_app().await;
+    let email = "ursula_le_guin@gmail.com";
+    store_subscriber(&app, email, "Ursula").await;
+
+    // Act
+    post_bounce(&app, email, "HardBounce").await;
+
+    // Assert
+    assert_eq!(subscriber_status(&app, email).await, "bounced");
+}
+
+#[tokio::test]
+async fn webhooks_must_carry_the_configured_credentials() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.webhooks.basic_auth = Some(WebhookBasicAuthSettings {
+            username: "postmark".into(),
+            password: Secret::new("a-long-random-password".into()),
+        })
+    })
+    .await;
+    let email = "ursula_le_guin@gmail.com";
+    store_subscriber(&app, email, "Ursula").await;
+    let post_bounce_as = |password: Option<&str>| {
+        let body = serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "HardBounce",
+            "Email": email
+        });
+        let request = app
+            .api_client
+            .post(format!("{}/webhooks/postmark", &app.address))
+            .json(&body);
+        let request = match password {
+            Some(password) => request.basic_auth("postmark", Some(password)),
+            None => request,
+        };
+        request.send()
+    };
+
+    // Act - Part 1 - Missing or invalid credentials
+    for password in [None, Some("a-wrong-password")] {
+        let response = post_bounce_as(password).await.unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(subscriber_status(&app, email).await, "confirmed");
+    }
+
+    // Act - Part 2 - Valid credentials
+    let response = post_bounce_as(Some("a-long-random-password"))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_status(&app, email).await, "bounced");
+}